@@ -1,15 +1,38 @@
+use std::sync::Arc;
+
+/// Bit-packed array of values, storing each element as an index into a lookup
+/// table of its distinct values.
+///
+/// The packed elements and lookup table are shared between clones and only copied
+/// on the next write, so cloning (or taking a `snapshot`) is cheap.
 pub struct Palette<T: Ord> {
-    lookup: Vec<T>,
+    lookup: Arc<Vec<T>>,
+    ref_counts: Arc<Vec<usize>>,
+    live_entries: usize,
     index_bits: usize,
     index_mask: usize,
-    indexes_per_slice: usize,
-    elements: Vec<usize>,
+    elements: Arc<Vec<usize>>,
     len: usize,
 }
 
 impl<T: Ord> Palette<T> {
     const MAX_INDEX_BITS: usize = usize::MAX.count_ones() as usize;
 
+    /// Creates a new palette of `len` elements, each initialized to `default`.
+    pub fn new(len: usize, default: T) -> Self {
+        let index_bits = Self::compute_index_bits(1);
+
+        Self {
+            lookup: Arc::new(vec![default]),
+            ref_counts: Arc::new(vec![len]),
+            live_entries: 1,
+            index_bits,
+            index_mask: Self::compute_mask(index_bits),
+            elements: Arc::new(vec![0usize; Self::compute_slices(index_bits, len)]),
+            len,
+        }
+    }
+
     // In a very verbose fashion, converts the total length of the palette array into
     //  a bit length (the bit length of each entry added together), and then
     //  determines how many `usize`s are required to contain that.
@@ -17,13 +40,225 @@ impl<T: Ord> Palette<T> {
         ((index_bits * array_len) + (Self::MAX_INDEX_BITS - 1)) / Self::MAX_INDEX_BITS
     }
 
-    fn set_value(&mut self, index: usize, lookup_index: usize) {
-        let palette_index = (index * self.index_bits) / Self::MAX_INDEX_BITS;
-        let slice_offset = (index - (palette_index * self.indexes_per_slice)) * self.index_bits;
-        self.elements[palette_index] = (self.elements[palette_index]
-            & !(self.index_mask << slice_offset))
+    // Computes the smallest bit width able to address `lookup_len` entries. Widths
+    //  are not restricted to powers of two, so indexes may straddle two slices.
+    const fn compute_index_bits(lookup_len: usize) -> usize {
+        let index_bits =
+            (Self::MAX_INDEX_BITS as u32 - lookup_len.saturating_sub(1).leading_zeros()) as usize;
+
+        if index_bits == 0 {
+            1
+        } else {
+            index_bits
+        }
+    }
+
+    const fn compute_mask(index_bits: usize) -> usize {
+        usize::MAX >> (Self::MAX_INDEX_BITS - index_bits)
+    }
+
+    #[inline(always)]
+    fn read_packed(
+        elements: &[usize],
+        index_bits: usize,
+        index_mask: usize,
+        index: usize,
+    ) -> usize {
+        let bit_index = index * index_bits;
+        let palette_index = bit_index / Self::MAX_INDEX_BITS;
+        let slice_offset = bit_index % Self::MAX_INDEX_BITS;
+
+        let mut lookup_index = elements[palette_index] >> slice_offset;
+        // The index straddles two slices, so pull the remaining high bits from the next.
+        if (slice_offset + index_bits) > Self::MAX_INDEX_BITS {
+            lookup_index |= elements[palette_index + 1] << (Self::MAX_INDEX_BITS - slice_offset);
+        }
+
+        lookup_index & index_mask
+    }
+
+    #[inline(always)]
+    fn write_packed(
+        elements: &mut [usize],
+        index_bits: usize,
+        index_mask: usize,
+        index: usize,
+        lookup_index: usize,
+    ) {
+        let bit_index = index * index_bits;
+        let palette_index = bit_index / Self::MAX_INDEX_BITS;
+        let slice_offset = bit_index % Self::MAX_INDEX_BITS;
+
+        elements[palette_index] = (elements[palette_index] & !(index_mask << slice_offset))
             | (lookup_index << slice_offset);
 
+        if (slice_offset + index_bits) > Self::MAX_INDEX_BITS {
+            let high_shift = Self::MAX_INDEX_BITS - slice_offset;
+            elements[palette_index + 1] = (elements[palette_index + 1]
+                & !(index_mask >> high_shift))
+                | (lookup_index >> high_shift);
+        }
+    }
+
+    #[inline(always)]
+    fn calculate_lookup_from_index(&self, index: usize) -> usize {
+        Self::read_packed(&self.elements, self.index_bits, self.index_mask, index)
+    }
+
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn lookup_len(&self) -> usize {
+        self.lookup.len()
+    }
+
+    /// Count of lookup entries referenced by at least one element.
+    pub fn live_lookup_len(&self) -> usize {
+        self.live_entries
+    }
+
+    pub fn index_bits(&self) -> usize {
+        self.index_bits
+    }
+
+    pub fn get(&self, index: usize) -> &T {
+        &self.lookup[self.calculate_lookup_from_index(index)]
+    }
+
+    pub fn get_lookup_value(&self, index: usize) -> &T {
+        &self.lookup[index]
+    }
+
+    /// Returns an immutable view of the palette's current contents, sharing its
+    /// backing memory until the palette is next written to.
+    pub fn snapshot(&self) -> PaletteSnapshot<T> {
+        PaletteSnapshot(self.clone())
+    }
+
+    /// Sequentially decodes the lookup indexes of the elements within `range`.
+    pub fn packed_indexes(&self, range: std::ops::Range<usize>) -> PackedIndexes<'_> {
+        let bit_index = range.start * self.index_bits;
+
+        PackedIndexes {
+            elements: &self.elements,
+            index_bits: self.index_bits,
+            index_mask: self.index_mask,
+            palette_index: bit_index / Self::MAX_INDEX_BITS,
+            slice_offset: bit_index % Self::MAX_INDEX_BITS,
+            remaining: range.len(),
+        }
+    }
+
+    /// Returns an iterator over the runs of consecutive equal elements, yielding
+    /// the index range of each run alongside its value.
+    pub fn runs(&self) -> PaletteRuns<'_, T> {
+        PaletteRuns {
+            palette: self,
+            indexes: self.packed_indexes(0..self.len()),
+            index: 0,
+            next_lookup_index: None,
+        }
+    }
+}
+
+/// Iterator over the lookup indexes of a range of `Palette` elements.
+//
+// Walks the packed slices sequentially, carrying the bit offset forward rather
+//  than recomputing it per element as `get` does.
+pub struct PackedIndexes<'a> {
+    elements: &'a [usize],
+    index_bits: usize,
+    index_mask: usize,
+    palette_index: usize,
+    slice_offset: usize,
+    remaining: usize,
+}
+
+impl Iterator for PackedIndexes<'_> {
+    type Item = usize;
+
+    #[inline(always)]
+    fn next(&mut self) -> Option<Self::Item> {
+        const MAX_INDEX_BITS: usize = usize::MAX.count_ones() as usize;
+
+        if self.remaining == 0 {
+            return None;
+        }
+
+        self.remaining -= 1;
+
+        let mut lookup_index = self.elements[self.palette_index] >> self.slice_offset;
+        self.slice_offset += self.index_bits;
+
+        if self.slice_offset >= MAX_INDEX_BITS {
+            self.palette_index += 1;
+            self.slice_offset -= MAX_INDEX_BITS;
+
+            // The index straddled the slice boundary, so append its high bits.
+            if self.slice_offset > 0 {
+                lookup_index |=
+                    self.elements[self.palette_index] << (self.index_bits - self.slice_offset);
+            }
+        }
+
+        Some(lookup_index & self.index_mask)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+/// Iterator over the runs of consecutive equal elements in a `Palette`.
+pub struct PaletteRuns<'a, T: Ord> {
+    palette: &'a Palette<T>,
+    indexes: PackedIndexes<'a>,
+    index: usize,
+    next_lookup_index: Option<usize>,
+}
+
+impl<'a, T: Ord> Iterator for PaletteRuns<'a, T> {
+    type Item = (std::ops::Range<usize>, &'a T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let lookup_index = self
+            .next_lookup_index
+            .take()
+            .or_else(|| self.indexes.next())?;
+        let start = self.index;
+        self.index += 1;
+
+        // Extend the run until the lookup index changes.
+        loop {
+            match self.indexes.next() {
+                Some(next_lookup_index) if next_lookup_index == lookup_index => self.index += 1,
+                next_lookup_index => {
+                    self.next_lookup_index = next_lookup_index;
+                    break;
+                }
+            }
+        }
+
+        Some((
+            start..self.index,
+            self.palette.get_lookup_value(lookup_index),
+        ))
+    }
+}
+
+impl<T: Ord + Clone> Palette<T> {
+    fn set_value(&mut self, index: usize, lookup_index: usize) {
+        // Writing detaches the elements from any outstanding snapshots.
+        let elements: &mut Vec<usize> = Arc::make_mut(&mut self.elements);
+        Self::write_packed(
+            elements,
+            self.index_bits,
+            self.index_mask,
+            index,
+            lookup_index,
+        );
+
         debug_assert_eq!(
             self.calculate_lookup_from_index(index),
             lookup_index,
@@ -31,102 +266,337 @@ impl<T: Ord> Palette<T> {
         );
     }
 
-    fn calculate_lookup_from_index(&self, index: usize) -> usize {
-        let palette_index = (index * self.index_bits) / Self::MAX_INDEX_BITS;
-        let slice_offset = (index - (palette_index * self.indexes_per_slice)) * self.index_bits;
-
-        (self.elements[palette_index] & (self.index_mask << slice_offset)) >> slice_offset
-    }
-
     fn allocate_lookup_entry(&mut self, entry: T) -> usize {
-        assert!(
+        debug_assert!(
             !self.lookup.contains(&entry),
             "lookup entry already present"
         );
 
-        let entry_index = self.lookup.len();
-        self.lookup.push(entry);
+        self.live_entries += 1;
 
-        // Ensure we can fit the new index bits.
-        if self.index_bits == Self::MAX_INDEX_BITS {
-            panic!(
-                "palette cannot contain more than {:?} entries",
-                Self::MAX_INDEX_BITS
-            );
-        // Check if the index mask needs to be recalculated.
-        } else if self.lookup.len() > self.index_mask {
-            let new_index_bits = self.index_bits << 1;
-            let new_indexes_per_slice = Self::MAX_INDEX_BITS / new_index_bits;
-            let mut palette = vec![0usize; Self::compute_slices(new_index_bits, self.len())];
-
-            // Iterate the new palette, and copy the old palette values.
-            let mut index: usize = 0;
-            for slice in palette.iter_mut() {
-                // Temporary slice value.
-                let mut new_slice = 0;
-
-                // Iterate each lookup index in the slice.
-                for slice_index in 0..new_indexes_per_slice {
-                    // Find the current index's lookup index, and if it isn't the
-                    //  default value, copy it into the new slice.
-                    let lookup_index = self.calculate_lookup_from_index(index);
-                    if lookup_index > 0 {
-                        new_slice |= lookup_index << (slice_index * new_index_bits);
-                    }
-
-                    // Ensure we increment the index, to keep current.
-                    index += 1;
-                }
+        // Reuse a dead entry if one exists, as it requires no change in index width.
+        if let Some(entry_index) = self.ref_counts.iter().position(|count| *count == 0) {
+            Arc::make_mut(&mut self.lookup)[entry_index] = entry;
+            return entry_index;
+        }
 
-                // If our new slice isn't all default values, copy it to the palette.
-                if new_slice > 0 {
-                    *slice = new_slice;
-                }
-            }
+        let entry_index = self.lookup.len();
+        Arc::make_mut(&mut self.lookup).push(entry);
+        Arc::make_mut(&mut self.ref_counts).push(0);
 
-            // Repalce our palette data with the newly expanded palette.
-            self.index_bits = new_index_bits;
-            self.index_mask = Self::compute_mask(new_index_bits);
-            self.indexes_per_slice = new_indexes_per_slice;
-            self.elements = palette;
+        // Ensure we can fit the new index bits.
+        let new_index_bits = Self::compute_index_bits(self.lookup.len());
+        if new_index_bits > self.index_bits {
+            self.repack(new_index_bits, |lookup_index| lookup_index);
         }
 
         entry_index
     }
 
-    fn compute_mask(index_bits: usize) -> usize {
-        let mut new_mask = 0;
+    /// Re-encodes every element with `new_index_bits` wide indexes, mapping each
+    /// lookup index through `remap` as it is copied.
+    fn repack(&mut self, new_index_bits: usize, remap: impl Fn(usize) -> usize) {
+        let new_index_mask = Self::compute_mask(new_index_bits);
+        let mut palette = vec![0usize; Self::compute_slices(new_index_bits, self.len())];
 
-        for bit in 0..index_bits {
-            new_mask |= 1 << bit;
+        for index in 0..self.len() {
+            // Default values are already zeroed, so only copy the rest.
+            let lookup_index = remap(self.calculate_lookup_from_index(index));
+            if lookup_index > 0 {
+                Self::write_packed(
+                    &mut palette,
+                    new_index_bits,
+                    new_index_mask,
+                    index,
+                    lookup_index,
+                );
+            }
         }
 
-        new_mask
+        // Replace our palette data with the newly encoded palette.
+        self.index_bits = new_index_bits;
+        self.index_mask = new_index_mask;
+        self.elements = Arc::new(palette);
     }
 
-    pub const fn len(&self) -> usize {
-        self.len
+    /// Removes all lookup entries which are no longer referenced by any element,
+    /// and narrows the index width to the smallest that fits the remaining entries.
+    pub fn compact(&mut self) {
+        if self.live_entries == self.lookup.len() {
+            return;
+        }
+
+        // Build a map of old lookup indexes to their compacted positions.
+        let mut remap = vec![0usize; self.lookup.len()];
+        let mut next_index = 0;
+        for (lookup_index, ref_count) in self.ref_counts.iter().enumerate() {
+            if *ref_count > 0 {
+                remap[lookup_index] = next_index;
+                next_index += 1;
+            }
+        }
+
+        let new_index_bits = Self::compute_index_bits(self.live_entries);
+        self.repack(new_index_bits, |lookup_index| remap[lookup_index]);
+
+        let ref_counts = Arc::make_mut(&mut self.ref_counts);
+        let mut lookup_index = 0;
+        Arc::make_mut(&mut self.lookup).retain(|_| {
+            lookup_index += 1;
+            ref_counts[lookup_index - 1] > 0
+        });
+        ref_counts.retain(|ref_count| *ref_count > 0);
     }
 
-    pub fn lookup_len(&self) -> usize {
-        self.lookup.len()
+    // Automatic compaction is deferred until the live entries would fit in an index
+    //  width two bits narrower than the current one, so that a palette hovering
+    //  around a width boundary doesn't repack on every `set`.
+    fn should_compact(&self) -> bool {
+        (Self::compute_index_bits(self.live_entries) + 2) <= self.index_bits
     }
 
-    pub fn get(&self, index: usize) -> &T {
-        &self.lookup[self.calculate_lookup_from_index(index)]
+    // Finds the lookup index of `value`, allocating an entry for it if necessary.
+    fn acquire_lookup_entry(&mut self, value: T) -> usize {
+        match self.lookup.iter().position(|entry| *entry == value) {
+            Some(lookup_index) => {
+                // A dead entry holding the same value is simply revived.
+                if self.ref_counts[lookup_index] == 0 {
+                    self.live_entries += 1;
+                }
+
+                lookup_index
+            }
+            None => self.allocate_lookup_entry(value),
+        }
     }
 
     pub fn set(&mut self, index: usize, value: T) {
-        let lookup_index = self
-            .lookup
-            .binary_search(&value)
-            .unwrap_or_else(|_| self.allocate_lookup_entry(value));
+        let old_lookup_index = self.calculate_lookup_from_index(index);
+        if self.lookup[old_lookup_index] == value {
+            return;
+        }
 
+        let lookup_index = self.acquire_lookup_entry(value);
         self.set_value(index, lookup_index);
+        let ref_counts = Arc::make_mut(&mut self.ref_counts);
+        ref_counts[lookup_index] += 1;
+        ref_counts[old_lookup_index] -= 1;
+
+        if ref_counts[old_lookup_index] == 0 {
+            self.live_entries -= 1;
+
+            if self.should_compact() {
+                self.compact();
+            }
+        }
     }
 
-    pub fn get_lookup_value(&self, index: usize) -> &T {
-        &self.lookup[index]
+    /// Sets every element of the palette to `value`, discarding all other lookup entries.
+    pub fn fill(&mut self, value: T) {
+        *self = Self::new(self.len(), value);
+    }
+
+    /// Sets every element within `range` to `value`.
+    ///
+    /// Elements are written a whole slice at a time, so the cost of the write is
+    /// proportional to the count of slices the range spans.
+    pub fn fill_range(&mut self, range: std::ops::Range<usize>, value: T) {
+        assert!(
+            range.end <= self.len(),
+            "Range must be contained within the palette."
+        );
+
+        if range.is_empty() {
+            return;
+        } else if range.len() == self.len() {
+            self.fill(value);
+            return;
+        }
+
+        // When only a single entry is live, every overwritten element is known to
+        //  reference it, and the range doesn't need to be decoded.
+        let uniform_lookup_index = if self.live_entries == 1 {
+            self.ref_counts.iter().position(|ref_count| *ref_count > 0)
+        } else {
+            None
+        };
+
+        let lookup_index = self.acquire_lookup_entry(value);
+
+        // Tally the references being released by the overwritten elements.
+        let mut released = vec![0usize; self.lookup.len()];
+        match uniform_lookup_index {
+            Some(old_lookup_index) => released[old_lookup_index] = range.len(),
+            None => {
+                for old_lookup_index in self.packed_indexes(range.clone()) {
+                    released[old_lookup_index] += 1;
+                }
+            }
+        }
+
+        let elements: &mut Vec<usize> = Arc::make_mut(&mut self.elements);
+        Self::fill_packed(elements, self.index_bits, range.clone(), lookup_index);
+
+        let ref_counts = Arc::make_mut(&mut self.ref_counts);
+        ref_counts[lookup_index] += range.len();
+        for (old_lookup_index, released_count) in released.into_iter().enumerate() {
+            if released_count > 0 {
+                ref_counts[old_lookup_index] -= released_count;
+
+                if ref_counts[old_lookup_index] == 0 {
+                    self.live_entries -= 1;
+                }
+            }
+        }
+
+        if self.should_compact() {
+            self.compact();
+        }
+    }
+
+    // Writes `lookup_index` to every element in `range`, masking in a repeating
+    //  pattern of the index one whole slice at a time.
+    fn fill_packed(
+        elements: &mut [usize],
+        index_bits: usize,
+        range: std::ops::Range<usize>,
+        lookup_index: usize,
+    ) {
+        // The index repeated end-to-end; wide enough that any window of
+        //  `MAX_INDEX_BITS` bits beginning within the first repetition is valid.
+        let mut pattern = 0u128;
+        let mut pattern_offset = 0;
+        while pattern_offset < 128 {
+            pattern |= (lookup_index as u128) << pattern_offset;
+            pattern_offset += index_bits;
+        }
+
+        let end_bit_index = range.end * index_bits;
+        let mut bit_index = range.start * index_bits;
+        while bit_index < end_bit_index {
+            let palette_index = bit_index / Self::MAX_INDEX_BITS;
+            let slice_start = palette_index * Self::MAX_INDEX_BITS;
+            let low_bit = bit_index - slice_start;
+            let high_bit = (end_bit_index - slice_start).min(Self::MAX_INDEX_BITS);
+
+            let mask = (usize::MAX >> (Self::MAX_INDEX_BITS - (high_bit - low_bit))) << low_bit;
+            // Align the pattern so its repetitions line up with this slice's index boundaries.
+            let slice_pattern = (pattern >> (slice_start % index_bits)) as usize;
+
+            elements[palette_index] = (elements[palette_index] & !mask) | (slice_pattern & mask);
+            bit_index = slice_start + Self::MAX_INDEX_BITS;
+        }
+    }
+
+    /// Replaces the contents of the palette with the contents of `slice`,
+    /// rebuilding the lookup table and packing the elements in a single pass.
+    pub fn copy_from_slice(&mut self, slice: &[T]) {
+        assert_eq!(
+            slice.len(),
+            self.len(),
+            "Slice must be the same length as the palette."
+        );
+
+        let mut lookup = Vec::<T>::new();
+        for value in slice.iter() {
+            if let Err(insert_index) = lookup.binary_search(value) {
+                lookup.insert(insert_index, value.clone());
+            }
+        }
+
+        let index_bits = Self::compute_index_bits(lookup.len());
+        let (elements, ref_counts) = Self::pack_indexes(
+            index_bits,
+            self.len(),
+            lookup.len(),
+            slice
+                .iter()
+                .map(|value| lookup.binary_search(value).unwrap()),
+        );
+
+        self.live_entries = lookup.len();
+        self.lookup = Arc::new(lookup);
+        self.ref_counts = Arc::new(ref_counts);
+        self.index_bits = index_bits;
+        self.index_mask = Self::compute_mask(index_bits);
+        self.elements = Arc::new(elements);
+    }
+
+    /// Creates a palette of `len` elements from a lookup table and the lookup index
+    /// of each element, in order.
+    ///
+    /// Lookup entries which aren't referenced by any element are kept, and can be
+    /// removed with `compact`.
+    pub fn from_lookup_indexes(
+        len: usize,
+        lookup: Vec<T>,
+        indexes: impl IntoIterator<Item = usize>,
+    ) -> Self {
+        assert!(
+            !lookup.is_empty(),
+            "Lookup must contain at least one entry."
+        );
+
+        let index_bits = Self::compute_index_bits(lookup.len());
+        let (elements, ref_counts) =
+            Self::pack_indexes(index_bits, len, lookup.len(), indexes.into_iter());
+
+        Self {
+            live_entries: ref_counts
+                .iter()
+                .filter(|ref_count| **ref_count > 0)
+                .count(),
+            lookup: Arc::new(lookup),
+            ref_counts: Arc::new(ref_counts),
+            index_bits,
+            index_mask: Self::compute_mask(index_bits),
+            elements: Arc::new(elements),
+            len,
+        }
+    }
+
+    // Packs `len` lookup indexes sequentially, carrying the bit offset forward, and
+    //  returns the packed slices alongside the reference count of each lookup entry.
+    fn pack_indexes(
+        index_bits: usize,
+        len: usize,
+        lookup_len: usize,
+        indexes: impl Iterator<Item = usize>,
+    ) -> (Vec<usize>, Vec<usize>) {
+        let mut ref_counts = vec![0usize; lookup_len];
+        let mut elements = vec![0usize; Self::compute_slices(index_bits, len)];
+
+        let mut palette_index = 0;
+        let mut slice_offset = 0;
+        let mut packed_len = 0;
+        for lookup_index in indexes.take(len) {
+            assert!(
+                lookup_index < lookup_len,
+                "Lookup index {} is out of range for a lookup of {} entries.",
+                lookup_index,
+                lookup_len
+            );
+
+            ref_counts[lookup_index] += 1;
+            packed_len += 1;
+
+            elements[palette_index] |= lookup_index << slice_offset;
+            slice_offset += index_bits;
+
+            if slice_offset >= Self::MAX_INDEX_BITS {
+                palette_index += 1;
+                slice_offset -= Self::MAX_INDEX_BITS;
+
+                // The index straddled the slice boundary, so carry its high bits over.
+                if slice_offset > 0 {
+                    elements[palette_index] |= lookup_index >> (index_bits - slice_offset);
+                }
+            }
+        }
+
+        assert_eq!(packed_len, len, "Too few lookup indexes were provided.");
+
+        (elements, ref_counts)
     }
 }
 
@@ -137,19 +607,208 @@ impl<T: Ord + Copy> Palette<T> {
             "Slice must be large enought to accomodate palette contents."
         );
 
-        let index = 0;
-        'elements: for element_index in 0..self.len() {
-            let element = self.elements[element_index];
+        for (value, lookup_index) in slice[..self.len()]
+            .iter_mut()
+            .zip(self.packed_indexes(0..self.len()))
+        {
+            *value = self.lookup[lookup_index];
+        }
+    }
+}
+
+impl<T: Ord> Clone for Palette<T> {
+    fn clone(&self) -> Self {
+        Self {
+            lookup: Arc::clone(&self.lookup),
+            ref_counts: Arc::clone(&self.ref_counts),
+            live_entries: self.live_entries,
+            index_bits: self.index_bits,
+            index_mask: self.index_mask,
+            elements: Arc::clone(&self.elements),
+            len: self.len,
+        }
+    }
+}
+
+/// Immutable, cheaply cloneable view of a `Palette` at the time it was taken.
+///
+/// Snapshots are `Send` and `Sync` whenever `T` is, so they can be handed to
+/// worker jobs while the source palette continues to be edited.
+pub struct PaletteSnapshot<T: Ord>(Palette<T>);
+
+impl<T: Ord> Clone for PaletteSnapshot<T> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<T: Ord> std::ops::Deref for PaletteSnapshot<T> {
+    type Target = Palette<T>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type TestPalette = Palette<u32>;
+
+    const LEN: usize = 300;
+
+    // Deterministic pseudo-random lookup indexes, each `index_bits` wide.
+    fn random_indexes(index_bits: usize, seed: u64) -> Vec<usize> {
+        let mut state = seed | 1;
+
+        (0..LEN)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                (state as usize) & TestPalette::compute_mask(index_bits)
+            })
+            .collect()
+    }
+
+    fn packed(index_bits: usize, indexes: &[usize]) -> Vec<usize> {
+        let mut elements = vec![0usize; TestPalette::compute_slices(index_bits, indexes.len())];
+        for (index, lookup_index) in indexes.iter().enumerate() {
+            TestPalette::write_packed(
+                &mut elements,
+                index_bits,
+                TestPalette::compute_mask(index_bits),
+                index,
+                *lookup_index,
+            );
+        }
 
-            for bit_shift in (0..(usize::MAX.count_ones())).step_by(self.index_bits) {
-                if index >= self.len() {
-                    break 'elements;
+        elements
+    }
+
+    fn unpacked(index_bits: usize, elements: &[usize]) -> Vec<usize> {
+        PackedIndexes {
+            elements,
+            index_bits,
+            index_mask: TestPalette::compute_mask(index_bits),
+            palette_index: 0,
+            slice_offset: 0,
+            remaining: LEN,
+        }
+        .collect()
+    }
+
+    #[test]
+    fn packed_indexes_round_trip_at_every_width() {
+        for index_bits in 1..=TestPalette::MAX_INDEX_BITS {
+            let index_mask = TestPalette::compute_mask(index_bits);
+            let mut expected = random_indexes(index_bits, index_bits as u64);
+            let mut elements = packed(index_bits, &expected);
+
+            for (index, lookup_index) in expected.iter().enumerate() {
+                assert_eq!(
+                    TestPalette::read_packed(&elements, index_bits, index_mask, index),
+                    *lookup_index,
+                    "index {} at {} bits",
+                    index,
+                    index_bits
+                );
+            }
+            assert_eq!(unpacked(index_bits, &elements), expected);
+
+            // Overwriting elements must leave their neighbors' bits intact.
+            for (index, lookup_index) in random_indexes(index_bits, !(index_bits as u64))
+                .into_iter()
+                .enumerate()
+                .step_by(3)
+            {
+                TestPalette::write_packed(
+                    &mut elements,
+                    index_bits,
+                    index_mask,
+                    index,
+                    lookup_index,
+                );
+                expected[index] = lookup_index;
+            }
+            assert_eq!(unpacked(index_bits, &elements), expected);
+
+            if index_bits <= 12 {
+                let (sequential, _) = TestPalette::pack_indexes(
+                    index_bits,
+                    LEN,
+                    1 << index_bits,
+                    expected.iter().copied(),
+                );
+                assert_eq!(
+                    sequential, elements,
+                    "sequential packing at {} bits",
+                    index_bits
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn fill_packed_matches_element_writes_at_every_width() {
+        let ranges = [
+            0..1,
+            0..LEN,
+            3..(LEN - 5),
+            63..64,
+            64..129,
+            100..101,
+            150..150,
+        ];
+
+        for index_bits in 1..=TestPalette::MAX_INDEX_BITS {
+            let mut expected = random_indexes(index_bits, index_bits as u64);
+            let mut elements = packed(index_bits, &expected);
+
+            for (fill, range) in ranges.iter().enumerate() {
+                // Alternate between all ones and a varied index.
+                let lookup_index = if fill % 2 == 0 {
+                    TestPalette::compute_mask(index_bits)
                 } else {
-                    slice[index] = self
-                        .get_lookup_value((element >> bit_shift) & self.index_mask)
-                        .clone();
-                }
+                    random_indexes(index_bits, fill as u64)[0]
+                };
+
+                TestPalette::fill_packed(&mut elements, index_bits, range.clone(), lookup_index);
+                expected[range.clone()].fill(lookup_index);
+                assert_eq!(
+                    unpacked(index_bits, &elements),
+                    expected,
+                    "filling {:?} at {} bits",
+                    range,
+                    index_bits
+                );
             }
         }
     }
+
+    #[test]
+    fn index_width_follows_distinct_values() {
+        let mut palette = TestPalette::new(LEN, 0);
+        assert_eq!(palette.index_bits(), 1);
+
+        for index in 0..LEN {
+            palette.set(index, index as u32);
+        }
+        assert_eq!(palette.index_bits(), 9);
+        assert!((0..LEN).all(|index| *palette.get(index) == index as u32));
+
+        palette.fill_range(0..(LEN - 2), 7);
+        assert_eq!(palette.live_lookup_len(), 3);
+        assert_eq!(palette.index_bits(), 2);
+        assert_eq!(*palette.get(0), 7);
+        assert_eq!(*palette.get(LEN - 1), (LEN - 1) as u32);
+
+        let mut slice = vec![0; LEN];
+        palette.copy_to_slice(&mut slice);
+        assert_eq!(
+            &slice[(LEN - 3)..],
+            &[7, (LEN - 2) as u32, (LEN - 1) as u32]
+        );
+    }
 }
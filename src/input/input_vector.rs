@@ -8,28 +8,40 @@ pub struct InputVector {
     pub sensitivity: f32,
 }
 
+/// Moves entities by their `InputVector`, relative to the direction they face.
+///
+/// Entities with a `Velocity` walk, having their horizontal velocity set so that
+/// physics can collide them with the world. Other entities fly, moving directly.
 pub struct InputVectorTranslationSystem;
 impl<'a> specs::System<'a> for InputVectorTranslationSystem {
     type SystemData = (
         specs::ReadExpect<'a, crate::time::DeltaTime>,
         specs::ReadStorage<'a, InputVector>,
         specs::WriteStorage<'a, crate::world::Transform>,
+        specs::WriteStorage<'a, crate::world::physics::Velocity>,
     );
 
-    fn run(&mut self, (delta, input, mut transform): Self::SystemData) {
+    fn run(&mut self, (delta, input, mut transform, mut velocity): Self::SystemData) {
         use specs::Join;
 
-        for (input, transform) in (&input, &mut transform).join() {
-            if input.keyb == Vec2::ZERO {
-                continue;
-            } else {
-                let delta_sensitivity = delta.0.as_secs_f32() * input.sensitivity;
+        for (input, transform, maybe_velocity) in
+            (&input, &mut transform, (&mut velocity).maybe()).join()
+        {
+            let direction =
+                transform
+                    .rot
+                    .mul_vec3(-glam::Vec3::new(input.keyb.x, 0.0, input.keyb.y));
 
-                transform.pos += transform.rot.mul_vec3(-glam::Vec3::new(
-                    delta_sensitivity * input.keyb.x,
-                    0.0,
-                    delta_sensitivity * input.keyb.y,
-                ));
+            if let Some(velocity) = maybe_velocity {
+                // Walk along the ground, no matter how far up or down the entity faces.
+                let horizontal = glam::Vec3::new(direction.x, 0.0, direction.z).normalize_or_zero()
+                    * input.keyb.length().min(1.0)
+                    * input.sensitivity;
+
+                velocity.0.x = horizontal.x;
+                velocity.0.z = horizontal.z;
+            } else if input.keyb != Vec2::ZERO {
+                transform.pos += direction * (delta.0.as_secs_f32() * input.sensitivity);
             }
         }
     }
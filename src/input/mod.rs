@@ -1,5 +1,6 @@
+use crate::world::picking::{BlockAction, BlockPicker};
 use specs::{Component, HashMapStorage, Join};
-use winit::event::{ElementState, KeyboardInput, VirtualKeyCode};
+use winit::event::{ElementState, KeyboardInput, MouseButton, VirtualKeyCode};
 
 mod input_vector;
 pub use input_vector::*;
@@ -27,6 +28,29 @@ impl Default for InputEventQueue {
     }
 }
 
+/// Mouse button event queue used to store button events for the current frame.
+pub struct MouseButtonQueue {
+    queue: std::collections::VecDeque<(MouseButton, ElementState)>,
+}
+
+impl MouseButtonQueue {
+    pub fn push_event(&mut self, button: MouseButton, state: ElementState) {
+        self.queue.push_back((button, state));
+    }
+
+    pub fn pop_event(&mut self) -> Option<(MouseButton, ElementState)> {
+        self.queue.pop_front()
+    }
+}
+
+impl Default for MouseButtonQueue {
+    fn default() -> Self {
+        Self {
+            queue: std::collections::VecDeque::new(),
+        }
+    }
+}
+
 /// Input resource used to track current key presses & their associated state.
 pub struct InputTracker {
     keys: Vec<(VirtualKeyCode, ElementState)>,
@@ -101,16 +125,35 @@ impl InputSystem {
             _ => {}
         }
     }
+
+    fn process_mouse_button(button: MouseButton) -> Option<BlockAction> {
+        match button {
+            MouseButton::Left => Some(BlockAction::Break),
+            MouseButton::Right => Some(BlockAction::Place),
+            _ => None,
+        }
+    }
 }
 
 impl<'a> specs::System<'a> for InputSystem {
     type SystemData = (
         specs::Write<'a, InputEventQueue>,
+        specs::Write<'a, MouseButtonQueue>,
         specs::Write<'a, InputTracker>,
         specs::WriteStorage<'a, InputVector>,
+        specs::WriteStorage<'a, BlockPicker>,
     );
 
-    fn run(&mut self, (mut input_events, mut input_tracker, mut input_vectors): Self::SystemData) {
+    fn run(
+        &mut self,
+        (
+            mut input_events,
+            mut mouse_button_events,
+            mut input_tracker,
+            mut input_vectors,
+            mut block_pickers,
+        ): Self::SystemData,
+    ) {
         // Process all pending input events.
         while let Some(input_event) = input_events.pop_event() {
             input_tracker.consume_input_event(input_event);
@@ -128,6 +171,17 @@ impl<'a> specs::System<'a> for InputSystem {
             input_vector.keyb = vec;
         }
 
+        // Queue block actions for mouse button presses.
+        while let Some((button, state)) = mouse_button_events.pop_event() {
+            if let (ElementState::Pressed, Some(action)) =
+                (state, Self::process_mouse_button(button))
+            {
+                for block_picker in (&mut block_pickers).join() {
+                    block_picker.queue_action(action);
+                }
+            }
+        }
+
         // Maintain the InputTracker, discarding any Release presses.
         // TODO perhaps figure a way to do this at end-of-frame?
         input_tracker.maintain();
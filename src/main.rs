@@ -74,6 +74,26 @@ const VERTICES: [f32; 18] = [
 
 const INDICES: [u32; 6] = [1, 2, 3, 2, 3, 1];
 
+/// Seed from which all world generation is derived, unless the world was saved
+/// with another.
+const WORLD_SEED: u64 = 0x5EED;
+
+/// Directory in which the world's chunks are saved.
+const WORLD_DIRECTORY: &str = "world";
+
+/// Radius around each camera within which chunks are loaded, in chunks.
+const VIEW_RADIUS: i32 = 8;
+/// Radius around each camera beyond which chunks are unloaded, in chunks.
+const UNLOAD_RADIUS: i32 = 10;
+
+/// Furthest a camera can target blocks to break or place from, in blocks.
+const BLOCK_REACH: f32 = 8.0;
+
+/// Speed the camera walks at, in blocks per second.
+const WALK_SPEED: f32 = 5.0;
+/// Height the camera spawns at, above the tallest terrain.
+const SPAWN_HEIGHT: f32 = 128.0;
+
 static mut FRAME_COUNTER: usize = 0;
 
 pub fn get_frame_count() -> usize {
@@ -194,6 +214,7 @@ fn main() {
     // Insert resources.
     world.insert(input::InputTracker::default());
     world.insert(input::InputEventQueue::default());
+    world.insert(input::MouseButtonQueue::default());
     world.insert(time::DeltaTime(std::time::Duration::ZERO));
     world.insert(AutomataWindow { window });
 
@@ -209,6 +230,41 @@ fn main() {
         max_uniform_alignment as usize,
     ));
 
+    // Assemble world generation.
+    let pending_writes = world::chunk::PendingWrites::new();
+    world.insert(pending_writes.clone());
+
+    let preset = match std::env::args().skip_while(|arg| arg != "--preset").nth(1) {
+        Some(preset) => {
+            world::generation::WorldPreset::parse(&preset, &world::block::BLOCK_REGISTRY)
+                .unwrap_or_else(|error| panic!("Invalid world preset: {}", error))
+        }
+        None => world::generation::WorldPreset::default(),
+    };
+    info!("Generating world from preset: {:?}", preset);
+
+    // Worlds keep the seed they were created with, and give saved ids to blocks
+    //  registered since they were last loaded.
+    let world_directory = std::path::Path::new(WORLD_DIRECTORY);
+    let mut metadata = world::chunk::WorldMetadata::load(world_directory)
+        .unwrap_or_else(|error| panic!("Failed to load world metadata: {}", error))
+        .unwrap_or_else(|| world::chunk::WorldMetadata::new(WORLD_SEED));
+    if metadata.register_missing(&world::block::BLOCK_REGISTRY) {
+        metadata
+            .save(world_directory)
+            .unwrap_or_else(|error| panic!("Failed to save world metadata: {}", error));
+    }
+    info!("Loaded world with seed {:#x}.", metadata.seed);
+
+    let region_store =
+        std::sync::Arc::new(world::chunk::RegionStore::new(world_directory).with_id_map(
+            std::sync::Arc::new(metadata.id_map(&world::block::BLOCK_REGISTRY)),
+        ));
+    let chunk_generation = preset
+        .build(metadata.seed, pending_writes, &world::block::BLOCK_REGISTRY)
+        .with_region_store(std::sync::Arc::clone(&region_store))
+        .with_max_jobs(num_cpus::get() * 2);
+
     // Register systems.
     let mut dispatcher = specs::DispatcherBuilder::new()
         .with(input::InputSystem, "input", &[])
@@ -218,19 +274,47 @@ fn main() {
             &["input"],
         )
         .with(
-            world::TransformMatrixSystem,
-            "transform",
+            world::physics::PhysicsSystem::new(),
+            "physics",
             &["input_translation"],
         )
+        .with(world::TransformMatrixSystem, "transform", &["physics"])
+        .with(
+            world::chunk::ChunkStreamingSystem::new(VIEW_RADIUS, UNLOAD_RADIUS)
+                .with_region_store(std::sync::Arc::clone(&region_store))
+                .with_max_loads_per_frame(num_cpus::get() * 2),
+            "chunk_streaming",
+            &["transform"],
+        )
+        .with(chunk_generation, "chunk_generation", &["chunk_streaming"])
+        .with(
+            world::picking::BlockPickingSystem,
+            "block_picking",
+            &["transform", "chunk_generation"],
+        )
+        .with(
+            world::chunk::ChunkLightingSystem,
+            "chunk_lighting",
+            &["chunk_generation", "block_picking"],
+        )
+        .with(
+            world::chunk::ChunkMeshingSystem::new().with_max_jobs(num_cpus::get() * 2),
+            "chunk_meshing",
+            &["chunk_lighting"],
+        )
         .with_barrier()
         .with_thread_local(render::OpenGLMaintenanceSystem)
         .with_thread_local(render::mesh::VertexArrayRenderSystem::new())
         .with_thread_local(render::mesh::MultiDrawIndirectRenderSystem::new())
+        .with_thread_local(render::BlockOutlineRenderSystem::new())
         .build();
 
     // Register components.
     world.register::<input::InputVector>();
     world.register::<render::Material>();
+    world.register::<world::picking::BlockPicker>();
+    world.register::<world::physics::Velocity>();
+    world.register::<world::physics::Collider>();
 
     dispatcher.setup(&mut world);
 
@@ -255,12 +339,13 @@ fn main() {
 
     {
         let aspect_ratio = world.read_resource::<AutomataWindow>().aspect_ratio();
+        let stone = world::block::BLOCK_REGISTRY.core_block("stone");
 
         world
             .create_entity()
             .with(input::InputVector {
                 keyb: glam::Vec2::ZERO,
-                sensitivity: 100.0,
+                sensitivity: WALK_SPEED,
             })
             .with(render::camera::Camera {
                 view: glam::Mat4::IDENTITY,
@@ -272,7 +357,20 @@ fn main() {
                     1000.0,
                 )),
             })
-            .with(world::Transform::default())
+            .with(world::picking::BlockPicker::new(BLOCK_REACH, stone))
+            .with(world::physics::Velocity::default())
+            // Roughly a block wide and two tall, with the camera at eye level.
+            .with(
+                world::physics::Collider::new(world::physics::Aabb::new(
+                    glam::Vec3::new(-0.3, -1.6, -0.3),
+                    glam::Vec3::new(0.3, 0.2, 0.3),
+                ))
+                .with_step_height(1.0),
+            )
+            .with(world::Transform {
+                pos: glam::Vec3::new(0.0, SPAWN_HEIGHT, 0.0),
+                ..world::Transform::default()
+            })
             .build();
     }
 
@@ -316,6 +414,13 @@ fn main() {
                 ..
             } => {}
 
+            Event::WindowEvent {
+                event: WindowEvent::MouseInput { state, button, .. },
+                ..
+            } => world
+                .write_resource::<input::MouseButtonQueue>()
+                .push_event(button, state),
+
             Event::WindowEvent {
                 event:
                     WindowEvent::KeyboardInput {
@@ -368,6 +473,29 @@ fn main() {
                 gl_context.swap_buffers();
             }
 
+            Event::LoopDestroyed => {
+                use specs::Join;
+
+                // Save every modified chunk before exiting.
+                for chunk in world.read_storage::<world::chunk::Chunk>().join() {
+                    if let Some(blocks) = chunk.snapshot().filter(|_| chunk.is_modified()) {
+                        region_store.save(chunk.position(), blocks);
+                    }
+                }
+
+                // Writes into chunks which never generated are kept for when they do.
+                let pending_writes = world.read_resource::<world::chunk::PendingWrites>().drain();
+                for (position, writes) in pending_writes {
+                    if let Err(error) = region_store.save_pending(position, writes) {
+                        error!("Failed to save pending writes: {}", error);
+                    }
+                }
+
+                if let Err(error) = region_store.flush() {
+                    error!("Failed to save chunks: {}", error);
+                }
+            }
+
             _ => {}
         }
     })
@@ -1,9 +1,11 @@
 mod material;
+mod outline;
 
 pub mod camera;
 pub mod mesh;
 
 pub use material::*;
+pub use outline::*;
 
 use glam::{Mat4, Vec4};
 
@@ -0,0 +1,115 @@
+use crate::opengl::{
+    buffer::{Buffer, BufferDraw},
+    shader::{Fragment, ProgramPipeline, ShaderProgram, Vertex},
+    VertexArrayObject, VertexFormat,
+};
+use glam::{Mat4, Vec3};
+
+const OUTLINE_VERTEX_SRC: &str = r#"
+    #version 450 core
+
+    layout (location = 0) in vec3 v_pos;
+
+    layout (location = 1) uniform mat4 mvp;
+
+    out gl_PerVertex { vec4 gl_Position; };
+
+    void main() {
+        gl_Position = mvp * vec4(v_pos, 1.0);
+    }
+"#;
+
+const OUTLINE_FRAGMENT_SRC: &str = r#"
+    #version 450 core
+
+    out vec4 f_color;
+
+    void main() {
+       f_color = vec4(0.0, 0.0, 0.0, 1.0);
+    }
+"#;
+
+/// Line list of the 12 edges of a unit cube.
+#[rustfmt::skip]
+const OUTLINE_VERTICES: [f32; 72] = [
+    // Bottom
+    0.0, 0.0, 0.0,  1.0, 0.0, 0.0,
+    1.0, 0.0, 0.0,  1.0, 0.0, 1.0,
+    1.0, 0.0, 1.0,  0.0, 0.0, 1.0,
+    0.0, 0.0, 1.0,  0.0, 0.0, 0.0,
+    // Top
+    0.0, 1.0, 0.0,  1.0, 1.0, 0.0,
+    1.0, 1.0, 0.0,  1.0, 1.0, 1.0,
+    1.0, 1.0, 1.0,  0.0, 1.0, 1.0,
+    0.0, 1.0, 1.0,  0.0, 1.0, 0.0,
+    // Sides
+    0.0, 0.0, 0.0,  0.0, 1.0, 0.0,
+    1.0, 0.0, 0.0,  1.0, 1.0, 0.0,
+    1.0, 0.0, 1.0,  1.0, 1.0, 1.0,
+    0.0, 0.0, 1.0,  0.0, 1.0, 1.0,
+];
+
+/// Amount the outline is grown by on each side, so it isn't hidden by the faces
+/// of the block it outlines.
+const OUTLINE_INFLATION: f32 = 0.002;
+
+/// Draws an outline around the block targeted by each camera's `BlockPicker`.
+pub struct BlockOutlineRenderSystem {
+    pipeline: ProgramPipeline,
+    vertices: Buffer<f32>,
+    vao: VertexArrayObject,
+}
+
+impl BlockOutlineRenderSystem {
+    pub fn new() -> Self {
+        let vertices = Buffer::<f32>::new_data(&OUTLINE_VERTICES, BufferDraw::Static);
+        let mut vao = VertexArrayObject::new();
+        vao.allocate_vertex_attribute(0, 3, 0, 0, VertexFormat::F32(false));
+        vao.allocate_vertex_buffer_binding(0, &vertices, 0, 0);
+        vao.commit(None);
+
+        Self {
+            pipeline: ProgramPipeline::new(
+                ShaderProgram::<Vertex>::new(&[OUTLINE_VERTEX_SRC]),
+                None,
+                None,
+                None,
+                Some(ShaderProgram::<Fragment>::new(&[OUTLINE_FRAGMENT_SRC])),
+            ),
+            vertices,
+            vao,
+        }
+    }
+}
+
+impl<'a> specs::System<'a> for BlockOutlineRenderSystem {
+    type SystemData = (
+        specs::ReadStorage<'a, crate::render::camera::Camera>,
+        specs::ReadStorage<'a, crate::world::picking::BlockPicker>,
+    );
+
+    fn run(&mut self, (cameras, pickers): Self::SystemData) {
+        use specs::Join;
+
+        for (camera, picker) in (&cameras, &pickers).join() {
+            if let (Some(projector), Some(target)) = (&camera.projector, picker.target()) {
+                let model = Mat4::from_scale_rotation_translation(
+                    Vec3::splat(1.0 + (OUTLINE_INFLATION * 2.0)),
+                    glam::Quat::IDENTITY,
+                    target.position.as_ivec3().as_vec3() - Vec3::splat(OUTLINE_INFLATION),
+                );
+
+                self.pipeline.bind();
+                self.pipeline
+                    .vertex()
+                    .set_uniform_mat4("mvp", projector.matrix() * camera.view * model)
+                    .ok();
+                self.vao.bind();
+
+                unsafe {
+                    gl::DrawArrays(gl::LINES, 0, (self.vertices.data_len() / 3) as i32);
+                }
+            }
+        }
+    }
+}
@@ -1,8 +1,12 @@
+use super::Block;
 use std::{
     collections::HashMap,
     sync::{atomic::AtomicU16, RwLock},
 };
 
+/// Brightest light level a block can emit or be lit to.
+pub const MAX_LIGHT_LVL: u8 = 15;
+
 bitflags::bitflags! {
     pub struct Attributes : u64 {
         const TRANSPARENT = 1 << 0;
@@ -15,6 +19,7 @@ bitflags::bitflags! {
 struct BlockDefinition {
     name: String,
     attribs: Attributes,
+    light_lvl: u8,
 }
 
 impl BlockDefinition {
@@ -29,6 +34,15 @@ impl BlockDefinition {
     pub fn has_attribute(&self, attrib: Attributes) -> bool {
         self.attribs.contains(attrib)
     }
+
+    pub fn light_lvl(&self) -> u8 {
+        self.light_lvl
+    }
+}
+
+lazy_static::lazy_static! {
+    /// Global block registry, shared by the main thread and background workers.
+    pub static ref BLOCK_REGISTRY: BlockRegistry = BlockRegistry::default();
 }
 
 pub struct BlockRegistry {
@@ -51,9 +65,27 @@ impl BlockRegistry {
     }
 
     pub fn register_block(&self, group: &str, name: &str, attribs: Attributes) -> u16 {
+        self.register_light_block(group, name, attribs, 0)
+    }
+
+    /// Registers a block which emits light at `light_lvl`, up to `MAX_LIGHT_LVL`.
+    pub fn register_light_block(
+        &self,
+        group: &str,
+        name: &str,
+        attribs: Attributes,
+        light_lvl: u8,
+    ) -> u16 {
+        assert!(
+            light_lvl <= MAX_LIGHT_LVL,
+            "Block light level must not exceed {}.",
+            MAX_LIGHT_LVL
+        );
+
         let definition = BlockDefinition {
             name: format!("{}:{}", group, name),
             attribs,
+            light_lvl,
         };
 
         let id = self.next_id().expect("Out of valid block IDs!");
@@ -71,6 +103,15 @@ impl BlockRegistry {
         id
     }
 
+    /// Count of registered blocks, which are numbered from zero.
+    pub fn len(&self) -> usize {
+        self.definitions.read().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
     pub fn block_exists(&self, id: u16) -> bool {
         (id as usize) < self.definitions.read().unwrap().len()
     }
@@ -79,6 +120,20 @@ impl BlockRegistry {
         self.id_lookup.read().unwrap().get(&name).copied()
     }
 
+    /// Returns the block registered as `name`, e.g. `core:stone`, if any.
+    pub fn get_block(&self, name: &str) -> Option<Block> {
+        self.get_block_id(name.to_string())
+            .map(|id| Block::new(id, 0, 0))
+    }
+
+    /// Returns the block registered as `core:{name}`.
+    ///
+    /// Panics if it isn't registered.
+    pub fn core_block(&self, name: &str) -> Block {
+        self.get_block(&format!("core:{}", name))
+            .unwrap_or_else(|| panic!("Block \"core:{}\" is not registered!", name))
+    }
+
     pub fn get_block_name(&self, id: u16) -> String {
         self.definitions.read().unwrap()[id as usize]
             .name()
@@ -88,6 +143,11 @@ impl BlockRegistry {
     pub fn get_block_attributes(&self, id: u16) -> Attributes {
         self.definitions.read().unwrap()[id as usize].attribs()
     }
+
+    /// Level of the light the block emits, or zero if it emits none.
+    pub fn get_block_light(&self, id: u16) -> u8 {
+        self.definitions.read().unwrap()[id as usize].light_lvl()
+    }
 }
 
 impl Default for BlockRegistry {
@@ -99,6 +159,65 @@ impl Default for BlockRegistry {
         };
 
         registry.register_block("core", "air", Attributes::TRANSPARENT);
+        registry.register_block("core", "bedrock", Attributes::COLLIDEABLE);
+        registry.register_block(
+            "core",
+            "stone",
+            Attributes::COLLIDEABLE | Attributes::DESCTRUCTIBLE,
+        );
+        registry.register_block(
+            "core",
+            "dirt",
+            Attributes::COLLIDEABLE | Attributes::DESCTRUCTIBLE,
+        );
+        registry.register_block(
+            "core",
+            "grass",
+            Attributes::COLLIDEABLE | Attributes::DESCTRUCTIBLE,
+        );
+        registry.register_block(
+            "core",
+            "sand",
+            Attributes::COLLIDEABLE | Attributes::DESCTRUCTIBLE,
+        );
+        registry.register_block(
+            "core",
+            "snow",
+            Attributes::COLLIDEABLE | Attributes::DESCTRUCTIBLE,
+        );
+        registry.register_block(
+            "core",
+            "log",
+            Attributes::COLLIDEABLE | Attributes::DESCTRUCTIBLE,
+        );
+        registry.register_block(
+            "core",
+            "leaves",
+            Attributes::COLLIDEABLE | Attributes::DESCTRUCTIBLE,
+        );
+        registry.register_block(
+            "core",
+            "cobblestone",
+            Attributes::COLLIDEABLE | Attributes::DESCTRUCTIBLE,
+        );
+        registry.register_block(
+            "core",
+            "coal_ore",
+            Attributes::COLLIDEABLE | Attributes::DESCTRUCTIBLE,
+        );
+        registry.register_block(
+            "core",
+            "iron_ore",
+            Attributes::COLLIDEABLE | Attributes::DESCTRUCTIBLE,
+        );
+        registry.register_light_block(
+            "core",
+            "torch",
+            Attributes::TRANSPARENT | Attributes::DESCTRUCTIBLE,
+            14,
+        );
+        // Stands in for saved blocks which are no longer registered.
+        registry.register_block("core", "unknown", Attributes::COLLIDEABLE);
 
         registry
     }
@@ -28,9 +28,17 @@ impl Block {
         self.color
     }
 
+    /// Light level at the block's position, as filled in by `WorldBlocks::get_block`.
+    ///
+    /// Light is stored apart from blocks, in `ChunkLight`, so blocks held in chunk
+    /// storage (and saved to disk) don't carry it.
     pub const fn light_lvl(&self) -> u8 {
         self.light_lvl
     }
+
+    pub const fn with_light_lvl(self, light_lvl: u8) -> Self {
+        Self { light_lvl, ..self }
+    }
 }
 
 impl Eq for Block {}
@@ -0,0 +1,487 @@
+use super::{BlockStorage, BlockStorageMut, ChunkStorage, CHUNK_SIZE_CUBED};
+use crate::{collections::Palette, world::block::Block};
+
+/// Version written at the head of every encoded palette.
+///
+/// Layout (little-endian):
+///  - `u8` format version
+///  - `u32` element count
+///  - `u16` lookup entry count, followed by each entry as `u16` id, `u16` color
+///  - `u8` index bit width, `0` when the lookup holds a single entry
+///  - the lookup index of each element, bit-packed least significant bit first
+///
+/// Light isn't encoded, as it lives outside of blocks and is recomputed whenever a
+/// chunk is loaded.
+pub const PALETTE_FORMAT_VERSION: u8 = 1;
+
+// Lookup entries are counted with a `u16`, so no more than 16 bits are ever needed.
+const MAX_ENCODED_INDEX_BITS: u8 = 16;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EncodeError {
+    /// The palette holds more elements than its `u32` count can describe.
+    TooManyElements(usize),
+    /// The palette's lookup holds more entries than its `u16` count can describe.
+    TooManyLookupEntries(usize),
+}
+
+impl std::fmt::Display for EncodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::TooManyElements(len) => write!(f, "palette of {} elements is too large", len),
+            Self::TooManyLookupEntries(lookup_len) => {
+                write!(f, "palette lookup of {} entries is too large", lookup_len)
+            }
+        }
+    }
+}
+
+impl std::error::Error for EncodeError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The input ended before the encoding was complete.
+    UnexpectedEof,
+    UnsupportedVersion(u8),
+    EmptyLookup,
+    DuplicateLookupEntry(u16),
+    InvalidIndexBits(u8),
+    LookupIndexOutOfRange {
+        index: usize,
+        lookup_index: usize,
+    },
+    InvalidLength {
+        expected: usize,
+        found: usize,
+    },
+    /// A string was not valid UTF-8.
+    InvalidString,
+    /// A local block index lies outside of a chunk.
+    InvalidLocalIndex(u16),
+    InvalidReplace(u8),
+    /// The encoding was complete, but the input continued past it.
+    TrailingBytes(usize),
+}
+
+impl std::fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnexpectedEof => write!(f, "unexpected end of input"),
+            Self::UnsupportedVersion(version) => {
+                write!(f, "unsupported palette format version {}", version)
+            }
+            Self::EmptyLookup => write!(f, "palette lookup contains no entries"),
+            Self::DuplicateLookupEntry(id) => {
+                write!(f, "palette lookup contains block id {} more than once", id)
+            }
+            Self::InvalidIndexBits(index_bits) => {
+                write!(f, "invalid palette index width of {} bits", index_bits)
+            }
+            Self::LookupIndexOutOfRange {
+                index,
+                lookup_index,
+            } => write!(
+                f,
+                "element {} references lookup index {} which does not exist",
+                index, lookup_index
+            ),
+            Self::InvalidLength { expected, found } => {
+                write!(f, "expected {} elements, found {}", expected, found)
+            }
+            Self::InvalidString => write!(f, "string is not valid UTF-8"),
+            Self::InvalidLocalIndex(index) => {
+                write!(f, "local block index {} lies outside of a chunk", index)
+            }
+            Self::InvalidReplace(replace) => write!(f, "invalid replace rule {}", replace),
+            Self::TrailingBytes(count) => write!(f, "{} unexpected trailing bytes", count),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+/// Cursor over a byte slice, reading little-endian values.
+pub struct ByteReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    pub const fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, position: 0 }
+    }
+
+    pub const fn position(&self) -> usize {
+        self.position
+    }
+
+    pub const fn remaining(&self) -> usize {
+        self.bytes.len() - self.position
+    }
+
+    pub fn read_bytes(&mut self, len: usize) -> Result<&'a [u8], DecodeError> {
+        if self.remaining() < len {
+            return Err(DecodeError::UnexpectedEof);
+        }
+
+        let bytes = &self.bytes[self.position..(self.position + len)];
+        self.position += len;
+
+        Ok(bytes)
+    }
+
+    pub fn read_u8(&mut self) -> Result<u8, DecodeError> {
+        Ok(self.read_bytes(1)?[0])
+    }
+
+    pub fn read_u16(&mut self) -> Result<u16, DecodeError> {
+        Ok(u16::from_le_bytes(self.read_bytes(2)?.try_into().unwrap()))
+    }
+
+    pub fn read_u32(&mut self) -> Result<u32, DecodeError> {
+        Ok(u32::from_le_bytes(self.read_bytes(4)?.try_into().unwrap()))
+    }
+
+    pub fn read_u64(&mut self) -> Result<u64, DecodeError> {
+        Ok(u64::from_le_bytes(self.read_bytes(8)?.try_into().unwrap()))
+    }
+
+    /// Reads a UTF-8 string, prefixed by its `u16` byte length.
+    pub fn read_string(&mut self) -> Result<&'a str, DecodeError> {
+        let len = self.read_u16()? as usize;
+        std::str::from_utf8(self.read_bytes(len)?).map_err(|_| DecodeError::InvalidString)
+    }
+
+    /// Fails if any input remains unread.
+    pub fn finish(&self) -> Result<(), DecodeError> {
+        match self.remaining() {
+            0 => Ok(()),
+            remaining => Err(DecodeError::TrailingBytes(remaining)),
+        }
+    }
+}
+
+fn encoded_index_bits(lookup_len: usize) -> u8 {
+    match lookup_len {
+        1 => 0,
+        lookup_len => (usize::BITS - (lookup_len - 1).leading_zeros()) as u8,
+    }
+}
+
+/// Appends the encoding of `palette` to `bytes`. Unreferenced lookup entries are omitted.
+pub fn encode_palette(palette: &Palette<Block>, bytes: &mut Vec<u8>) -> Result<(), EncodeError> {
+    // Compacting a clone only copies the palette's backing, leaving the original untouched.
+    let mut palette = palette.clone();
+    palette.compact();
+
+    let len =
+        u32::try_from(palette.len()).map_err(|_| EncodeError::TooManyElements(palette.len()))?;
+    let lookup_len = u16::try_from(palette.lookup_len())
+        .map_err(|_| EncodeError::TooManyLookupEntries(palette.lookup_len()))?;
+
+    bytes.push(PALETTE_FORMAT_VERSION);
+    bytes.extend_from_slice(&len.to_le_bytes());
+    bytes.extend_from_slice(&lookup_len.to_le_bytes());
+
+    for lookup_index in 0..palette.lookup_len() {
+        let block = palette.get_lookup_value(lookup_index);
+        bytes.extend_from_slice(&block.id().to_le_bytes());
+        bytes.extend_from_slice(&block.color().to_le_bytes());
+    }
+
+    let index_bits = encoded_index_bits(palette.lookup_len());
+    bytes.push(index_bits);
+
+    if index_bits > 0 {
+        let mut bit_buffer = 0u32;
+        let mut buffered_bits = 0;
+
+        for lookup_index in palette.packed_indexes(0..palette.len()) {
+            bit_buffer |= (lookup_index as u32) << buffered_bits;
+            buffered_bits += index_bits;
+
+            while buffered_bits >= 8 {
+                bytes.push(bit_buffer as u8);
+                bit_buffer >>= 8;
+                buffered_bits -= 8;
+            }
+        }
+
+        if buffered_bits > 0 {
+            bytes.push(bit_buffer as u8);
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads a single encoded palette of `expected_len` elements from `reader`, leaving
+/// it positioned after the encoding.
+pub fn read_palette(
+    reader: &mut ByteReader,
+    expected_len: usize,
+) -> Result<Palette<Block>, DecodeError> {
+    let version = reader.read_u8()?;
+    if version != PALETTE_FORMAT_VERSION {
+        return Err(DecodeError::UnsupportedVersion(version));
+    }
+
+    // Checked before anything is allocated, so that a corrupt header can't request
+    //  an arbitrarily large allocation.
+    let len = reader.read_u32()? as usize;
+    if len != expected_len {
+        return Err(DecodeError::InvalidLength {
+            expected: expected_len,
+            found: len,
+        });
+    }
+
+    let lookup_len = reader.read_u16()? as usize;
+    if lookup_len == 0 {
+        return Err(DecodeError::EmptyLookup);
+    }
+
+    let mut lookup = Vec::with_capacity(lookup_len);
+    for _ in 0..lookup_len {
+        let block = Block::new(reader.read_u16()?, reader.read_u16()?, 0);
+        if lookup.contains(&block) {
+            return Err(DecodeError::DuplicateLookupEntry(block.id()));
+        }
+
+        lookup.push(block);
+    }
+
+    let index_bits = reader.read_u8()?;
+    if index_bits > MAX_ENCODED_INDEX_BITS || (1usize << index_bits) < lookup_len {
+        return Err(DecodeError::InvalidIndexBits(index_bits));
+    }
+
+    let packed_bytes = reader.read_bytes(((len * (index_bits as usize)) + 7) / 8)?;
+    let index_mask = (1u32 << index_bits) - 1;
+    let mut indexes = Vec::with_capacity(len);
+    let mut packed_bytes = packed_bytes.iter();
+    let mut bit_buffer = 0u32;
+    let mut buffered_bits = 0;
+
+    for index in 0..len {
+        while buffered_bits < index_bits {
+            bit_buffer |= (*packed_bytes.next().unwrap() as u32) << buffered_bits;
+            buffered_bits += 8;
+        }
+
+        let lookup_index = (bit_buffer & index_mask) as usize;
+        bit_buffer >>= index_bits;
+        buffered_bits -= index_bits;
+
+        if lookup_index >= lookup_len {
+            return Err(DecodeError::LookupIndexOutOfRange {
+                index,
+                lookup_index,
+            });
+        }
+
+        indexes.push(lookup_index);
+    }
+
+    Ok(Palette::from_lookup_indexes(len, lookup, indexes))
+}
+
+/// Decodes a palette of `expected_len` elements previously produced by `encode_palette`.
+pub fn decode_palette(bytes: &[u8], expected_len: usize) -> Result<Palette<Block>, DecodeError> {
+    let mut reader = ByteReader::new(bytes);
+    let palette = read_palette(&mut reader, expected_len)?;
+    reader.finish()?;
+
+    Ok(palette)
+}
+
+/// Encodes the blocks of a chunk, regardless of its storage backend, as a palette.
+pub fn encode_chunk(storage: &dyn BlockStorage) -> Result<Vec<u8>, EncodeError> {
+    let mut blocks = vec![Block::AIR; storage.len()];
+    storage.copy_to_slice(&mut blocks);
+
+    let mut palette = Palette::new(blocks.len(), Block::AIR);
+    palette.copy_from_slice(&blocks);
+
+    let mut bytes = Vec::new();
+    encode_palette(&palette, &mut bytes)?;
+
+    Ok(bytes)
+}
+
+/// Decodes the blocks of a chunk previously produced by `encode_chunk`.
+pub fn decode_chunk(bytes: &[u8]) -> Result<ChunkStorage, DecodeError> {
+    let palette = decode_palette(bytes, CHUNK_SIZE_CUBED as usize)?;
+
+    let mut blocks = vec![Block::AIR; palette.len()];
+    palette.copy_to_slice(&mut blocks);
+
+    let mut storage = ChunkStorage::default();
+    storage.copy_from_slice(&blocks);
+
+    Ok(storage)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::chunk::{DenseStorage, RunLengthStorage, StorageKind, UniformStorage};
+
+    const CHUNK_LEN: usize = CHUNK_SIZE_CUBED as usize;
+
+    fn block(n: usize) -> Block {
+        Block::new(n as u16, (n % 4) as u16, 0)
+    }
+
+    // Blocks cycling through `distinct` values, so that neighbors always differ.
+    fn varied(distinct: usize) -> Vec<Block> {
+        (0..CHUNK_LEN)
+            .map(|index| block(index % distinct))
+            .collect()
+    }
+
+    fn layered() -> ChunkStorage {
+        let mut storage = ChunkStorage::default();
+        storage.fill_range(0..(CHUNK_LEN / 2), block(1));
+        storage.fill_range((CHUNK_LEN / 2)..(CHUNK_LEN / 2 + 100), block(2));
+        storage.set(CHUNK_LEN - 1, block(3));
+
+        storage
+    }
+
+    fn assert_round_trips(storage: &dyn BlockStorage) {
+        let decoded = decode_chunk(&encode_chunk(storage).unwrap()).unwrap();
+
+        let (mut expected, mut found) = (vec![Block::AIR; CHUNK_LEN], vec![Block::AIR; CHUNK_LEN]);
+        storage.copy_to_slice(&mut expected);
+        decoded.copy_to_slice(&mut found);
+        assert!(
+            expected == found,
+            "decoded blocks differ from those encoded"
+        );
+    }
+
+    #[test]
+    fn every_storage_backend_round_trips() {
+        let mut palette = Palette::new(CHUNK_LEN, Block::AIR);
+        palette.copy_from_slice(&varied(300));
+
+        assert_round_trips(&UniformStorage::new(block(1)));
+        assert_round_trips(&palette);
+        assert_round_trips(&palette.snapshot());
+        assert_round_trips(&DenseStorage::from_storage(&palette));
+        assert_round_trips(&RunLengthStorage::from_storage(&layered()));
+
+        let mut dense = ChunkStorage::default();
+        dense.copy_from_slice(&varied(5));
+        for index in 0..=ChunkStorage::DENSE_EDIT_THRESHOLD {
+            dense.set(index * 7, block(index % 11));
+        }
+
+        let mut palette_storage = ChunkStorage::default();
+        palette_storage.copy_from_slice(&varied(17));
+
+        let chunks = [
+            (ChunkStorage::new(block(1)), StorageKind::Uniform),
+            (layered(), StorageKind::RunLength),
+            (palette_storage, StorageKind::Palette),
+            (dense, StorageKind::Dense),
+        ];
+        for (storage, kind) in chunks.iter() {
+            assert_eq!(storage.kind(), *kind);
+            assert_round_trips(storage);
+            assert_round_trips(&storage.snapshot());
+        }
+    }
+
+    #[test]
+    fn palettes_round_trip_at_every_index_width() {
+        for lookup_len in (0..=12).flat_map(|bits| [1 << bits, (1 << bits) + 1]) {
+            let mut palette = Palette::new(5000, Block::AIR);
+            for index in 0..palette.len() {
+                palette.set(index, block((index * 7) % lookup_len));
+            }
+
+            let mut bytes = Vec::new();
+            encode_palette(&palette, &mut bytes).unwrap();
+            assert_eq!(bytes[7 + (lookup_len * 4)], encoded_index_bits(lookup_len));
+
+            let decoded = decode_palette(&bytes, palette.len()).unwrap();
+            assert_eq!(decoded.len(), palette.len());
+            assert!((0..palette.len()).all(|index| decoded.get(index) == palette.get(index)));
+        }
+    }
+
+    #[test]
+    fn light_is_not_encoded() {
+        let palette = Palette::new(10, Block::new(2, 7, 9));
+        let mut bytes = Vec::new();
+        encode_palette(&palette, &mut bytes).unwrap();
+        assert_eq!(bytes, [1, 10, 0, 0, 0, 1, 0, 2, 0, 7, 0, 0]);
+
+        let decoded = decode_palette(&bytes, 10).unwrap();
+        assert_eq!(*decoded.get(9), Block::new(2, 7, 0));
+        assert_eq!(decoded.get(9).light_lvl(), 0);
+    }
+
+    #[test]
+    fn malformed_encodings_are_rejected() {
+        let bytes = encode_chunk(&layered()).unwrap();
+
+        for len in 0..bytes.len() {
+            assert_eq!(
+                decode_chunk(&bytes[..len]).err(),
+                Some(DecodeError::UnexpectedEof)
+            );
+        }
+
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert_eq!(
+            decode_chunk(&trailing).err(),
+            Some(DecodeError::TrailingBytes(1))
+        );
+
+        let mut version = bytes;
+        version[0] = 9;
+        assert_eq!(
+            decode_chunk(&version).err(),
+            Some(DecodeError::UnsupportedVersion(9))
+        );
+
+        assert_eq!(
+            decode_chunk(&[1, 10, 0, 0, 0, 1, 0, 2, 0, 7, 0, 0]).err(),
+            Some(DecodeError::InvalidLength {
+                expected: CHUNK_LEN,
+                found: 10
+            })
+        );
+        // A length far beyond what's expected is rejected before reading on.
+        assert_eq!(
+            decode_palette(&[1, 0xFF, 0xFF, 0xFF, 0xFF], 10).err(),
+            Some(DecodeError::InvalidLength {
+                expected: 10,
+                found: u32::MAX as usize
+            })
+        );
+        assert_eq!(
+            decode_palette(&[1, 2, 0, 0, 0, 0, 0, 0], 2).err(),
+            Some(DecodeError::EmptyLookup)
+        );
+        assert_eq!(
+            decode_palette(&[1, 2, 0, 0, 0, 2, 0, 1, 0, 0, 0, 1, 0, 0, 0, 1, 0], 2).err(),
+            Some(DecodeError::DuplicateLookupEntry(1))
+        );
+        assert_eq!(
+            decode_palette(
+                &[1, 2, 0, 0, 0, 3, 0, 1, 0, 0, 0, 2, 0, 0, 0, 3, 0, 0, 0, 2, 0b1110],
+                2
+            )
+            .err(),
+            Some(DecodeError::LookupIndexOutOfRange {
+                index: 1,
+                lookup_index: 3
+            })
+        );
+    }
+}
@@ -0,0 +1,247 @@
+use super::{
+    BlockPos, BlockStorage, BlockStorageMut, Chunk, ChunkMap, ChunkPos, ChunkStorage, LightUpdates,
+};
+use crate::{world::block::Block, DIRECTION};
+use specs::shred::{ResourceId, World};
+
+type WorldBlocksData<'a> = (
+    specs::Read<'a, ChunkMap>,
+    specs::Write<'a, LightUpdates>,
+    specs::WriteStorage<'a, Chunk>,
+);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EditError {
+    /// No chunk is loaded at the given position.
+    NotLoaded(ChunkPos),
+    /// The chunk at the given position is loaded, but its blocks are still being
+    /// generated.
+    NotGenerated(ChunkPos),
+}
+
+impl std::fmt::Display for EditError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotLoaded(position) => write!(f, "no chunk is loaded at {}", position),
+            Self::NotGenerated(position) => {
+                write!(f, "chunk at {} has not finished generating", position)
+            }
+        }
+    }
+}
+
+impl std::error::Error for EditError {}
+
+/// Reads and edits blocks anywhere in the loaded world, by block position.
+///
+/// Edited chunks are marked dirty, along with the neighbors sharing any face of
+/// the edited block, since their meshes cull faces against it. Dirty chunks are
+/// remeshed by the next run of `ChunkMeshingSystem`, so that any number of edits
+/// made within a frame cost each chunk a single remesh. Edits are likewise queued
+/// to be relit by the next run of `ChunkLightingSystem`.
+///
+/// Fetched like any other system data, e.g. `world.system_data::<WorldBlocks>()`.
+pub struct WorldBlocks<'a> {
+    chunk_map: specs::Read<'a, ChunkMap>,
+    light_updates: specs::Write<'a, LightUpdates>,
+    chunks: specs::WriteStorage<'a, Chunk>,
+}
+
+impl<'a> WorldBlocks<'a> {
+    /// Returns the blocks of the chunk at `position`, if it is loaded and generated.
+    pub fn chunk_blocks(&self, position: ChunkPos) -> Option<&ChunkStorage> {
+        self.chunk_map
+            .get(position)
+            .and_then(|entity| self.chunks.get(entity))
+            .and_then(Chunk::blocks)
+    }
+
+    /// Returns the block at `position`, with the light level there, if its chunk
+    /// is loaded and generated.
+    pub fn get_block(&self, position: BlockPos) -> Option<Block> {
+        let (chunk_position, local) = position.split();
+        let chunk = self
+            .chunk_map
+            .get(chunk_position)
+            .and_then(|entity| self.chunks.get(entity))?;
+        let index = local.index().get();
+
+        Some(
+            chunk
+                .blocks()?
+                .get(index)
+                .with_light_lvl(chunk.light()?.block_light(index)),
+        )
+    }
+
+    /// Returns the skylight level at `position`, if its chunk is loaded and generated.
+    pub fn get_sky_light(&self, position: BlockPos) -> Option<u8> {
+        let (chunk_position, local) = position.split();
+
+        self.chunk_map
+            .get(chunk_position)
+            .and_then(|entity| self.chunks.get(entity))
+            .and_then(Chunk::light)
+            .map(|light| light.sky_light(local.index().get()))
+    }
+
+    /// Writes `block` at `position`, returning the block it replaced.
+    pub fn set_block(&mut self, position: BlockPos, block: Block) -> Result<Block, EditError> {
+        let (chunk_position, local) = position.split();
+        let chunk = self
+            .chunk_map
+            .get(chunk_position)
+            .and_then(|entity| self.chunks.get_mut(entity))
+            .ok_or(EditError::NotLoaded(chunk_position))?;
+
+        let index = local.index().get();
+        let replaced = chunk
+            .blocks()
+            .ok_or(EditError::NotGenerated(chunk_position))?
+            .get(index);
+
+        if replaced == block {
+            return Ok(replaced);
+        }
+
+        chunk.blocks_mut().unwrap().set(index, block);
+        self.light_updates.push(position);
+
+        // Faces along the chunk's borders are culled against its neighbors' blocks.
+        for direction in DIRECTION::ALL {
+            if local.offset(direction).is_none() {
+                self.mark_dirty(chunk_position.offset(direction));
+            }
+        }
+
+        Ok(replaced)
+    }
+
+    /// Marks the chunk at `position` to be remeshed, if it is loaded.
+    fn mark_dirty(&mut self, position: ChunkPos) {
+        if let Some(chunk) = self
+            .chunk_map
+            .get(position)
+            .and_then(|entity| self.chunks.get_mut(entity))
+        {
+            chunk.mark_dirty();
+        }
+    }
+}
+
+impl<'a> specs::SystemData<'a> for WorldBlocks<'a> {
+    fn setup(world: &mut World) {
+        WorldBlocksData::setup(world);
+    }
+
+    fn fetch(world: &'a World) -> Self {
+        let (chunk_map, light_updates, chunks) = WorldBlocksData::fetch(world);
+
+        Self {
+            chunk_map,
+            light_updates,
+            chunks,
+        }
+    }
+
+    fn reads() -> Vec<ResourceId> {
+        WorldBlocksData::reads()
+    }
+
+    fn writes() -> Vec<ResourceId> {
+        WorldBlocksData::writes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::{
+        block::BLOCK_REGISTRY,
+        chunk::{ChunkState, CHUNK_SIZE},
+    };
+    use specs::{Builder, WorldExt};
+
+    // A meshed chunk at the origin, surrounded by its meshed neighbors.
+    fn world() -> World {
+        let mut world = World::new();
+        world.register::<Chunk>();
+        world.insert(ChunkMap::default());
+        world.insert(LightUpdates::default());
+
+        let origin = ChunkPos::default();
+        for position in std::iter::once(origin).chain(origin.neighbors()) {
+            let mut chunk = Chunk::new(position);
+            chunk.set_state(ChunkState::Generating);
+            chunk.complete_generation(ChunkStorage::default());
+            chunk.set_state(ChunkState::Meshing);
+            chunk.set_state(ChunkState::Ready);
+
+            let entity = world.create_entity().with(chunk).build();
+            world.write_resource::<ChunkMap>().insert(position, entity);
+        }
+
+        world
+    }
+
+    // Positions of the chunks marked dirty, which are then marked clean again.
+    fn take_dirty(world: &World) -> Vec<ChunkPos> {
+        let mut chunks = world.write_storage::<Chunk>();
+        let mut dirty = world
+            .read_resource::<ChunkMap>()
+            .iter()
+            .filter_map(|(position, entity)| {
+                let chunk = chunks.get_mut(entity).unwrap();
+
+                (chunk.state() == ChunkState::Dirty).then(|| {
+                    chunk.set_state(ChunkState::Meshing);
+                    chunk.set_state(ChunkState::Ready);
+                    position
+                })
+            })
+            .collect::<Vec<_>>();
+        dirty.sort_by_key(|position| (position.x, position.y, position.z));
+
+        dirty
+    }
+
+    #[test]
+    fn edits_dirty_the_neighbors_sharing_the_edited_face() {
+        let world = world();
+        let stone = BLOCK_REGISTRY.core_block("stone");
+        let origin = ChunkPos::default();
+        let set_block = |position: BlockPos| {
+            world
+                .system_data::<WorldBlocks>()
+                .set_block(position, stone)
+                .unwrap()
+        };
+
+        set_block(BlockPos::new(5, 6, 7));
+        assert_eq!(take_dirty(&world), [origin]);
+
+        // Rewriting a block with itself changes nothing.
+        set_block(BlockPos::new(5, 6, 7));
+        assert_eq!(take_dirty(&world), []);
+
+        set_block(BlockPos::new(CHUNK_SIZE - 1, 6, 7));
+        assert_eq!(take_dirty(&world), [origin, ChunkPos::new(1, 0, 0)]);
+
+        // A corner block borders three neighbors.
+        set_block(BlockPos::new(0, CHUNK_SIZE - 1, 0));
+        assert_eq!(
+            take_dirty(&world),
+            [
+                ChunkPos::new(-1, 0, 0),
+                ChunkPos::new(0, 0, -1),
+                origin,
+                ChunkPos::new(0, 1, 0),
+            ]
+        );
+        assert_eq!(world.read_resource::<LightUpdates>().len(), 3);
+
+        // Edits along the edge of the loaded world dirty only loaded chunks.
+        set_block(BlockPos::new(CHUNK_SIZE, 0, 0));
+        assert_eq!(take_dirty(&world), [origin, ChunkPos::new(1, 0, 0)]);
+    }
+}
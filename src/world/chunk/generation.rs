@@ -0,0 +1,281 @@
+use super::{
+    BlockPos, BlockStorageMut, Chunk, ChunkMap, ChunkPos, ChunkState, ChunkStorage, LightUpdates,
+    PendingWrites, RegionStore,
+};
+use crate::{concurrency::JobCompletion, DIRECTION};
+use specs::Entity;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+/// Describes the chunk being generated to each `ChunkGenerationStep`.
+#[derive(Debug, Clone, Copy)]
+pub struct ChunkGenerationContext {
+    pub position: ChunkPos,
+    /// Seed of the world the chunk belongs to.
+    pub seed: u64,
+}
+
+impl ChunkGenerationContext {
+    /// Position of the chunk's minimum corner.
+    pub const fn origin(&self) -> BlockPos {
+        self.position.origin()
+    }
+}
+
+/// A single pass over a chunk's blocks during generation.
+///
+/// Steps run on background workers, in the order they were registered, each
+/// seeing the output of the steps before it. Output must depend only on the
+/// context, so that a chunk generates identically regardless of which worker
+/// runs it, or when.
+pub trait ChunkGenerationStep: Send + Sync {
+    fn gen_pass(&self, context: &ChunkGenerationContext, blocks: &mut dyn BlockStorageMut);
+}
+
+struct GenerationJob {
+    completion: JobCompletion,
+    // Generated blocks, and whether saved pending writes modified them.
+    blocks: Arc<Mutex<Option<(ChunkStorage, bool)>>>,
+}
+
+/// Generates unloaded chunks on the background worker pool, by running each
+/// registered `ChunkGenerationStep` in turn.
+///
+/// With a region store, chunks which have been saved are loaded instead, and
+/// writes saved for them applied.
+pub struct ChunkGenerationSystem {
+    seed: u64,
+    steps: Vec<Arc<dyn ChunkGenerationStep>>,
+    region_store: Option<Arc<RegionStore>>,
+    jobs: HashMap<Entity, GenerationJob>,
+    max_jobs: usize,
+}
+
+impl ChunkGenerationSystem {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            steps: Vec::new(),
+            region_store: None,
+            jobs: HashMap::new(),
+            max_jobs: usize::MAX,
+        }
+    }
+
+    /// Loads chunks from `region_store` when they've been saved, rather than
+    /// generating them.
+    pub fn with_region_store(mut self, region_store: Arc<RegionStore>) -> Self {
+        self.region_store = Some(region_store);
+        self
+    }
+
+    /// Limits how many chunks may be generating at once.
+    pub fn with_max_jobs(mut self, max_jobs: usize) -> Self {
+        self.max_jobs = max_jobs;
+        self
+    }
+
+    /// Appends `step` to the end of the generation pipeline.
+    pub fn with_step(mut self, step: impl ChunkGenerationStep + 'static) -> Self {
+        self.add_step(step);
+        self
+    }
+
+    /// Appends `step` to the end of the generation pipeline.
+    ///
+    /// Chunks already being generated are unaffected.
+    pub fn add_step(&mut self, step: impl ChunkGenerationStep + 'static) {
+        self.steps.push(Arc::new(step));
+    }
+
+    pub const fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    fn queue_generation_job(&mut self, entity: Entity, position: ChunkPos) -> bool {
+        let context = ChunkGenerationContext {
+            position,
+            seed: self.seed,
+        };
+        let steps = self.steps.clone();
+        let region_store = self.region_store.clone();
+        let blocks = Arc::new(Mutex::new(None));
+        let blocks_clone = Arc::clone(&blocks);
+
+        let work = Box::new(move || {
+            // Chunks which fail to load are generated afresh, and overwritten when
+            //  next saved.
+            let loaded = region_store.as_ref().and_then(|region_store| {
+                region_store
+                    .load(position)
+                    .map_err(|error| warn!("Failed to load chunk at {}: {}", position, error))
+                    .ok()
+                    .flatten()
+            });
+
+            let mut storage = match loaded {
+                Some(storage) => storage,
+                None => {
+                    let mut storage = ChunkStorage::default();
+
+                    for step in &steps {
+                        step.gen_pass(&context, &mut storage);
+                    }
+
+                    storage
+                }
+            };
+
+            let saved_writes = region_store.map_or_else(Vec::new, |region_store| {
+                region_store.take_pending(position).unwrap_or_else(|error| {
+                    warn!("Failed to load pending writes for {}: {}", position, error);
+                    Vec::new()
+                })
+            });
+
+            let mut modified = false;
+            for write in saved_writes {
+                modified |= write.apply(&mut storage);
+            }
+
+            storage.optimize();
+            *blocks_clone.lock().unwrap() = Some((storage, modified));
+        });
+
+        match crate::concurrency::queue(work) {
+            Ok(completion) => {
+                self.jobs
+                    .insert(entity, GenerationJob { completion, blocks });
+                true
+            }
+            Err(()) => {
+                warn!("Failed to queue generation job; the worker pool is unavailable.");
+                false
+            }
+        }
+    }
+}
+
+impl<'a> specs::System<'a> for ChunkGenerationSystem {
+    type SystemData = (
+        specs::Entities<'a>,
+        specs::Read<'a, ChunkMap>,
+        specs::Read<'a, PendingWrites>,
+        specs::Write<'a, LightUpdates>,
+        specs::WriteStorage<'a, Chunk>,
+    );
+
+    fn run(
+        &mut self,
+        (entities, chunk_map, pending_writes, mut light_updates, mut chunks): Self::SystemData,
+    ) {
+        use specs::Join;
+
+        // Hand completed generation jobs back to their chunks.
+        let completed = self
+            .jobs
+            .iter()
+            .filter(|(_, job)| job.completion.is_complete())
+            .map(|(entity, _)| *entity)
+            .collect::<Vec<_>>();
+
+        for entity in completed {
+            let job = self.jobs.remove(&entity).unwrap();
+            let blocks = job.blocks.lock().unwrap().take();
+
+            // Chunks reset while generating ignore the result, and are queued again.
+            let chunk = chunks
+                .get_mut(entity)
+                .filter(|chunk| chunk.state() == ChunkState::Generating);
+
+            if let (Some(chunk), Some((mut blocks, mut modified))) = (chunk, blocks) {
+                let position = chunk.position();
+
+                // Apply writes spilled into the chunk while it was being generated.
+                for write in pending_writes.take(position) {
+                    modified |= write.apply(&mut blocks);
+                }
+
+                chunk.complete_generation(blocks);
+                if modified {
+                    chunk.mark_modified();
+                }
+
+                // Neighbors meshed before the chunk existed never culled their border
+                // faces against it.
+                for neighbor in chunk_map.neighbors(position).iter().flatten() {
+                    if let Some(neighbor) = chunks.get_mut(*neighbor) {
+                        neighbor.mark_dirty();
+                    }
+                }
+            }
+        }
+
+        // Apply writes spilled into chunks which have already been generated, and
+        // queue them to be relit. Writes into chunks yet to be generated remain
+        // queued until they are.
+        for position in pending_writes.take_fresh() {
+            let chunk = chunk_map
+                .get(position)
+                .and_then(|entity| chunks.get_mut(entity))
+                .filter(|chunk| chunk.state().is_readable());
+
+            if let Some(chunk) = chunk {
+                let writes = pending_writes.take(position);
+                let blocks = chunk.blocks_mut().unwrap();
+                let mut borders = DIRECTION::empty();
+
+                for write in writes {
+                    if write.apply(blocks) {
+                        let local = write.index.position();
+                        light_updates.push(position.block(local));
+
+                        for direction in DIRECTION::ALL {
+                            if local.offset(direction).is_none() {
+                                borders |= direction;
+                            }
+                        }
+                    }
+                }
+
+                // Faces along the chunk's borders are culled against its neighbors'
+                //  blocks, as with `WorldBlocks::set_block`.
+                for direction in DIRECTION::ALL.into_iter().filter(|d| borders.contains(*d)) {
+                    if let Some(neighbor) = chunk_map
+                        .get(position.offset(direction))
+                        .and_then(|entity| chunks.get_mut(entity))
+                    {
+                        neighbor.mark_dirty();
+                    }
+                }
+            }
+        }
+
+        // Forget jobs for chunks which have since been unloaded.
+        self.jobs.retain(|entity, _| entities.is_alive(*entity));
+
+        // Queue generation of newly created chunks.
+        let unloaded = (&entities, &chunks)
+            .join()
+            .filter(|(entity, chunk)| {
+                chunk.state() == ChunkState::Unloaded && !self.jobs.contains_key(entity)
+            })
+            .map(|(entity, chunk)| (entity, chunk.position()))
+            .collect::<Vec<_>>();
+
+        for (entity, position) in unloaded {
+            if self.jobs.len() >= self.max_jobs {
+                break;
+            }
+
+            if self.queue_generation_job(entity, position) {
+                chunks
+                    .get_mut(entity)
+                    .unwrap()
+                    .set_state(ChunkState::Generating);
+            }
+        }
+    }
+}
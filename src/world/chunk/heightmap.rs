@@ -0,0 +1,129 @@
+use super::{
+    BlockPos, ChunkPos, CHUNK_SIZE, CHUNK_SIZE_MASK, CHUNK_SIZE_SHIFT, CHUNK_SIZE_SQUARED,
+};
+use std::collections::{BTreeSet, HashMap};
+
+/// Heights of the highest opaque block (lacking the `TRANSPARENT` attribute) in each
+/// block column of a column of chunks, among those chunks which have been lit.
+pub struct ColumnHeightmap {
+    heights: Vec<Option<i32>>,
+    /// Vertical positions of the column's chunks which have been lit, and not yet
+    ///  unloaded.
+    chunks: BTreeSet<i32>,
+}
+
+impl ColumnHeightmap {
+    fn new() -> Self {
+        Self {
+            heights: vec![None; CHUNK_SIZE_SQUARED as usize],
+            chunks: BTreeSet::new(),
+        }
+    }
+
+    /// Index of the block column at local `x` and `z`.
+    const fn index(x: i32, z: i32) -> usize {
+        ((x & CHUNK_SIZE_MASK) + ((z & CHUNK_SIZE_MASK) * CHUNK_SIZE)) as usize
+    }
+}
+
+/// Resource holding the heightmaps of every column of chunks.
+///
+/// Blocks above their column's height are exposed to the sky. Columns are kept
+/// until their last lit chunk is unloaded.
+#[derive(Default)]
+pub struct Heightmaps {
+    columns: HashMap<(i32, i32), ColumnHeightmap>,
+}
+
+impl Heightmaps {
+    const fn column_position(x: i32, z: i32) -> (i32, i32) {
+        (x >> CHUNK_SIZE_SHIFT, z >> CHUNK_SIZE_SHIFT)
+    }
+
+    /// Y of the highest opaque block at world `x` and `z`, if any has been lit.
+    pub fn height(&self, x: i32, z: i32) -> Option<i32> {
+        self.columns
+            .get(&Self::column_position(x, z))
+            .and_then(|column| column.heights[ColumnHeightmap::index(x, z)])
+    }
+
+    pub fn set_height(&mut self, x: i32, z: i32, height: Option<i32>) {
+        self.columns
+            .entry(Self::column_position(x, z))
+            .or_insert_with(ColumnHeightmap::new)
+            .heights[ColumnHeightmap::index(x, z)] = height;
+    }
+
+    /// Whether nothing opaque lies above `position`.
+    pub fn is_exposed(&self, position: BlockPos) -> bool {
+        self.height(position.x, position.z)
+            .map_or(true, |height| position.y > height)
+    }
+
+    /// Records that the chunk at `position` has been lit.
+    pub fn add_chunk(&mut self, position: ChunkPos) {
+        self.columns
+            .entry((position.x, position.z))
+            .or_insert_with(ColumnHeightmap::new)
+            .chunks
+            .insert(position.y);
+    }
+
+    /// Records that the chunk at `position` has been unloaded, dropping its
+    /// column's heightmap along with the column's last chunk.
+    pub fn remove_chunk(&mut self, position: ChunkPos) {
+        if let Some(column) = self.columns.get_mut(&(position.x, position.z)) {
+            column.chunks.remove(&position.y);
+
+            if column.chunks.is_empty() {
+                self.columns.remove(&(position.x, position.z));
+            }
+        }
+    }
+
+    /// Count of columns with a heightmap.
+    pub fn len(&self) -> usize {
+        self.columns.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.columns.is_empty()
+    }
+
+    /// Vertical positions of the lit chunks in the column at chunk `x` and `z`,
+    /// from the top down.
+    pub fn column_chunks(&self, x: i32, z: i32) -> impl Iterator<Item = i32> + '_ {
+        self.columns
+            .get(&(x, z))
+            .into_iter()
+            .flat_map(|column| column.chunks.iter().rev().copied())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn columns_are_dropped_with_their_last_chunk() {
+        let mut heightmaps = Heightmaps::default();
+        heightmaps.add_chunk(ChunkPos::new(0, 0, 0));
+        heightmaps.add_chunk(ChunkPos::new(0, -1, 0));
+        heightmaps.add_chunk(ChunkPos::new(1, 0, 0));
+        heightmaps.set_height(5, 5, Some(10));
+
+        heightmaps.remove_chunk(ChunkPos::new(0, 0, 0));
+        assert_eq!(heightmaps.column_chunks(0, 0).collect::<Vec<_>>(), [-1]);
+        assert_eq!(heightmaps.height(5, 5), Some(10));
+
+        heightmaps.remove_chunk(ChunkPos::new(0, -1, 0));
+        assert_eq!(heightmaps.column_chunks(0, 0).count(), 0);
+        assert_eq!(heightmaps.height(5, 5), None);
+        assert_eq!(heightmaps.len(), 1);
+
+        // Chunks which were never lit leave their columns untouched.
+        heightmaps.remove_chunk(ChunkPos::new(1, 5, 0));
+        heightmaps.remove_chunk(ChunkPos::new(2, 0, 0));
+        assert_eq!(heightmaps.len(), 1);
+    }
+}
@@ -0,0 +1,381 @@
+use super::{
+    generate_packed_mesh, BlockStorage, ChunkLight, ChunkMesh, ChunkPos, ChunkStorage,
+    StorageSnapshot,
+};
+use crate::{concurrency::JobCompletion, world::block::BLOCK_REGISTRY};
+use specs::{Component, DenseVecStorage, Entity};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+/// Lifecycle of a chunk, from creation through to being drawable.
+///
+/// ```text
+/// Unloaded → Generating → Generated → Meshing → Ready
+///                                       ↑   ↓     ↓
+///                                       Dirty ←───┘
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChunkState {
+    /// The chunk exists, but has no block data yet.
+    Unloaded,
+    /// Block data is being produced, and cannot be read or edited.
+    Generating,
+    /// Block data is complete, but has not yet been meshed.
+    Generated,
+    /// A mesh is being built from a snapshot of the block data.
+    Meshing,
+    /// The chunk's mesh reflects its block data.
+    Ready,
+    /// The block data has been edited since the mesh was built.
+    Dirty,
+}
+
+impl ChunkState {
+    pub const fn can_transition_to(self, state: ChunkState) -> bool {
+        matches!(
+            (self, state),
+            (_, ChunkState::Unloaded)
+                | (ChunkState::Unloaded, ChunkState::Generating)
+                | (ChunkState::Generating, ChunkState::Generated)
+                | (ChunkState::Generated, ChunkState::Meshing)
+                | (ChunkState::Dirty, ChunkState::Meshing)
+                | (ChunkState::Meshing, ChunkState::Ready)
+                | (ChunkState::Meshing, ChunkState::Dirty)
+                | (ChunkState::Ready, ChunkState::Dirty)
+        )
+    }
+
+    /// Whether block data exists and may be read.
+    pub const fn is_readable(self) -> bool {
+        !matches!(self, ChunkState::Unloaded | ChunkState::Generating)
+    }
+
+    /// Whether the chunk has a mesh which may be drawn, even if it is stale.
+    pub const fn is_renderable(self) -> bool {
+        matches!(self, ChunkState::Ready | ChunkState::Dirty)
+    }
+}
+
+#[derive(Component)]
+#[storage(DenseVecStorage)]
+pub struct Chunk {
+    position: ChunkPos,
+    blocks: ChunkStorage,
+    light: ChunkLight,
+    lit: bool,
+    modified: bool,
+    state: ChunkState,
+}
+
+impl Chunk {
+    pub fn new(position: ChunkPos) -> Self {
+        Self {
+            position,
+            blocks: ChunkStorage::default(),
+            light: ChunkLight::default(),
+            lit: false,
+            modified: false,
+            state: ChunkState::Unloaded,
+        }
+    }
+
+    pub const fn position(&self) -> ChunkPos {
+        self.position
+    }
+
+    pub const fn state(&self) -> ChunkState {
+        self.state
+    }
+
+    pub fn set_state(&mut self, state: ChunkState) {
+        assert!(
+            self.state.can_transition_to(state),
+            "Invalid chunk state transition: {:?} -> {:?}",
+            self.state,
+            state
+        );
+
+        self.state = state;
+    }
+
+    /// Returns the chunk's blocks, if they have been generated.
+    pub fn blocks(&self) -> Option<&ChunkStorage> {
+        if self.state.is_readable() {
+            Some(&self.blocks)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the chunk's blocks for editing, if they have been generated.
+    ///
+    /// Chunks which have been (or are being) meshed are marked dirty, so that
+    /// they're remeshed to reflect the edit, and the chunk is marked modified.
+    pub fn blocks_mut(&mut self) -> Option<&mut ChunkStorage> {
+        if !self.state.is_readable() {
+            return None;
+        }
+
+        self.mark_dirty();
+        self.modified = true;
+        Some(&mut self.blocks)
+    }
+
+    /// Whether the chunk's blocks have been edited since they were generated or
+    /// loaded, so that they differ from what would be generated or loaded again.
+    pub const fn is_modified(&self) -> bool {
+        self.modified
+    }
+
+    pub fn mark_modified(&mut self) {
+        self.modified = true;
+    }
+
+    /// Marks the chunk to be remeshed, if it has been (or is being) meshed, e.g.
+    /// after an edit to a neighbor along their shared border.
+    pub fn mark_dirty(&mut self) {
+        if matches!(self.state, ChunkState::Meshing | ChunkState::Ready) {
+            self.state = ChunkState::Dirty;
+        }
+    }
+
+    /// Returns the chunk's light levels, if its blocks have been generated.
+    pub fn light(&self) -> Option<&ChunkLight> {
+        if self.state.is_readable() {
+            Some(&self.light)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the chunk's light levels for editing, if its blocks have been
+    /// generated, marking it dirty as `blocks_mut` does.
+    pub fn light_mut(&mut self) -> Option<&mut ChunkLight> {
+        if !self.state.is_readable() {
+            return None;
+        }
+
+        self.mark_dirty();
+        Some(&mut self.light)
+    }
+
+    /// Whether the chunk has been lit since its blocks were generated.
+    pub const fn is_lit(&self) -> bool {
+        self.lit
+    }
+
+    pub fn mark_lit(&mut self) {
+        self.lit = true;
+    }
+
+    /// Replaces the chunk's blocks with freshly generated ones, completing generation.
+    ///
+    /// The chunk is left unlit, to be lit by `ChunkLightingSystem`, and unmodified.
+    pub fn complete_generation(&mut self, blocks: ChunkStorage) {
+        self.set_state(ChunkState::Generated);
+        self.blocks = blocks;
+        self.light = ChunkLight::default();
+        self.lit = false;
+        self.modified = false;
+    }
+
+    /// Takes a snapshot of the chunk's blocks, if they have been generated.
+    pub fn snapshot(&self) -> Option<StorageSnapshot> {
+        self.blocks().map(ChunkStorage::snapshot)
+    }
+
+    /// Takes a snapshot of the chunk's blocks along with a copy of its light
+    /// levels, if they have been generated.
+    pub fn snapshot_with_light(&self) -> Option<(StorageSnapshot, ChunkLight)> {
+        Some((self.snapshot()?, self.light()?.clone()))
+    }
+}
+
+/// Resource mapping chunk positions to their chunk entities.
+#[derive(Default)]
+pub struct ChunkMap {
+    entities: HashMap<ChunkPos, Entity>,
+}
+
+impl ChunkMap {
+    pub fn get(&self, position: ChunkPos) -> Option<Entity> {
+        self.entities.get(&position).copied()
+    }
+
+    pub fn insert(&mut self, position: ChunkPos, entity: Entity) -> Option<Entity> {
+        self.entities.insert(position, entity)
+    }
+
+    pub fn remove(&mut self, position: ChunkPos) -> Option<Entity> {
+        self.entities.remove(&position)
+    }
+
+    pub fn contains(&self, position: ChunkPos) -> bool {
+        self.entities.contains_key(&position)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (ChunkPos, Entity)> + '_ {
+        self.entities
+            .iter()
+            .map(|(position, entity)| (*position, *entity))
+    }
+
+    /// Returns the face-adjacent neighbors of `position`, ordered as `NEIGHBOR_OFFSETS`.
+    pub fn neighbors(&self, position: ChunkPos) -> [Option<Entity>; 6] {
+        position.neighbors().map(|neighbor| self.get(neighbor))
+    }
+}
+
+struct MeshJob {
+    completion: JobCompletion,
+    mesh: Arc<Mutex<Option<ChunkMesh>>>,
+}
+
+/// Meshes generated and dirty chunks on the background worker pool.
+pub struct ChunkMeshingSystem {
+    jobs: HashMap<Entity, MeshJob>,
+    max_jobs: usize,
+}
+
+impl ChunkMeshingSystem {
+    pub fn new() -> Self {
+        Self {
+            jobs: HashMap::new(),
+            max_jobs: usize::MAX,
+        }
+    }
+
+    /// Limits how many chunks may be meshing at once.
+    pub fn with_max_jobs(mut self, max_jobs: usize) -> Self {
+        self.max_jobs = max_jobs;
+        self
+    }
+
+    fn queue_mesh_job(
+        &mut self,
+        entity: Entity,
+        (blocks, light): (StorageSnapshot, ChunkLight),
+        neighbors: [Option<(StorageSnapshot, ChunkLight)>; 6],
+    ) -> bool {
+        let mesh = Arc::new(Mutex::new(None));
+        let mesh_clone = Arc::clone(&mesh);
+
+        let work = Box::new(move || {
+            let neighbor_blocks = [0, 1, 2, 3, 4, 5].map(|normal_index| {
+                neighbors[normal_index]
+                    .as_ref()
+                    .map(|(blocks, _)| blocks as &dyn BlockStorage)
+            });
+            let neighbor_light = [0, 1, 2, 3, 4, 5]
+                .map(|normal_index| neighbors[normal_index].as_ref().map(|(_, light)| light));
+
+            *mesh_clone.lock().unwrap() = Some(generate_packed_mesh(
+                &BLOCK_REGISTRY,
+                &blocks,
+                neighbor_blocks,
+                &light,
+                neighbor_light,
+            ));
+        });
+
+        match crate::concurrency::queue(work) {
+            Ok(completion) => {
+                self.jobs.insert(entity, MeshJob { completion, mesh });
+                true
+            }
+            Err(()) => {
+                warn!("Failed to queue mesh job; the worker pool is unavailable.");
+                false
+            }
+        }
+    }
+}
+
+impl<'a> specs::System<'a> for ChunkMeshingSystem {
+    type SystemData = (
+        specs::Entities<'a>,
+        specs::Read<'a, ChunkMap>,
+        specs::WriteStorage<'a, Chunk>,
+        specs::WriteStorage<'a, ChunkMesh>,
+    );
+
+    fn run(&mut self, (entities, chunk_map, mut chunks, mut meshes): Self::SystemData) {
+        use specs::Join;
+
+        // Collect completed mesh jobs.
+        let completed = self
+            .jobs
+            .iter()
+            .filter(|(_, job)| job.completion.is_complete())
+            .map(|(entity, _)| *entity)
+            .collect::<Vec<_>>();
+
+        for entity in completed {
+            let job = self.jobs.remove(&entity).unwrap();
+
+            if let Some(chunk) = chunks.get_mut(entity) {
+                if let Some(mesh) = job.mesh.lock().unwrap().take() {
+                    meshes.insert(entity, mesh).ok();
+                }
+
+                // Chunks edited while meshing remain dirty, and are meshed again.
+                if chunk.state() == ChunkState::Meshing {
+                    chunk.set_state(ChunkState::Ready);
+                }
+            }
+        }
+
+        // Find chunks whose neighbors are all either absent or readable, so that
+        // faces along their borders are culled correctly.
+        let meshable = (&entities, &chunks)
+            .join()
+            .filter(|(entity, chunk)| {
+                matches!(chunk.state(), ChunkState::Generated | ChunkState::Dirty)
+                    && !self.jobs.contains_key(entity)
+                    && chunk_map
+                        .neighbors(chunk.position())
+                        .iter()
+                        .flatten()
+                        .all(|neighbor| {
+                            chunks
+                                .get(*neighbor)
+                                .map_or(true, |neighbor| neighbor.state().is_readable())
+                        })
+            })
+            .map(|(entity, chunk)| (entity, chunk.position()))
+            .collect::<Vec<_>>();
+
+        for (entity, position) in meshable {
+            if self.jobs.len() >= self.max_jobs {
+                break;
+            }
+
+            // Edited chunks are re-encoded before being snapshotted, so that chunks
+            //  which stopped being edited don't keep their dense storage.
+            let chunk = chunks.get_mut(entity).unwrap();
+            chunk.blocks.settle();
+            let chunk = chunk.snapshot_with_light().unwrap();
+            let neighbors = chunk_map.neighbors(position).map(|neighbor| {
+                neighbor
+                    .and_then(|neighbor| chunks.get(neighbor))
+                    .and_then(Chunk::snapshot_with_light)
+            });
+
+            if self.queue_mesh_job(entity, chunk, neighbors) {
+                chunks
+                    .get_mut(entity)
+                    .unwrap()
+                    .set_state(ChunkState::Meshing);
+            }
+        }
+
+        // Forget jobs for chunks which have since been unloaded.
+        self.jobs.retain(|entity, _| entities.is_alive(*entity));
+    }
+}
@@ -0,0 +1,702 @@
+use super::{
+    BlockPos, BlockStorage, Chunk, ChunkMap, ChunkPos, Heightmaps, LocalPos, CHUNK_SIZE,
+    CHUNK_SIZE_CUBED, CHUNK_SIZE_SHIFT,
+};
+use crate::{
+    collections::Palette,
+    world::block::{Attributes, Block, BLOCK_REGISTRY, MAX_LIGHT_LVL},
+    DIRECTION,
+};
+use glam::IVec3;
+use specs::Entity;
+use std::collections::{HashSet, VecDeque};
+
+/// Kinds of light, which spread independently of each other.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LightChannel {
+    /// Light cast by light-emitting blocks.
+    Block,
+    /// Light cast by the sky onto blocks with nothing opaque above them.
+    Sky,
+}
+
+/// Light levels of each block in a chunk, indexed by local block index.
+///
+/// Levels are palette-compressed, since most chunks are lit uniformly (e.g. entirely
+/// dark underground). Clones share memory until next written to, so meshing jobs
+/// can cheaply take a copy.
+#[derive(Clone)]
+pub struct ChunkLight {
+    block: Palette<u8>,
+    sky: Palette<u8>,
+}
+
+impl ChunkLight {
+    pub fn new() -> Self {
+        Self {
+            block: Palette::new(CHUNK_SIZE_CUBED as usize, 0),
+            sky: Palette::new(CHUNK_SIZE_CUBED as usize, 0),
+        }
+    }
+
+    fn channel(&self, channel: LightChannel) -> &Palette<u8> {
+        match channel {
+            LightChannel::Block => &self.block,
+            LightChannel::Sky => &self.sky,
+        }
+    }
+
+    fn channel_mut(&mut self, channel: LightChannel) -> &mut Palette<u8> {
+        match channel {
+            LightChannel::Block => &mut self.block,
+            LightChannel::Sky => &mut self.sky,
+        }
+    }
+
+    pub fn get(&self, channel: LightChannel, index: usize) -> u8 {
+        *self.channel(channel).get(index)
+    }
+
+    pub fn set(&mut self, channel: LightChannel, index: usize, level: u8) {
+        self.channel_mut(channel).set(index, level);
+    }
+
+    pub fn fill(&mut self, channel: LightChannel, level: u8) {
+        self.channel_mut(channel).fill(level);
+    }
+
+    /// Level of the light cast by light-emitting blocks.
+    pub fn block_light(&self, index: usize) -> u8 {
+        self.get(LightChannel::Block, index)
+    }
+
+    pub fn set_block_light(&mut self, index: usize, level: u8) {
+        self.set(LightChannel::Block, index, level);
+    }
+
+    /// Level of the light cast by the sky.
+    pub fn sky_light(&self, index: usize) -> u8 {
+        self.get(LightChannel::Sky, index)
+    }
+
+    pub fn set_sky_light(&mut self, index: usize, level: u8) {
+        self.set(LightChannel::Sky, index, level);
+    }
+}
+
+impl Default for ChunkLight {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Resource queueing the positions of blocks edited since lighting was last
+/// updated.
+#[derive(Default)]
+pub struct LightUpdates {
+    positions: Vec<BlockPos>,
+}
+
+impl LightUpdates {
+    pub fn push(&mut self, position: BlockPos) {
+        self.positions.push(position);
+    }
+
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    fn take(&mut self) -> Vec<BlockPos> {
+        std::mem::take(&mut self.positions)
+    }
+}
+
+/// Flood fills a single channel of light through loaded chunks, across their borders.
+///
+/// Light spreads from its sources through transparent blocks, losing a level with
+/// each step. Lights are removed by flooding outwards from where they were,
+/// darkening every block they lit, then relighting the darkened area from the
+/// brighter blocks around it.
+///
+/// Block light is sourced from light-emitting blocks. Skylight is sourced at full
+/// strength from every transparent block above its column's height, so it falls
+/// straight down to the highest opaque block, and spreads sideways from there into
+/// overhangs and caves.
+struct LightEngine<'s, 'a> {
+    channel: LightChannel,
+    chunk_map: &'s ChunkMap,
+    chunks: &'s mut specs::WriteStorage<'a, Chunk>,
+    heightmaps: &'s mut Heightmaps,
+    /// Whether each block lets light through, and the level it emits, by id.
+    definitions: &'s [(bool, u8)],
+    propagation: VecDeque<BlockPos>,
+    removal: VecDeque<(BlockPos, u8)>,
+    /// Chunks whose meshes face blocks whose light changed.
+    changed: HashSet<ChunkPos>,
+}
+
+impl<'s, 'a> LightEngine<'s, 'a> {
+    fn new(
+        channel: LightChannel,
+        chunk_map: &'s ChunkMap,
+        chunks: &'s mut specs::WriteStorage<'a, Chunk>,
+        heightmaps: &'s mut Heightmaps,
+        definitions: &'s [(bool, u8)],
+    ) -> Self {
+        Self {
+            channel,
+            chunk_map,
+            chunks,
+            heightmaps,
+            definitions,
+            propagation: VecDeque::new(),
+            removal: VecDeque::new(),
+            changed: HashSet::new(),
+        }
+    }
+
+    /// Whether `block` lets light through, and the level it emits. Ids without a
+    /// definition, such as those registered since the definitions were gathered,
+    /// are treated as opaque and dark.
+    fn definition(&self, block: Block) -> (bool, u8) {
+        self.definitions
+            .get(block.id() as usize)
+            .copied()
+            .unwrap_or((false, 0))
+    }
+
+    fn is_transparent(&self, block: Block) -> bool {
+        self.definition(block).0
+    }
+
+    fn emission(&self, block: Block) -> u8 {
+        self.definition(block).1
+    }
+
+    /// Level of the light `block` is a source of at `position`.
+    fn source(&self, position: BlockPos, block: Block) -> u8 {
+        match self.channel {
+            LightChannel::Block => self.emission(block),
+            LightChannel::Sky => {
+                if self.is_transparent(block) && self.heightmaps.is_exposed(position) {
+                    MAX_LIGHT_LVL
+                } else {
+                    0
+                }
+            }
+        }
+    }
+
+    /// Returns the block at `position` and its light level, if its chunk is readable.
+    fn get(&self, position: BlockPos) -> Option<(Block, u8)> {
+        let (chunk_position, local) = position.split();
+        let chunk = self
+            .chunk_map
+            .get(chunk_position)
+            .and_then(|entity| self.chunks.get(entity))?;
+        let index = local.index().get();
+
+        Some((
+            chunk.blocks()?.get(index),
+            chunk.light()?.get(self.channel, index),
+        ))
+    }
+
+    fn set(&mut self, position: BlockPos, level: u8) {
+        let (chunk_position, local) = position.split();
+        let light = self
+            .chunk_map
+            .get(chunk_position)
+            .and_then(|entity| self.chunks.get_mut(entity))
+            .and_then(Chunk::light_mut);
+
+        if let Some(light) = light {
+            light.set(self.channel, local.index().get(), level);
+
+            // Faces along the chunk's borders are lit by its neighbors' blocks.
+            for direction in DIRECTION::ALL {
+                if local.offset(direction).is_none() {
+                    self.changed.insert(chunk_position.offset(direction));
+                }
+            }
+        }
+    }
+
+    /// Lights a freshly generated chunk, from both its own light sources and the
+    /// light of its neighbors.
+    fn light_chunk(&mut self, entity: Entity) {
+        let chunk = self.chunks.get(entity).unwrap();
+        let position = chunk.position();
+        let blocks = chunk.blocks().unwrap();
+        let mut buffer = vec![Block::AIR; CHUNK_SIZE_CUBED as usize];
+        blocks.copy_to_slice(&mut buffer);
+
+        match self.channel {
+            LightChannel::Block => self.light_emitters(position, &buffer),
+            LightChannel::Sky => self.light_sky(entity, position, &buffer),
+        }
+
+        // Relight the neighbors' borders, so that their light spreads into the chunk.
+        for direction in DIRECTION::ALL {
+            let neighbor = position.offset(direction);
+            let light = self
+                .chunk_map
+                .get(neighbor)
+                .and_then(|entity| self.chunks.get(entity))
+                .and_then(Chunk::light);
+
+            if let Some(light) = light {
+                for local in LocalPos::iter().filter(|local| local.offset(direction).is_none()) {
+                    let faced = local.wrapping_offset(direction);
+
+                    if light.get(self.channel, faced.index().get()) > 1 {
+                        self.propagation.push_back(neighbor.block(faced));
+                    }
+                }
+            }
+        }
+    }
+
+    fn light_emitters(&mut self, position: ChunkPos, blocks: &[Block]) {
+        if blocks.iter().all(|block| self.emission(*block) == 0) {
+            return;
+        }
+
+        for local in LocalPos::iter() {
+            let emission = self.emission(blocks[local.index().get()]);
+            if emission > 0 {
+                let emitter = position.block(local);
+                self.set(emitter, emission);
+                self.propagation.push_back(emitter);
+            }
+        }
+    }
+
+    /// Raises the heightmap of the chunk's column to the chunk's opaque blocks,
+    /// and lights the blocks left exposed to the sky.
+    fn light_sky(&mut self, entity: Entity, position: ChunkPos, blocks: &[Block]) {
+        let origin = position.origin();
+        self.heightmaps.add_chunk(position);
+
+        let mut covered = Vec::new();
+        for z in 0..CHUNK_SIZE {
+            for x in 0..CHUNK_SIZE {
+                let top = (0..CHUNK_SIZE).rev().find(|y| {
+                    let local = LocalPos::new(x, *y, z).unwrap();
+                    !self.is_transparent(blocks[local.index().get()])
+                });
+
+                if let Some(top) = top.map(|top| origin.y + top) {
+                    let (x, z) = (origin.x + x, origin.z + z);
+                    let height = self.heightmaps.height(x, z);
+
+                    if height.map_or(true, |height| top > height) {
+                        self.heightmaps.set_height(x, z, Some(top));
+
+                        // Lit chunks below are now covered between the old and new
+                        //  heights.
+                        covered.extend(
+                            self.column_blocks(x, z, height, top - 1)
+                                .into_iter()
+                                .filter(|block| block.y < origin.y),
+                        );
+                    }
+                }
+            }
+        }
+
+        // Heights of the chunk's columns, and those bordering them, relative to
+        //  the bottom of the chunk.
+        let heights = (-1..=CHUNK_SIZE)
+            .flat_map(|z| (-1..=CHUNK_SIZE).map(move |x| (x, z)))
+            .map(|(x, z)| {
+                self.heightmaps
+                    .height(origin.x + x, origin.z + z)
+                    .map_or(i32::MIN, |height| height - origin.y)
+            })
+            .collect::<Vec<_>>();
+        let is_exposed =
+            |x: i32, y: i32, z: i32| y > heights[((x + 1) + ((z + 1) * (CHUNK_SIZE + 2))) as usize];
+
+        if (0..CHUNK_SIZE).all(|z| (0..CHUNK_SIZE).all(|x| is_exposed(x, 0, z))) {
+            // Open sky, as in most chunks above the ground.
+            let chunk = self.chunks.get_mut(entity).unwrap();
+            chunk.light_mut().unwrap().fill(self.channel, MAX_LIGHT_LVL);
+            self.changed.extend(position.neighbors());
+        } else {
+            for local in LocalPos::iter() {
+                let IVec3 { x, y, z } = local.as_ivec3();
+                if is_exposed(x, y, z) {
+                    self.set(position.block(local), MAX_LIGHT_LVL);
+                }
+            }
+        }
+
+        // Only sources next to shaded blocks need to spread their light.
+        for local in LocalPos::iter() {
+            let IVec3 { x, y, z } = local.as_ivec3();
+            if is_exposed(x, y, z)
+                && !(is_exposed(x, y - 1, z)
+                    && is_exposed(x + 1, y, z)
+                    && is_exposed(x - 1, y, z)
+                    && is_exposed(x, y, z + 1)
+                    && is_exposed(x, y, z - 1))
+            {
+                self.propagation.push_back(position.block(local));
+            }
+        }
+
+        for block in covered {
+            self.update_block(block);
+        }
+    }
+
+    /// Returns the blocks of the column at `x` and `z` in lit chunks, from above
+    /// `above` (or the bottom of the column) up to and including `to`.
+    fn column_blocks(&self, x: i32, z: i32, above: Option<i32>, to: i32) -> Vec<BlockPos> {
+        let from = above.map_or(i32::MIN, |above| above + 1);
+
+        self.heightmaps
+            .column_chunks(x >> CHUNK_SIZE_SHIFT, z >> CHUNK_SIZE_SHIFT)
+            .flat_map(|chunk_y| (chunk_y * CHUNK_SIZE)..((chunk_y + 1) * CHUNK_SIZE))
+            .filter(|y| (from..=to).contains(y))
+            .map(|y| BlockPos::new(x, y, z))
+            .collect()
+    }
+
+    /// Returns the height of the highest opaque block at `x` and `z`, no higher
+    /// than `below`, among loaded chunks.
+    fn find_height(&self, x: i32, z: i32, below: i32) -> Option<i32> {
+        self.column_blocks(x, z, None, below)
+            .into_iter()
+            .filter(|block| {
+                self.get(*block)
+                    .map_or(false, |(block, _)| !self.is_transparent(block))
+            })
+            .map(|block| block.y)
+            .max()
+    }
+
+    /// Updates the heightmap for an edit at `position`, relighting blocks covered or
+    /// uncovered by it.
+    fn update_height(&mut self, position: BlockPos) {
+        let block = match self.get(position) {
+            Some((block, _)) => block,
+            None => return,
+        };
+
+        let (x, z) = (position.x, position.z);
+        let height = self.heightmaps.height(x, z);
+        let raised =
+            !self.is_transparent(block) && height.map_or(true, |height| position.y > height);
+        let lowered = self.is_transparent(block) && height == Some(position.y);
+
+        let changed = if raised {
+            self.heightmaps.set_height(x, z, Some(position.y));
+            self.column_blocks(x, z, height, position.y - 1)
+        } else if lowered {
+            let lowered = self.find_height(x, z, position.y - 1);
+            self.heightmaps.set_height(x, z, lowered);
+            self.column_blocks(x, z, lowered, position.y - 1)
+        } else {
+            return;
+        };
+
+        for block in changed {
+            self.update_block(block);
+        }
+    }
+
+    /// Relights the area around `position`, after its block was edited.
+    fn update_block(&mut self, position: BlockPos) {
+        let (block, level) = match self.get(position) {
+            Some(block) => block,
+            None => return,
+        };
+
+        if level > 0 {
+            self.set(position, 0);
+            self.removal.push_back((position, level));
+        }
+
+        let source = self.source(position, block);
+        if source > 0 {
+            self.set(position, source);
+            self.propagation.push_back(position);
+        }
+
+        // Light may now pass through the block from its neighbors.
+        if self.is_transparent(block) {
+            for direction in DIRECTION::ALL {
+                self.propagation.push_back(position.offset(direction));
+            }
+        }
+    }
+
+    /// Runs the removal and then propagation queues until both are empty.
+    fn flood(&mut self) {
+        while let Some((position, level)) = self.removal.pop_front() {
+            for direction in DIRECTION::ALL {
+                let neighbor = position.offset(direction);
+                let (block, neighbor_level) = match self.get(neighbor) {
+                    Some((_, 0)) | None => continue,
+                    Some(block) => block,
+                };
+
+                // Neighbors dimmer than the removed light were lit by it, unless
+                //  they're a source of that light themselves.
+                let source = self.source(neighbor, block);
+                if neighbor_level < level && source < neighbor_level {
+                    self.set(neighbor, source);
+                    self.removal.push_back((neighbor, neighbor_level));
+
+                    if source > 0 {
+                        self.propagation.push_back(neighbor);
+                    }
+                } else {
+                    self.propagation.push_back(neighbor);
+                }
+            }
+        }
+
+        while let Some(position) = self.propagation.pop_front() {
+            let level = match self.get(position) {
+                Some((_, level)) if level > 1 => level,
+                _ => continue,
+            };
+
+            for direction in DIRECTION::ALL {
+                let neighbor = position.offset(direction);
+
+                if let Some((block, neighbor_level)) = self.get(neighbor) {
+                    if neighbor_level < (level - 1) && self.is_transparent(block) {
+                        self.set(neighbor, level - 1);
+                        self.propagation.push_back(neighbor);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Marks every chunk facing changed light to be remeshed.
+    fn finish(self) {
+        for position in self.changed {
+            if let Some(chunk) = self
+                .chunk_map
+                .get(position)
+                .and_then(|entity| self.chunks.get_mut(entity))
+            {
+                chunk.mark_dirty();
+            }
+        }
+    }
+}
+
+/// Lights newly generated chunks, and relights the world around edited blocks.
+///
+/// Should run after anything which edits blocks, and before `ChunkMeshingSystem`,
+/// so that meshes are built with up to date light. Light isn't yet visible, since
+/// nothing draws the meshes it's baked into (see `ChunkMesh`).
+pub struct ChunkLightingSystem;
+
+impl<'a> specs::System<'a> for ChunkLightingSystem {
+    type SystemData = (
+        specs::Entities<'a>,
+        specs::Read<'a, ChunkMap>,
+        specs::Write<'a, LightUpdates>,
+        specs::Write<'a, Heightmaps>,
+        specs::WriteStorage<'a, Chunk>,
+    );
+
+    fn run(
+        &mut self,
+        (entities, chunk_map, mut updates, mut heightmaps, mut chunks): Self::SystemData,
+    ) {
+        use specs::Join;
+
+        let unlit = (&entities, &chunks)
+            .join()
+            .filter(|(_, chunk)| chunk.state().is_readable() && !chunk.is_lit())
+            .map(|(entity, _)| entity)
+            .collect::<Vec<_>>();
+
+        if unlit.is_empty() && updates.is_empty() {
+            return;
+        }
+
+        for entity in &unlit {
+            chunks.get_mut(*entity).unwrap().mark_lit();
+        }
+
+        let updates = updates.take();
+        let definitions = (0..BLOCK_REGISTRY.len() as u16)
+            .map(|id| {
+                (
+                    BLOCK_REGISTRY
+                        .get_block_attributes(id)
+                        .contains(Attributes::TRANSPARENT),
+                    BLOCK_REGISTRY.get_block_light(id),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        for channel in [LightChannel::Block, LightChannel::Sky] {
+            let mut engine = LightEngine::new(
+                channel,
+                &chunk_map,
+                &mut chunks,
+                &mut heightmaps,
+                &definitions,
+            );
+
+            for entity in &unlit {
+                engine.light_chunk(*entity);
+            }
+
+            for position in &updates {
+                if channel == LightChannel::Sky {
+                    engine.update_height(*position);
+                }
+                engine.update_block(*position);
+            }
+
+            engine.flood();
+            engine.finish();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::chunk::{ChunkState, ChunkStorage, LightUpdates, WorldBlocks};
+    use specs::{Builder, RunNow, World, WorldExt};
+
+    // A column of two chunks: solid stone beneath open air.
+    fn world() -> World {
+        let mut world = World::new();
+        world.register::<Chunk>();
+        world.insert(ChunkMap::default());
+        world.insert(LightUpdates::default());
+        world.insert(Heightmaps::default());
+
+        let stone = BLOCK_REGISTRY.core_block("stone");
+        for (position, block) in [
+            (ChunkPos::new(0, -1, 0), stone),
+            (ChunkPos::new(0, 0, 0), Block::AIR),
+        ] {
+            let mut chunk = Chunk::new(position);
+            chunk.set_state(ChunkState::Generating);
+            chunk.complete_generation(ChunkStorage::new(block));
+
+            let entity = world.create_entity().with(chunk).build();
+            world.write_resource::<ChunkMap>().insert(position, entity);
+        }
+
+        ChunkLightingSystem.run_now(&world);
+        world
+    }
+
+    fn set_block(world: &World, position: BlockPos, block: Block) {
+        world
+            .system_data::<WorldBlocks>()
+            .set_block(position, block)
+            .unwrap();
+        ChunkLightingSystem.run_now(world);
+    }
+
+    fn light(world: &World, channel: LightChannel, position: BlockPos) -> u8 {
+        let (chunk_position, local) = position.split();
+        let chunk_map = world.read_resource::<ChunkMap>();
+        let chunks = world.read_storage::<Chunk>();
+
+        chunks
+            .get(chunk_map.get(chunk_position).unwrap())
+            .and_then(Chunk::light)
+            .unwrap()
+            .get(channel, local.index().get())
+    }
+
+    #[test]
+    fn block_light_floods_around_obstacles_and_recedes() {
+        let world = world();
+        let torch = BLOCK_REGISTRY.core_block("torch");
+        let stone = BLOCK_REGISTRY.core_block("stone");
+
+        set_block(&world, BlockPos::new(5, 5, 5), torch);
+        for distance in 0..10 {
+            assert_eq!(
+                light(
+                    &world,
+                    LightChannel::Block,
+                    BlockPos::new(5 + distance, 5, 5)
+                ),
+                14 - (distance as u8)
+            );
+        }
+        assert_eq!(
+            light(&world, LightChannel::Block, BlockPos::new(6, 7, 8)),
+            8
+        );
+        assert_eq!(
+            light(&world, LightChannel::Block, BlockPos::new(5, 0, 5)),
+            9
+        );
+        assert_eq!(
+            light(&world, LightChannel::Block, BlockPos::new(5, -1, 5)),
+            0
+        );
+
+        // Light spreads around opaque blocks, rather than through them.
+        set_block(&world, BlockPos::new(6, 5, 5), stone);
+        assert_eq!(
+            light(&world, LightChannel::Block, BlockPos::new(6, 5, 5)),
+            0
+        );
+        assert_eq!(
+            light(&world, LightChannel::Block, BlockPos::new(7, 5, 5)),
+            10
+        );
+
+        set_block(&world, BlockPos::new(5, 5, 5), Block::AIR);
+        for x in 0..16 {
+            assert_eq!(
+                light(&world, LightChannel::Block, BlockPos::new(x, 5, 5)),
+                0
+            );
+        }
+    }
+
+    #[test]
+    fn sky_light_falls_off_beneath_roofs() {
+        let world = world();
+        let stone = BLOCK_REGISTRY.core_block("stone");
+        assert_eq!(light(&world, LightChannel::Sky, BlockPos::new(5, 0, 5)), 15);
+        assert_eq!(light(&world, LightChannel::Sky, BlockPos::new(5, -1, 5)), 0);
+
+        // A roof over x and z in 0..10, open to the sky at its edges.
+        {
+            let mut blocks = world.system_data::<WorldBlocks>();
+            for x in 0..10 {
+                for z in 0..10 {
+                    blocks.set_block(BlockPos::new(x, 5, z), stone).unwrap();
+                }
+            }
+        }
+        ChunkLightingSystem.run_now(&world);
+
+        assert_eq!(world.read_resource::<Heightmaps>().height(5, 5), Some(5));
+        assert_eq!(light(&world, LightChannel::Sky, BlockPos::new(5, 6, 5)), 15);
+        assert_eq!(light(&world, LightChannel::Sky, BlockPos::new(9, 4, 5)), 14);
+        assert_eq!(light(&world, LightChannel::Sky, BlockPos::new(5, 0, 5)), 10);
+
+        // Light pours straight down a hole in the roof.
+        set_block(&world, BlockPos::new(5, 5, 5), Block::AIR);
+        assert_eq!(light(&world, LightChannel::Sky, BlockPos::new(5, 0, 5)), 15);
+        assert_eq!(light(&world, LightChannel::Sky, BlockPos::new(6, 0, 5)), 14);
+    }
+}
@@ -1,10 +1,13 @@
-use super::{CHUNK_SIZE, CHUNK_SIZE_CUBED, CHUNK_SIZE_MASK, CHUNK_SIZE_SHIFT, CHUNK_SIZE_SQUARED};
+use super::{
+    BlockStorage, ChunkLight, LocalIndex, LocalPos, CHUNK_SIZE, CHUNK_SIZE_CUBED, CHUNK_SIZE_MASK,
+    CHUNK_SIZE_SHIFT,
+};
 use crate::{
-    collections::Palette,
     render::mesh::{PackedVertex, QuadIndexes, QuadVertexes},
     world::block::{self, Block, BlockRegistry},
     DIRECTION,
 };
+use specs::{Component, DenseVecStorage};
 
 const PACKED_VERTEX_BY_NORMAL_INDEX: [[i32; 4]; 6] = [
     [
@@ -51,24 +54,52 @@ const PACKED_VERTEX_BY_NORMAL_INDEX: [[i32; 4]; 6] = [
     ],
 ];
 
-const INDEX_STEP_BY_NORMAL_INDEX: [i32; 6] = [
-    1,
-    CHUNK_SIZE_SQUARED,
-    CHUNK_SIZE,
-    -1,
-    -CHUNK_SIZE_SQUARED,
-    -CHUNK_SIZE,
-];
+/// Offset of the block light level packed into each vertex position, above the
+/// position and normal bits.
+const PACKED_LIGHT_SHIFT: i32 = 24;
+/// Offset of the skylight level packed into each vertex position, above the block
+/// light level.
+const PACKED_SKY_LIGHT_SHIFT: i32 = 28;
+
+/// Packed quad mesh data generated from a chunk's blocks.
+///
+/// Drawing chunk meshes is out of scope for now: nothing uploads or renders
+/// `ChunkMesh`, so the light levels packed into its vertexes are only carried
+/// through for a chunk shader to decode once there is one.
+#[derive(Component)]
+#[storage(DenseVecStorage)]
+pub struct ChunkMesh {
+    pub indexes: Vec<QuadIndexes<u32>>,
+    pub vertexes: Vec<QuadVertexes<PackedVertex>>,
+}
+
+impl ChunkMesh {
+    pub const fn empty() -> Self {
+        Self {
+            indexes: Vec::new(),
+            vertexes: Vec::new(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.indexes.is_empty()
+    }
+}
 
+/// Generates a greedy mesh of the faces of `block_storage` which aren't culled by
+/// the blocks they face.
+///
+/// Each face is lit by the block light and skylight levels of the block it faces,
+/// and faces are only merged with those lit equally.
 pub fn generate_packed_mesh(
     block_registry: &BlockRegistry,
-    blocks_palette: &mut Palette<Block>,
-    neighbors: [Option<&Palette<Block>>; 6],
-) {
-    if blocks_palette.lookup_len() == 1
-        && blocks_palette.get_lookup_value(0).id() == BlockRegistry::AIR_ID
-    {
-        // return empty mesh data
+    block_storage: &dyn BlockStorage,
+    neighbors: [Option<&dyn BlockStorage>; 6],
+    light: &ChunkLight,
+    neighbor_light: [Option<&ChunkLight>; 6],
+) -> ChunkMesh {
+    if block_storage.distinct_len() == 1 && block_storage.get(0).id() == BlockRegistry::AIR_ID {
+        return ChunkMesh::empty();
     }
 
     // TODO create a pooled list for these
@@ -77,232 +108,218 @@ pub fn generate_packed_mesh(
     let mut blocks = [Block::AIR; CHUNK_SIZE_CUBED as usize];
     let mut faces = [DIRECTION::empty(); CHUNK_SIZE_CUBED as usize];
 
-    blocks_palette.copy_to_slice(&mut blocks);
+    block_storage.copy_to_slice(&mut blocks);
 
-    let mut index = -1;
-    for y in 0..CHUNK_SIZE {
-        for z in 0..CHUNK_SIZE {
-            for x in 0..CHUNK_SIZE {
-                index += 1;
+    for local_position in LocalPos::iter() {
+        let index = local_position.index().get() as i32;
+        let block = blocks[index as usize];
 
-                let block = blocks[index as usize];
+        if block.id() == 0 {
+            continue;
+        } else {
+            let is_transparent = block_registry
+                .get_block_attributes(block.id())
+                .contains(block::Attributes::TRANSPARENT);
+            let packed_position = pack_position(local_position);
 
-                if block.id() == 0 {
-                    continue;
-                } else {
-                    let is_transparent = block_registry
-                        .get_block_attributes(block.id())
-                        .contains(block::Attributes::TRANSPARENT);
-                    let local_position =
-                        x | (y << CHUNK_SIZE_SHIFT) | (z << CHUNK_SIZE_SHIFT << CHUNK_SIZE_SHIFT);
-
-                    // Iterate once over all 6 faces of the block.
-                    for normal_index in 0..6 {
-                        // Face direction always exists on a single bit, so we can iterate
-                        // directions by shifting with the normal index.
-                        let face_direction =
-                            DIRECTION::from_bits_truncate((1 << normal_index) as u8);
-
-                        // If the current normal already has a face.
-                        if faces[index as usize].contains(face_direction) {
-                            continue;
-                        }
-
-                        let is_negative_normal = ((normal_index as i32) - 3) >= 0;
-                        // Normal index constrained to represent the xyz axes.
-                        let component_index = normal_index % 3;
-                        let component_shift = CHUNK_SIZE_SHIFT * component_index;
+            // Iterate once over all 6 faces of the block.
+            for normal_index in 0..6 {
+                // Face direction always exists on a single bit, so we can iterate
+                // directions by shifting with the normal index.
+                let face_direction = DIRECTION::from_normal_index(normal_index);
 
-                        // Axis index of the current normal direction.
-                        let faced_axis_value =
-                            (local_position >> component_shift) & CHUNK_SIZE_MASK;
-                        // Indicates whether or not the face check is within the current chunk bounds.
-                        let facing_neighbor = (!is_negative_normal
-                            && (faced_axis_value == (CHUNK_SIZE - 1)))
-                            || (is_negative_normal && (faced_axis_value == 0));
-
-                        // Counts our successful traversals.
-                        let mut traversals = 0;
-                        for perpendicular_normal_index in 1..3 {
-                            let traversal_normal_index =
-                                (component_index + perpendicular_normal_index) % 3;
-                            let traversal_normal_shift = CHUNK_SIZE_SHIFT * traversal_normal_index;
-                            let traversal_normal_axis_value =
-                                (local_position >> traversal_normal_shift) & CHUNK_SIZE_MASK;
-                            // Amount to add to current index to 'traverse' our 1D array by 1 block in our current normal direction.
-                            let traversal_index_step =
-                                INDEX_STEP_BY_NORMAL_INDEX[traversal_normal_index as usize];
-                            let mut traversal_index = index + (traversals * traversal_index_step);
-                            let mut total_traversal_len = traversal_normal_axis_value + traversals;
-
-                            while total_traversal_len < CHUNK_SIZE
-                                && !faces[traversal_index as usize].contains(face_direction)
-                                && blocks[traversal_index as usize].id() == block.id()
-                            {
-                                if facing_neighbor {
-                                    // This block of code translates the local position to a local position in the neighbor
-                                    // in the direction of our perpendicular normal index.
-                                    let sign = if is_negative_normal { -1 } else { 1 };
-                                    let component_mask = CHUNK_SIZE_MASK << component_shift;
-                                    let traversal_local_position =
-                                        local_position + (traversals << traversal_normal_shift);
+                // If the current normal already has a face.
+                if faces[index as usize].contains(face_direction) {
+                    continue;
+                }
 
-                                    let neighbor_local_position = (!component_mask
-                                        & traversal_local_position)
-                                        | (wrap(
-                                            ((traversal_local_position & component_mask)
-                                                >> component_shift)
-                                                + sign,
-                                            CHUNK_SIZE,
-                                            0,
-                                            CHUNK_SIZE_MASK,
-                                        ) << component_shift);
+                let is_negative_normal = normal_index >= 3;
+                // Normal index constrained to represent the xyz axes.
+                let component_index = normal_index % 3;
 
-                                    // Index into the neighbor blocks collections and call .GetPoint() with adjusted local position.
-                                    //
-                                    // Remark: If there's no neighbor at the index given, no chunk exists there (for instance, chunks)
-                                    // at the edge of render distance).
-                                    let neighbor_x = neighbor_local_position & CHUNK_SIZE_MASK;
-                                    let neighbor_y = (neighbor_local_position >> CHUNK_SIZE_SHIFT)
-                                        & CHUNK_SIZE_MASK;
-                                    let neighbor_z = (neighbor_local_position
-                                        >> (CHUNK_SIZE_SHIFT * 2))
-                                        & CHUNK_SIZE_MASK;
+                // Indicates whether or not the face check is within the current chunk bounds.
+                let facing_neighbor = local_position.offset(face_direction).is_none();
 
-                                    let neighbor_blocks_index = neighbor_x
-                                        + (CHUNK_SIZE * (neighbor_z + (CHUNK_SIZE * neighbor_y)));
+                // Counts our successful traversals.
+                let mut traversals = 0;
+                // Block light and skylight levels of the block faced by the first face.
+                let mut face_light = None;
+                for perpendicular_normal_index in 1..3 {
+                    let traversal_normal_index = (component_index + perpendicular_normal_index) % 3;
+                    let traversal_normal_shift = CHUNK_SIZE_SHIFT * (traversal_normal_index as i32);
+                    let traversal_normal_axis_value =
+                        local_position.as_ivec3()[traversal_normal_index];
+                    // Amount to add to current index to 'traverse' our 1D array by 1 block in our current normal direction.
+                    let traversal_index_step =
+                        LocalIndex::step(DIRECTION::from_normal_index(traversal_normal_index));
+                    let mut traversal_index = index + (traversals * traversal_index_step);
+                    let mut total_traversal_len = traversal_normal_axis_value + traversals;
 
-                                    if let Some(neighbor_palette) = neighbors[normal_index as usize]
-                                    {
-                                        let faced_block_id =
-                                            neighbor_palette.get(neighbor_blocks_index as usize);
+                    while total_traversal_len < CHUNK_SIZE
+                        && !faces[traversal_index as usize].contains(face_direction)
+                        && blocks[traversal_index as usize].id() == block.id()
+                    {
+                        let faced_light;
 
-                                        if is_transparent {
-                                            if block.id() == faced_block_id.id() {
-                                                break;
-                                            }
-                                        } else if !block_registry
-                                            .get_block_attributes(faced_block_id.id())
-                                            .contains(block::Attributes::TRANSPARENT)
-                                        {
-                                            break;
-                                        }
-                                    }
-                                } else {
-                                    // Amount to add to current traversal index to get the block currently
-                                    // being faced by our traverser.
-                                    let faced_block_index = traversal_index
-                                        + INDEX_STEP_BY_NORMAL_INDEX[normal_index as usize];
-                                    let faced_block_id = blocks[faced_block_index as usize].id();
+                        if facing_neighbor {
+                            // Translates the traverser's local position to the local position it faces in
+                            // the neighbor.
+                            //
+                            // Remark: If there's no neighbor at the index given, no chunk exists there (for instance, chunks)
+                            // at the edge of render distance).
+                            let neighbor_index = LocalIndex::new(traversal_index as usize)
+                                .unwrap()
+                                .position()
+                                .wrapping_offset(face_direction)
+                                .index();
+                            faced_light = neighbor_light[normal_index].map_or((0, 0), |light| {
+                                (
+                                    light.block_light(neighbor_index.get()),
+                                    light.sky_light(neighbor_index.get()),
+                                )
+                            });
 
-                                    if is_transparent {
-                                        if block.id() == faced_block_id {
-                                            break;
-                                        }
-                                    } else if block_registry
-                                        .get_block_attributes(faced_block_id)
-                                        .contains(block::Attributes::TRANSPARENT)
-                                    {
-                                        if !is_negative_normal {
-                                            // The current face is culled, and the faced block is opaque, so
-                                            // cull its face adjacent to the current block.
-                                            faces[faced_block_index as usize] |=
-                                                DIRECTION::from_bits_truncate(
-                                                    (1 << ((normal_index + 3) % 6)) as u8,
-                                                );
-                                        }
+                            if let Some(neighbor_storage) = neighbors[normal_index] {
+                                let faced_block_id = neighbor_storage.get(neighbor_index.get());
 
+                                if is_transparent {
+                                    if block.id() == faced_block_id.id() {
                                         break;
                                     }
+                                } else if !block_registry
+                                    .get_block_attributes(faced_block_id.id())
+                                    .contains(block::Attributes::TRANSPARENT)
+                                {
+                                    break;
                                 }
-
-                                faces[traversal_index as usize] |= face_direction;
-                                traversal_index += traversal_index_step;
-                                total_traversal_len += 1;
-                                traversals += 1;
                             }
+                        } else {
+                            // Amount to add to current traversal index to get the block currently
+                            // being faced by our traverser.
+                            let faced_block_index =
+                                traversal_index + LocalIndex::step(face_direction);
+                            let faced_block_id = blocks[faced_block_index as usize].id();
+                            faced_light = (
+                                light.block_light(faced_block_index as usize),
+                                light.sky_light(faced_block_index as usize),
+                            );
+
+                            if is_transparent {
+                                if block.id() == faced_block_id {
+                                    break;
+                                }
+                            } else if block_registry
+                                .get_block_attributes(faced_block_id)
+                                .contains(block::Attributes::TRANSPARENT)
+                            {
+                                if !is_negative_normal {
+                                    // The current face is culled, and the faced block is opaque, so
+                                    // cull its face adjacent to the current block.
+                                    faces[faced_block_index as usize] |= face_direction.opposite();
+                                }
 
-                            // Face is occluded.
-                            if traversals == 0 {
                                 break;
                             }
-                            // If it's the first traversal and we've only made a 1x1 face, continue and test the next axis
-                            else if traversals == 1 && perpendicular_normal_index == 1 {
-                                continue;
-                            }
+                        }
+
+                        // Differently lit faces can't share vertexes.
+                        if *face_light.get_or_insert(faced_light) != faced_light {
+                            break;
+                        }
 
-                            let compressed_vertexes =
-                                PACKED_VERTEX_BY_NORMAL_INDEX[normal_index as usize];
-                            let traversal_component_mask =
-                                CHUNK_SIZE_MASK << traversal_normal_shift;
-                            let unary_traversal_component_mask = !traversal_component_mask;
+                        faces[traversal_index as usize] |= face_direction;
+                        traversal_index += traversal_index_step;
+                        total_traversal_len += 1;
+                        traversals += 1;
+                    }
 
-                            // This solution should probably be temporary, as it seems like it *should*
-                            // be able to be optimized.
-                            let uv_shift = (component_index
-                                + traversal_index
-                                + if component_index == 1 && traversal_normal_index == 2 {
-                                    1
-                                } else {
-                                    0
-                                })
-                                % 2;
+                    // Face is occluded.
+                    if traversals == 0 {
+                        break;
+                    }
+                    // If it's the first traversal and we've only made a 1x1 face, continue and test the next axis
+                    else if traversals == 1 && perpendicular_normal_index == 1 {
+                        continue;
+                    }
 
-                            let indexes_start = (vertexes.len() * 4) as u32;
-                            indexes.push(QuadIndexes::new([
-                                indexes_start + 0,
-                                indexes_start + 1,
-                                indexes_start + 3,
-                                indexes_start + 1,
-                                indexes_start + 2,
-                                indexes_start + 3,
-                            ]));
+                    let compressed_vertexes = PACKED_VERTEX_BY_NORMAL_INDEX[normal_index];
+                    let traversal_component_mask = CHUNK_SIZE_MASK << traversal_normal_shift;
+                    let unary_traversal_component_mask = !traversal_component_mask;
 
-                            // TODO uvz calculations
-                            vertexes.push(QuadVertexes::new([
-                                PackedVertex {
-                                    xyz: local_position
-                                        + (unary_traversal_component_mask & compressed_vertexes[0])
-                                        | ((compressed_vertexes[0] * traversals)
-                                            & traversal_component_mask),
-                                    uvz: 0,
-                                },
-                                PackedVertex {
-                                    xyz: local_position
-                                        + (unary_traversal_component_mask & compressed_vertexes[1])
-                                        | ((compressed_vertexes[1] * traversals)
-                                            & traversal_component_mask),
-                                    uvz: 0,
-                                },
-                                PackedVertex {
-                                    xyz: local_position
-                                        + (unary_traversal_component_mask & compressed_vertexes[2])
-                                        | ((compressed_vertexes[2] * traversals)
-                                            & traversal_component_mask),
-                                    uvz: 0,
-                                },
-                                PackedVertex {
-                                    xyz: local_position
-                                        + (unary_traversal_component_mask & compressed_vertexes[3])
-                                        | ((compressed_vertexes[3] * traversals)
-                                            & traversal_component_mask),
-                                    uvz: 0,
-                                },
-                            ]));
+                    // This solution should probably be temporary, as it seems like it *should*
+                    // be able to be optimized.
+                    let uv_shift = ((component_index as i32)
+                        + traversal_index
+                        + if component_index == 1 && traversal_normal_index == 2 {
+                            1
+                        } else {
+                            0
+                        })
+                        % 2;
 
-                            break;
-                        }
-                    }
+                    let (block_light, sky_light) = face_light.unwrap();
+                    let packed_light = ((block_light as i32) << PACKED_LIGHT_SHIFT)
+                        | ((sky_light as i32) << PACKED_SKY_LIGHT_SHIFT);
+                    let indexes_start = (vertexes.len() * 4) as u32;
+                    indexes.push(QuadIndexes::new([
+                        indexes_start + 0,
+                        indexes_start + 1,
+                        indexes_start + 3,
+                        indexes_start + 1,
+                        indexes_start + 2,
+                        indexes_start + 3,
+                    ]));
+
+                    // TODO uvz calculations
+                    vertexes.push(QuadVertexes::new([
+                        PackedVertex {
+                            xyz: packed_position
+                                + (unary_traversal_component_mask & compressed_vertexes[0])
+                                | ((compressed_vertexes[0] * traversals)
+                                    & traversal_component_mask)
+                                | packed_light,
+                            uvz: 0,
+                        },
+                        PackedVertex {
+                            xyz: packed_position
+                                + (unary_traversal_component_mask & compressed_vertexes[1])
+                                | ((compressed_vertexes[1] * traversals)
+                                    & traversal_component_mask)
+                                | packed_light,
+                            uvz: 0,
+                        },
+                        PackedVertex {
+                            xyz: packed_position
+                                + (unary_traversal_component_mask & compressed_vertexes[2])
+                                | ((compressed_vertexes[2] * traversals)
+                                    & traversal_component_mask)
+                                | packed_light,
+                            uvz: 0,
+                        },
+                        PackedVertex {
+                            xyz: packed_position
+                                + (unary_traversal_component_mask & compressed_vertexes[3])
+                                | ((compressed_vertexes[3] * traversals)
+                                    & traversal_component_mask)
+                                | packed_light,
+                            uvz: 0,
+                        },
+                    ]));
+
+                    break;
                 }
             }
         }
     }
+
+    ChunkMesh { indexes, vertexes }
 }
 
+/// Packs a local position into the vertex position format, with each component
+/// shifted by `CHUNK_SIZE_SHIFT` bits in xyz order.
 #[inline(always)]
-const fn wrap(mut value: i32, delta: i32, min_val: i32, max_val: i32) -> i32 {
-    let mod_val = (max_val + 1) - min_val;
-    value += delta - min_val;
-    value += (1 - (value / mod_val)) * mod_val;
-    (value % mod_val) + min_val
+const fn pack_position(local_position: LocalPos) -> i32 {
+    local_position.x()
+        | (local_position.y() << CHUNK_SIZE_SHIFT)
+        | (local_position.z() << (CHUNK_SIZE_SHIFT * 2))
 }
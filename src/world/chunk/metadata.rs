@@ -0,0 +1,252 @@
+use super::{ByteReader, RegionError};
+use crate::world::block::{Block, BlockRegistry};
+use std::path::Path;
+
+/// Version written at the head of every world metadata file.
+///
+/// Layout (little-endian):
+///  - `[u8; 4]` magic, `b"AWLD"`
+///  - `u8` format version
+///  - `u64` world seed
+///  - `u16` saved block count, followed by the `"group:name"` of each saved block
+///    id in order, as a `u16` byte length and UTF-8 bytes
+pub const METADATA_FORMAT_VERSION: u8 = 1;
+
+/// Name of the metadata file within a world's directory.
+pub const METADATA_FILE_NAME: &str = "world.meta";
+
+const METADATA_MAGIC: [u8; 4] = *b"AWLD";
+/// Name of the block standing in for saved blocks which are no longer registered.
+const UNKNOWN_BLOCK_NAME: &str = "core:unknown";
+
+/// Properties of a saved world which must survive restarts.
+///
+/// Saved chunks don't hold registry ids, which depend on the order blocks are
+/// registered in, but "saved ids": indexes into the metadata's table of block
+/// names. The table is only ever appended to, so saved ids never change meaning.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WorldMetadata {
+    pub seed: u64,
+    blocks: Vec<String>,
+}
+
+impl WorldMetadata {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            blocks: Vec::new(),
+        }
+    }
+
+    /// Names of the saved blocks, indexed by saved id.
+    pub fn blocks(&self) -> &[String] {
+        &self.blocks
+    }
+
+    /// Assigns saved ids to every block in `registry` which doesn't have one yet,
+    /// returning whether any were added.
+    pub fn register_missing(&mut self, registry: &BlockRegistry) -> bool {
+        let saved_len = self.blocks.len();
+
+        for id in 0..(registry.len() as u16) {
+            let name = registry.get_block_name(id);
+
+            if !self.blocks.contains(&name) {
+                self.blocks.push(name);
+            }
+        }
+
+        self.blocks.len() > saved_len
+    }
+
+    /// Maps between saved ids and the ids of the blocks currently in `registry`.
+    ///
+    /// Saved blocks which are no longer registered are loaded as `core:unknown`.
+    pub fn id_map(&self, registry: &BlockRegistry) -> BlockIdMap {
+        let unknown = registry
+            .get_block_id(UNKNOWN_BLOCK_NAME.to_string())
+            .unwrap_or_else(|| panic!("Block \"{}\" is not registered!", UNKNOWN_BLOCK_NAME));
+
+        let to_registry = self
+            .blocks
+            .iter()
+            .map(|name| {
+                registry.get_block_id(name.clone()).unwrap_or_else(|| {
+                    warn!(
+                        "Saved block \"{}\" is not registered; loading it as \"{}\".",
+                        name, UNKNOWN_BLOCK_NAME
+                    );
+                    unknown
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let mut to_saved = vec![None; registry.len()];
+        for (saved_id, id) in to_registry.iter().enumerate().rev() {
+            // Blocks which were unknown when loaded are saved as `core:unknown`, rather
+            //  than whichever missing block happened to come first.
+            if *id != unknown || self.blocks[saved_id] == UNKNOWN_BLOCK_NAME {
+                to_saved[*id as usize] = Some(saved_id as u16);
+            }
+        }
+
+        BlockIdMap {
+            to_registry,
+            to_saved,
+            unknown,
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&METADATA_MAGIC);
+        bytes.push(METADATA_FORMAT_VERSION);
+        bytes.extend_from_slice(&self.seed.to_le_bytes());
+        bytes.extend_from_slice(&(self.blocks.len() as u16).to_le_bytes());
+
+        for name in &self.blocks {
+            bytes.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            bytes.extend_from_slice(name.as_bytes());
+        }
+
+        bytes
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, RegionError> {
+        let mut reader = ByteReader::new(bytes);
+
+        if (reader.read_bytes(METADATA_MAGIC.len())? != METADATA_MAGIC)
+            || (reader.read_u8()? != METADATA_FORMAT_VERSION)
+        {
+            return Err(RegionError::InvalidHeader);
+        }
+
+        let seed = reader.read_u64()?;
+        let blocks = (0..reader.read_u16()?)
+            .map(|_| reader.read_string().map(str::to_string))
+            .collect::<Result<Vec<_>, _>>()?;
+        reader.finish()?;
+
+        Ok(Self { seed, blocks })
+    }
+
+    /// Loads the metadata of the world in `directory`, if it has been saved.
+    pub fn load(directory: &Path) -> Result<Option<Self>, RegionError> {
+        match std::fs::read(directory.join(METADATA_FILE_NAME)) {
+            Ok(bytes) => Self::decode(&bytes).map(Some),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    /// Saves the metadata into `directory`, replacing the previous file only once
+    /// the new one is completely written.
+    pub fn save(&self, directory: &Path) -> Result<(), RegionError> {
+        std::fs::create_dir_all(directory)?;
+
+        let path = directory.join(METADATA_FILE_NAME);
+        let temporary_path = path.with_extension("tmp");
+        std::fs::write(&temporary_path, self.encode())?;
+        std::fs::rename(&temporary_path, &path)?;
+
+        Ok(())
+    }
+}
+
+/// Translates blocks between registry ids and saved ids.
+#[derive(Debug, Clone)]
+pub struct BlockIdMap {
+    to_registry: Vec<u16>,
+    to_saved: Vec<Option<u16>>,
+    unknown: u16,
+}
+
+impl BlockIdMap {
+    /// Translates a saved block into a registered one. Ids missing from the saved
+    /// table become `core:unknown`.
+    pub fn to_registry(&self, block: Block) -> Block {
+        let id = self
+            .to_registry
+            .get(block.id() as usize)
+            .copied()
+            .unwrap_or(self.unknown);
+
+        Block::new(id, block.color(), block.light_lvl())
+    }
+
+    /// Whether the saved `block` is loaded as a `core:unknown` placeholder for a
+    /// block which is no longer registered, rather than as `core:unknown` itself.
+    pub fn is_placeholder(&self, block: Block) -> bool {
+        self.to_registry(block).id() == self.unknown
+            && self.to_saved[self.unknown as usize] != Some(block.id())
+    }
+
+    /// Registry id of `core:unknown`.
+    pub const fn unknown(&self) -> u16 {
+        self.unknown
+    }
+
+    /// Translates a registered block into a saved one.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the block was registered after the map was built.
+    pub fn to_saved(&self, block: Block) -> Block {
+        let id = self
+            .to_saved
+            .get(block.id() as usize)
+            .copied()
+            .flatten()
+            .unwrap_or_else(|| panic!("Block id {} has no saved id!", block.id()));
+
+        Block::new(id, block.color(), block.light_lvl())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn id(registry: &BlockRegistry, name: &str) -> u16 {
+        registry.get_block_id(name.to_string()).unwrap()
+    }
+
+    #[test]
+    fn saved_ids_are_remapped_to_registry_ids() {
+        let registry = BlockRegistry::default();
+        let mut metadata = WorldMetadata::new(7);
+        metadata.register_missing(&registry);
+
+        // A world saved with its blocks in reverse, and a block since removed.
+        metadata.blocks.reverse();
+        metadata.blocks.insert(1, "mod:gone".to_string());
+        let metadata = WorldMetadata::decode(&metadata.encode()).unwrap();
+        let id_map = metadata.id_map(&registry);
+
+        for (saved_id, name) in metadata.blocks().iter().enumerate() {
+            let block = Block::new(saved_id as u16, 3, 0);
+            if saved_id == 1 {
+                assert_eq!(
+                    id_map.to_registry(block).id(),
+                    id(&registry, "core:unknown")
+                );
+                assert!(id_map.is_placeholder(block));
+                continue;
+            }
+
+            let loaded = id_map.to_registry(block);
+            assert_eq!(loaded.id(), id(&registry, name));
+            assert_eq!(loaded.color(), 3);
+            assert_eq!(id_map.to_saved(loaded), block);
+            assert!(!id_map.is_placeholder(block));
+        }
+
+        // Ids beyond the saved table are unknown too.
+        let beyond = Block::new(metadata.blocks().len() as u16, 0, 0);
+        assert_eq!(
+            id_map.to_registry(beyond).id(),
+            id(&registry, "core:unknown")
+        );
+        assert!(id_map.is_placeholder(beyond));
+    }
+}
@@ -1,20 +1,35 @@
+mod codec;
+mod edit;
+mod generation;
+mod heightmap;
+mod lifecycle;
+mod light;
 mod mesher;
+mod metadata;
+mod pending;
+mod position;
+mod raycast;
+mod region;
+mod storage;
+mod streaming;
 
+pub use codec::*;
+pub use edit::*;
+pub use generation::*;
+pub use heightmap::*;
+pub use lifecycle::*;
+pub use light::*;
 pub use mesher::*;
-
-use crate::collections::Palette;
-
-use super::block::Block;
+pub use metadata::*;
+pub use pending::*;
+pub use position::*;
+pub use raycast::*;
+pub use region::*;
+pub use storage::*;
+pub use streaming::*;
 
 pub const CHUNK_SIZE: i32 = 32;
 pub const CHUNK_SIZE_SQUARED: i32 = CHUNK_SIZE.pow(2);
 pub const CHUNK_SIZE_CUBED: i32 = CHUNK_SIZE.pow(3);
 pub const CHUNK_SIZE_SHIFT: i32 = CHUNK_SIZE.trailing_zeros() as i32;
 pub const CHUNK_SIZE_MASK: i32 = CHUNK_SIZE - 1;
-
-pub trait ChunkGenerationStep {
-    fn gen_pass(&self, blocks: &mut Palette<Block>);
-}
-
-pub struct ChunkGenerationSystem {
-}
\ No newline at end of file
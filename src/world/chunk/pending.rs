@@ -0,0 +1,269 @@
+use super::{BlockStorageMut, ByteReader, ChunkPos, DecodeError, LocalIndex, RegionError};
+use crate::world::block::Block;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+};
+
+/// Version written at the head of every encoding of pending writes.
+///
+/// Layout (little-endian):
+///  - `[u8; 4]` magic, `b"APND"`
+///  - `u8` format version
+///  - `u32` chunk count, followed by each chunk as its `i32` x, y and z position,
+///    a `u32` write count and each of its writes as:
+///    - `u16` local index, `u16` block id and `u16` block color
+///    - `u8` replace rule: `0` any, `1` air, `2` solid or `3` only, followed for
+///      `3` by the `u16` id and `u16` color of the only block replaced
+pub const PENDING_FORMAT_VERSION: u8 = 1;
+
+const PENDING_MAGIC: [u8; 4] = *b"APND";
+
+/// Which existing blocks a write may replace.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Replace {
+    Any,
+    /// Only air, so that e.g. leaves never cut into terrain.
+    Air,
+    /// Anything but air, so that e.g. veins never extend into caves.
+    Solid,
+    /// Only the given block, so that e.g. ore only ever replaces stone.
+    Only(Block),
+}
+
+impl Replace {
+    pub fn allows(self, existing: Block) -> bool {
+        match self {
+            Replace::Any => true,
+            Replace::Air => existing == Block::AIR,
+            Replace::Solid => existing != Block::AIR,
+            Replace::Only(block) => existing == block,
+        }
+    }
+}
+
+/// A single block write into a chunk, addressed by local block index.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PendingWrite {
+    pub index: LocalIndex,
+    pub block: Block,
+    pub replace: Replace,
+}
+
+impl PendingWrite {
+    /// Translates the written block, and any block the write is limited to
+    /// replacing, with `map`.
+    pub fn map_blocks(self, map: impl Fn(Block) -> Block) -> Self {
+        Self {
+            block: map(self.block),
+            replace: match self.replace {
+                Replace::Only(block) => Replace::Only(map(block)),
+                replace => replace,
+            },
+            ..self
+        }
+    }
+
+    /// Applies the write to `blocks`, returning whether the block was replaced.
+    pub fn apply(&self, blocks: &mut dyn BlockStorageMut) -> bool {
+        let index = self.index.get();
+        if self.replace.allows(blocks.get(index)) && blocks.get(index) != self.block {
+            blocks.set(index, self.block);
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[derive(Default)]
+struct PendingWritesInner {
+    writes: HashMap<ChunkPos, Vec<PendingWrite>>,
+    // Positions written to since the last `take_fresh`.
+    fresh: HashSet<ChunkPos>,
+}
+
+/// Queue of block writes keyed by the position of the chunk they target.
+///
+/// Generation steps which spill over their chunk's edges (trees, ore veins, ...)
+/// push writes here from background workers. Each frame, the chunk generation
+/// system applies writes targeting chunks which have already been generated,
+/// and leaves the rest buffered until their chunk finishes generating.
+///
+/// Handles are cheap to clone, and every clone refers to the same queue.
+#[derive(Clone, Default)]
+pub struct PendingWrites {
+    inner: Arc<Mutex<PendingWritesInner>>,
+}
+
+impl PendingWrites {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queues writes into the chunk at `position`.
+    pub fn extend(&self, position: ChunkPos, writes: impl IntoIterator<Item = PendingWrite>) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.writes.entry(position).or_default().extend(writes);
+        inner.fresh.insert(position);
+    }
+
+    /// Queues every write of `writes`, grouped by chunk position.
+    pub fn extend_all(&self, writes: HashMap<ChunkPos, Vec<PendingWrite>>) {
+        let mut inner = self.inner.lock().unwrap();
+        for (position, chunk_writes) in writes {
+            inner
+                .writes
+                .entry(position)
+                .or_default()
+                .extend(chunk_writes);
+            inner.fresh.insert(position);
+        }
+    }
+
+    /// Removes and returns every write queued for the chunk at `position`.
+    pub fn take(&self, position: ChunkPos) -> Vec<PendingWrite> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.fresh.remove(&position);
+        inner.writes.remove(&position).unwrap_or_default()
+    }
+
+    /// Removes and returns the positions of chunks which have been written to since
+    /// this was last called. Their writes remain queued.
+    pub fn take_fresh(&self) -> Vec<ChunkPos> {
+        self.inner.lock().unwrap().fresh.drain().collect()
+    }
+
+    /// Removes and returns the writes queued for every chunk whose position
+    /// satisfies `predicate`, grouped by chunk position.
+    pub fn take_where(
+        &self,
+        mut predicate: impl FnMut(ChunkPos) -> bool,
+    ) -> HashMap<ChunkPos, Vec<PendingWrite>> {
+        let mut inner = self.inner.lock().unwrap();
+        let positions = inner
+            .writes
+            .keys()
+            .copied()
+            .filter(|position| predicate(*position))
+            .collect::<Vec<_>>();
+
+        positions
+            .into_iter()
+            .map(|position| {
+                inner.fresh.remove(&position);
+                (position, inner.writes.remove(&position).unwrap())
+            })
+            .collect()
+    }
+
+    /// Removes and returns every queued write, grouped by chunk position.
+    pub fn drain(&self) -> HashMap<ChunkPos, Vec<PendingWrite>> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.fresh.clear();
+        std::mem::take(&mut inner.writes)
+    }
+
+    /// Count of chunks with writes queued.
+    pub fn len(&self) -> usize {
+        self.inner.lock().unwrap().writes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+fn push_block(bytes: &mut Vec<u8>, block: Block) {
+    bytes.extend_from_slice(&block.id().to_le_bytes());
+    bytes.extend_from_slice(&block.color().to_le_bytes());
+}
+
+fn read_block(reader: &mut ByteReader) -> Result<Block, DecodeError> {
+    Ok(Block::new(reader.read_u16()?, reader.read_u16()?, 0))
+}
+
+/// Encodes writes queued for chunks which aren't loaded, so that they survive
+/// restarts.
+pub fn encode_pending_writes(writes: &HashMap<ChunkPos, Vec<PendingWrite>>) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(&PENDING_MAGIC);
+    bytes.push(PENDING_FORMAT_VERSION);
+    bytes.extend_from_slice(&(writes.len() as u32).to_le_bytes());
+
+    for (position, chunk_writes) in writes {
+        for coordinate in [position.x, position.y, position.z] {
+            bytes.extend_from_slice(&coordinate.to_le_bytes());
+        }
+        bytes.extend_from_slice(&(chunk_writes.len() as u32).to_le_bytes());
+
+        for write in chunk_writes {
+            bytes.extend_from_slice(&(write.index.get() as u16).to_le_bytes());
+            push_block(&mut bytes, write.block);
+
+            match write.replace {
+                Replace::Any => bytes.push(0),
+                Replace::Air => bytes.push(1),
+                Replace::Solid => bytes.push(2),
+                Replace::Only(block) => {
+                    bytes.push(3);
+                    push_block(&mut bytes, block);
+                }
+            }
+        }
+    }
+
+    bytes
+}
+
+/// Decodes writes previously encoded by `encode_pending_writes`.
+pub fn decode_pending_writes(
+    bytes: &[u8],
+) -> Result<HashMap<ChunkPos, Vec<PendingWrite>>, RegionError> {
+    let mut reader = ByteReader::new(bytes);
+
+    if (reader.read_bytes(PENDING_MAGIC.len())? != PENDING_MAGIC)
+        || (reader.read_u8()? != PENDING_FORMAT_VERSION)
+    {
+        return Err(RegionError::InvalidHeader);
+    }
+
+    let mut writes = HashMap::new();
+    for _ in 0..reader.read_u32()? {
+        let position = ChunkPos::new(
+            reader.read_u32()? as i32,
+            reader.read_u32()? as i32,
+            reader.read_u32()? as i32,
+        );
+
+        let chunk_writes = (0..reader.read_u32()?)
+            .map(|_| {
+                let index = reader.read_u16()?;
+                let index =
+                    LocalIndex::new(index as usize).ok_or(DecodeError::InvalidLocalIndex(index))?;
+                let block = read_block(&mut reader)?;
+                let replace = match reader.read_u8()? {
+                    0 => Replace::Any,
+                    1 => Replace::Air,
+                    2 => Replace::Solid,
+                    3 => Replace::Only(read_block(&mut reader)?),
+                    replace => return Err(DecodeError::InvalidReplace(replace)),
+                };
+
+                Ok(PendingWrite {
+                    index,
+                    block,
+                    replace,
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        writes
+            .entry(position)
+            .or_insert_with(Vec::new)
+            .extend(chunk_writes);
+    }
+    reader.finish()?;
+
+    Ok(writes)
+}
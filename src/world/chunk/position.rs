@@ -0,0 +1,447 @@
+use super::{CHUNK_SIZE, CHUNK_SIZE_CUBED, CHUNK_SIZE_MASK, CHUNK_SIZE_SHIFT, CHUNK_SIZE_SQUARED};
+use crate::DIRECTION;
+use glam::{IVec3, Vec3};
+
+/// Offsets to each face-adjacent position, ordered to match the bits of `DIRECTION`.
+pub const NEIGHBOR_OFFSETS: [IVec3; 6] = [
+    IVec3::new(1, 0, 0),
+    IVec3::new(0, 1, 0),
+    IVec3::new(0, 0, 1),
+    IVec3::new(-1, 0, 0),
+    IVec3::new(0, -1, 0),
+    IVec3::new(0, 0, -1),
+];
+
+/// Steps between the indexes of face-adjacent blocks, ordered as `NEIGHBOR_OFFSETS`.
+const INDEX_STEPS: [i32; 6] = [
+    1,
+    CHUNK_SIZE_SQUARED,
+    CHUNK_SIZE,
+    -1,
+    -CHUNK_SIZE_SQUARED,
+    -CHUNK_SIZE,
+];
+
+impl DIRECTION {
+    /// Every single direction, ordered by their bits.
+    pub const ALL: [DIRECTION; 6] = [
+        DIRECTION::EAST,
+        DIRECTION::UP,
+        DIRECTION::NORTH,
+        DIRECTION::WEST,
+        DIRECTION::DOWN,
+        DIRECTION::SOUTH,
+    ];
+
+    /// The single direction whose bit is `1 << normal_index`.
+    pub const fn from_normal_index(normal_index: usize) -> Self {
+        Self::from_bits_truncate(1 << normal_index)
+    }
+
+    /// Index of the lowest direction in `self`, as ordered in `ALL`.
+    pub const fn normal_index(self) -> usize {
+        self.bits().trailing_zeros() as usize
+    }
+
+    /// Every direction in `self` turned to face the other way.
+    pub const fn opposite(self) -> Self {
+        // Each direction's opposite lies three bits away.
+        Self::from_bits_truncate((self.bits() << 3) | (self.bits() >> 3))
+    }
+
+    /// Sum of the unit offsets of every direction in `self`, so that e.g.
+    /// `EAST | UP` offsets diagonally.
+    pub fn offset(self) -> IVec3 {
+        Self::ALL
+            .iter()
+            .filter(|direction| self.contains(**direction))
+            .fold(IVec3::ZERO, |offset, direction| {
+                offset + NEIGHBOR_OFFSETS[direction.normal_index()]
+            })
+    }
+}
+
+/// Position of a block, in block space.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct BlockPos {
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+}
+
+impl BlockPos {
+    pub const fn new(x: i32, y: i32, z: i32) -> Self {
+        Self { x, y, z }
+    }
+
+    pub const fn from_ivec3(position: IVec3) -> Self {
+        Self::new(position.x, position.y, position.z)
+    }
+
+    pub const fn as_ivec3(self) -> IVec3 {
+        IVec3::new(self.x, self.y, self.z)
+    }
+
+    /// Position of the block containing world-space `position`.
+    pub fn containing(position: Vec3) -> Self {
+        Self::from_ivec3(position.floor().as_ivec3())
+    }
+
+    /// Position of the chunk containing the block.
+    pub const fn chunk(self) -> ChunkPos {
+        ChunkPos::new(
+            self.x >> CHUNK_SIZE_SHIFT,
+            self.y >> CHUNK_SIZE_SHIFT,
+            self.z >> CHUNK_SIZE_SHIFT,
+        )
+    }
+
+    /// Position of the block within its chunk.
+    pub const fn local(self) -> LocalPos {
+        LocalPos {
+            x: (self.x & CHUNK_SIZE_MASK) as u8,
+            y: (self.y & CHUNK_SIZE_MASK) as u8,
+            z: (self.z & CHUNK_SIZE_MASK) as u8,
+        }
+    }
+
+    /// Splits the position into the position of its chunk and its local position.
+    pub const fn split(self) -> (ChunkPos, LocalPos) {
+        (self.chunk(), self.local())
+    }
+
+    pub fn offset(self, direction: DIRECTION) -> Self {
+        self + direction.offset()
+    }
+
+    /// Returns the face-adjacent neighbors of the block, ordered as `NEIGHBOR_OFFSETS`.
+    pub fn neighbors(self) -> [Self; 6] {
+        NEIGHBOR_OFFSETS.map(|offset| self + offset)
+    }
+}
+
+/// Position of a chunk, in chunk space.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct ChunkPos {
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+}
+
+impl ChunkPos {
+    pub const fn new(x: i32, y: i32, z: i32) -> Self {
+        Self { x, y, z }
+    }
+
+    pub const fn from_ivec3(position: IVec3) -> Self {
+        Self::new(position.x, position.y, position.z)
+    }
+
+    pub const fn as_ivec3(self) -> IVec3 {
+        IVec3::new(self.x, self.y, self.z)
+    }
+
+    /// Position of the chunk containing world-space `position`.
+    pub fn containing(position: Vec3) -> Self {
+        BlockPos::containing(position).chunk()
+    }
+
+    /// Position of the chunk's minimum corner, in block space.
+    pub const fn origin(self) -> BlockPos {
+        BlockPos::new(
+            self.x << CHUNK_SIZE_SHIFT,
+            self.y << CHUNK_SIZE_SHIFT,
+            self.z << CHUNK_SIZE_SHIFT,
+        )
+    }
+
+    /// Block-space position of `local` within the chunk.
+    pub const fn block(self, local: LocalPos) -> BlockPos {
+        let origin = self.origin();
+
+        BlockPos::new(
+            origin.x + (local.x as i32),
+            origin.y + (local.y as i32),
+            origin.z + (local.z as i32),
+        )
+    }
+
+    pub fn offset(self, direction: DIRECTION) -> Self {
+        self + direction.offset()
+    }
+
+    /// Returns the face-adjacent neighbors of the chunk, ordered as `NEIGHBOR_OFFSETS`.
+    pub fn neighbors(self) -> [Self; 6] {
+        NEIGHBOR_OFFSETS.map(|offset| self + offset)
+    }
+}
+
+/// Position of a block within its chunk, each component within `0..CHUNK_SIZE`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct LocalPos {
+    x: u8,
+    y: u8,
+    z: u8,
+}
+
+impl LocalPos {
+    /// Returns the local position `(x, y, z)`, if it lies within a chunk.
+    pub const fn new(x: i32, y: i32, z: i32) -> Option<Self> {
+        if (x as u32) < (CHUNK_SIZE as u32)
+            && (y as u32) < (CHUNK_SIZE as u32)
+            && (z as u32) < (CHUNK_SIZE as u32)
+        {
+            Some(Self {
+                x: x as u8,
+                y: y as u8,
+                z: z as u8,
+            })
+        } else {
+            None
+        }
+    }
+
+    pub const fn from_ivec3(position: IVec3) -> Option<Self> {
+        Self::new(position.x, position.y, position.z)
+    }
+
+    pub const fn as_ivec3(self) -> IVec3 {
+        IVec3::new(self.x as i32, self.y as i32, self.z as i32)
+    }
+
+    pub const fn x(self) -> i32 {
+        self.x as i32
+    }
+
+    pub const fn y(self) -> i32 {
+        self.y as i32
+    }
+
+    pub const fn z(self) -> i32 {
+        self.z as i32
+    }
+
+    pub const fn index(self) -> LocalIndex {
+        LocalIndex(
+            (self.x as u16)
+                | ((self.z as u16) << CHUNK_SIZE_SHIFT)
+                | ((self.y as u16) << (CHUNK_SIZE_SHIFT * 2)),
+        )
+    }
+
+    /// Returns the adjacent position in `direction`, if it lies within the chunk.
+    pub fn offset(self, direction: DIRECTION) -> Option<Self> {
+        Self::from_ivec3(self.as_ivec3() + direction.offset())
+    }
+
+    /// Returns the adjacent position in `direction`, wrapping around into the
+    /// neighboring chunk's local space.
+    pub fn wrapping_offset(self, direction: DIRECTION) -> Self {
+        let position = (self.as_ivec3() + direction.offset()) & CHUNK_SIZE_MASK;
+
+        Self {
+            x: position.x as u8,
+            y: position.y as u8,
+            z: position.z as u8,
+        }
+    }
+
+    /// Iterates over every local position, in index order.
+    pub fn iter() -> impl Iterator<Item = Self> {
+        LocalIndex::iter().map(LocalIndex::position)
+    }
+}
+
+impl From<LocalIndex> for LocalPos {
+    fn from(index: LocalIndex) -> Self {
+        index.position()
+    }
+}
+
+/// Index of a block within its chunk's storage, ordered by x, then z, then y.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub struct LocalIndex(u16);
+
+impl LocalIndex {
+    /// Returns the local index `index`, if it lies within a chunk.
+    pub const fn new(index: usize) -> Option<Self> {
+        if index < (CHUNK_SIZE_CUBED as usize) {
+            Some(Self(index as u16))
+        } else {
+            None
+        }
+    }
+
+    pub const fn get(self) -> usize {
+        self.0 as usize
+    }
+
+    pub const fn position(self) -> LocalPos {
+        LocalPos {
+            x: (self.0 as i32 & CHUNK_SIZE_MASK) as u8,
+            y: ((self.0 as i32 >> (CHUNK_SIZE_SHIFT * 2)) & CHUNK_SIZE_MASK) as u8,
+            z: ((self.0 as i32 >> CHUNK_SIZE_SHIFT) & CHUNK_SIZE_MASK) as u8,
+        }
+    }
+
+    /// Returns the index of the adjacent block in `direction`, if it lies within
+    /// the chunk.
+    pub fn offset(self, direction: DIRECTION) -> Option<Self> {
+        self.position().offset(direction).map(LocalPos::index)
+    }
+
+    /// Steps between this index and that of the adjacent block in the single
+    /// direction `direction`, which may lie outside the chunk.
+    pub const fn step(direction: DIRECTION) -> i32 {
+        INDEX_STEPS[direction.normal_index()]
+    }
+
+    /// Iterates over every local index, in order.
+    pub fn iter() -> impl Iterator<Item = Self> {
+        (0..(CHUNK_SIZE_CUBED as u16)).map(Self)
+    }
+}
+
+impl From<LocalPos> for LocalIndex {
+    fn from(position: LocalPos) -> Self {
+        position.index()
+    }
+}
+
+impl From<LocalIndex> for usize {
+    fn from(index: LocalIndex) -> Self {
+        index.get()
+    }
+}
+
+macro_rules! impl_global_position {
+    ($position:ty) => {
+        impl From<IVec3> for $position {
+            fn from(position: IVec3) -> Self {
+                Self::from_ivec3(position)
+            }
+        }
+
+        impl From<$position> for IVec3 {
+            fn from(position: $position) -> Self {
+                position.as_ivec3()
+            }
+        }
+
+        impl std::ops::Add<IVec3> for $position {
+            type Output = Self;
+
+            fn add(self, offset: IVec3) -> Self {
+                Self::from_ivec3(self.as_ivec3() + offset)
+            }
+        }
+
+        impl std::ops::Sub<IVec3> for $position {
+            type Output = Self;
+
+            fn sub(self, offset: IVec3) -> Self {
+                Self::from_ivec3(self.as_ivec3() - offset)
+            }
+        }
+
+        /// Offset from `other` to `self`.
+        impl std::ops::Sub for $position {
+            type Output = IVec3;
+
+            fn sub(self, other: Self) -> IVec3 {
+                self.as_ivec3() - other.as_ivec3()
+            }
+        }
+
+        impl std::fmt::Display for $position {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                std::fmt::Display::fmt(&self.as_ivec3(), f)
+            }
+        }
+    };
+}
+
+impl_global_position!(BlockPos);
+impl_global_position!(ChunkPos);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Block positions around chunk borders, on either side of the origin.
+    fn border_positions() -> Vec<BlockPos> {
+        let coordinates = [
+            -CHUNK_SIZE - 1,
+            -CHUNK_SIZE,
+            -1,
+            0,
+            1,
+            CHUNK_SIZE - 1,
+            CHUNK_SIZE,
+        ];
+
+        let mut positions = Vec::new();
+        for x in coordinates {
+            for y in coordinates {
+                for z in coordinates {
+                    positions.push(BlockPos::new(x, y, z));
+                }
+            }
+        }
+
+        positions
+    }
+
+    #[test]
+    fn block_positions_split_into_chunk_and_local_positions() {
+        for position in border_positions() {
+            let (chunk, local) = position.split();
+
+            assert_eq!(chunk.block(local), position);
+            assert_eq!(chunk.origin().chunk(), chunk);
+            assert_eq!(LocalIndex::from(local).position(), local);
+            assert_eq!(position - chunk.origin(), local.as_ivec3());
+        }
+
+        let (chunk, local) = BlockPos::containing(Vec3::new(-0.5, 31.5, 32.0)).split();
+        assert_eq!(chunk, ChunkPos::new(-1, 0, 1));
+        assert_eq!(
+            local,
+            LocalPos::new(CHUNK_SIZE - 1, CHUNK_SIZE - 1, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn local_indexes_round_trip_and_step_between_neighbors() {
+        assert!(LocalIndex::new(CHUNK_SIZE_CUBED as usize).is_none());
+        assert!(LocalPos::new(-1, 0, 0).is_none());
+        assert!(LocalPos::new(0, CHUNK_SIZE, 0).is_none());
+        assert_eq!(LocalIndex::iter().count(), CHUNK_SIZE_CUBED as usize);
+
+        for index in LocalIndex::iter() {
+            let local = index.position();
+            assert_eq!(local.index(), index);
+            assert_eq!(LocalPos::from_ivec3(local.as_ivec3()), Some(local));
+
+            for normal_index in 0..6 {
+                let direction = DIRECTION::from_normal_index(normal_index);
+                let wrapped = local.wrapping_offset(direction);
+
+                match index.offset(direction) {
+                    Some(neighbor) => {
+                        assert_eq!(
+                            neighbor.get() as i32,
+                            index.get() as i32 + LocalIndex::step(direction)
+                        );
+                        assert_eq!(neighbor.position(), wrapped);
+                    }
+                    // Wrapping lands in the neighboring chunk, on its opposite face.
+                    None => assert_eq!(
+                        ChunkPos::default().offset(direction).block(wrapped),
+                        ChunkPos::default().block(local).offset(direction)
+                    ),
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,133 @@
+use super::{BlockPos, BlockStorage, ChunkPos, ChunkStorage, WorldBlocks};
+use crate::{
+    world::block::{Attributes, Block, BLOCK_REGISTRY},
+    DIRECTION,
+};
+use glam::Vec3;
+
+/// Which blocks stop a raycast.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RaycastFilter {
+    /// Any block but air.
+    Solid,
+    /// Only blocks which aren't flagged `TRANSPARENT`.
+    Opaque,
+}
+
+impl RaycastFilter {
+    pub fn stops_at(self, block: Block) -> bool {
+        match self {
+            RaycastFilter::Solid => block != Block::AIR,
+            RaycastFilter::Opaque => !BLOCK_REGISTRY
+                .get_block_attributes(block.id())
+                .contains(Attributes::TRANSPARENT),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RaycastHit {
+    pub position: BlockPos,
+    pub block: Block,
+    /// Face of the block the ray entered through, or empty if the ray started
+    /// within the block.
+    pub face: DIRECTION,
+    /// Distance along the ray to where it entered the block.
+    pub distance: f32,
+}
+
+impl RaycastHit {
+    /// Position of the block on the other side of the hit face, where a block
+    /// placed against it would go.
+    pub fn adjacent(&self) -> BlockPos {
+        self.position.offset(self.face)
+    }
+}
+
+impl<'a> WorldBlocks<'a> {
+    /// Walks every block along the ray from world-space `origin` in `direction`,
+    /// returning the first which `filter` stops at, within `max_distance`.
+    ///
+    /// Rays stop without a hit on reaching a chunk which isn't loaded and generated.
+    /// Each chunk's blocks are looked up once, no matter how many of its blocks the
+    /// ray passes through.
+    pub fn raycast(
+        &self,
+        origin: Vec3,
+        direction: Vec3,
+        max_distance: f32,
+        filter: RaycastFilter,
+    ) -> Option<RaycastHit> {
+        let direction = direction.normalize_or_zero();
+        if direction == Vec3::ZERO {
+            return None;
+        }
+
+        let mut position = BlockPos::containing(origin).as_ivec3();
+        // Distance along the ray between crossings of each axis' block borders, and
+        //  to the next such crossing.
+        let mut delta = [f32::INFINITY; 3];
+        let mut next = [f32::INFINITY; 3];
+        for axis in 0..3 {
+            if direction[axis] != 0.0 {
+                let border = if direction[axis] > 0.0 {
+                    position[axis] + 1
+                } else {
+                    position[axis]
+                };
+
+                delta[axis] = direction[axis].recip().abs();
+                next[axis] = ((border as f32) - origin[axis]) / direction[axis];
+            }
+        }
+
+        let mut chunk: Option<(ChunkPos, &ChunkStorage)> = None;
+        let mut face = DIRECTION::empty();
+        let mut distance = 0.0;
+        loop {
+            let (chunk_position, local) = BlockPos::from(position).split();
+            let blocks = match chunk {
+                Some((cached_position, blocks)) if cached_position == chunk_position => blocks,
+                _ => {
+                    let blocks = self.chunk_blocks(chunk_position)?;
+                    chunk = Some((chunk_position, blocks));
+                    blocks
+                }
+            };
+
+            let block = blocks.get(local.index().get());
+            if filter.stops_at(block) {
+                return Some(RaycastHit {
+                    position: BlockPos::from(position),
+                    block,
+                    face,
+                    distance,
+                });
+            }
+
+            // Step into whichever neighbor the ray reaches first.
+            let axis = if next[0] <= next[1] && next[0] <= next[2] {
+                0
+            } else if next[1] <= next[2] {
+                1
+            } else {
+                2
+            };
+
+            distance = next[axis];
+            if distance > max_distance {
+                return None;
+            }
+
+            // The ray enters through the face opposite the direction it steps in.
+            if direction[axis] > 0.0 {
+                position[axis] += 1;
+                face = DIRECTION::from_normal_index(axis + 3);
+            } else {
+                position[axis] -= 1;
+                face = DIRECTION::from_normal_index(axis);
+            }
+            next[axis] += delta[axis];
+        }
+    }
+}
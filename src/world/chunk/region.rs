@@ -0,0 +1,979 @@
+use super::{
+    decode_chunk, decode_pending_writes, encode_chunk, encode_pending_writes, BlockIdMap,
+    BlockStorage, BlockStorageMut, ChunkPos, ChunkStorage, DecodeError, EncodeError, PendingWrite,
+    StorageSnapshot, CHUNK_SIZE_CUBED,
+};
+use crate::world::block::Block;
+use glam::IVec3;
+use std::{
+    collections::HashMap,
+    fs::{File, OpenOptions},
+    io::{Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+/// Width of a region along each axis, in chunks.
+pub const REGION_SIZE: i32 = 32;
+pub const REGION_SIZE_SHIFT: i32 = REGION_SIZE.trailing_zeros() as i32;
+pub const REGION_SIZE_MASK: i32 = REGION_SIZE - 1;
+/// Count of chunks in a region.
+pub const REGION_CHUNKS: usize = REGION_SIZE.pow(3) as usize;
+
+/// Version written at the head of every region file.
+///
+/// Layout (little-endian):
+///  - `[u8; 4]` magic, `b"AREG"`
+///  - `u8` format version, followed by 3 reserved bytes
+///  - an offset table of `REGION_CHUNKS` entries, ordered as blocks are within a
+///    chunk, each a `u32` first sector and a `u32` byte length, zero when absent
+///  - padding up to the first sector boundary after the table
+///  - each chunk's zlib-compressed `encode_chunk` output, starting at its first
+///    sector and spanning as many whole sectors as it needs
+pub const REGION_FORMAT_VERSION: u8 = 1;
+
+const REGION_MAGIC: [u8; 4] = *b"AREG";
+/// Name of the file holding writes queued for chunks which were never loaded.
+pub const PENDING_FILE_NAME: &str = "pending.bin";
+/// Granularity at which space is allocated to chunks, in bytes.
+const SECTOR_SIZE: u64 = 512;
+const TABLE_OFFSET: u64 = 8;
+const TABLE_ENTRY_SIZE: u64 = 8;
+const HEADER_SECTORS: u32 =
+    (((TABLE_OFFSET + (REGION_CHUNKS as u64 * TABLE_ENTRY_SIZE)) + SECTOR_SIZE - 1) / SECTOR_SIZE)
+        as u32;
+// Upper bound on the size of a decompressed chunk, so that a corrupt record can't
+//  request an arbitrarily large allocation.
+const MAX_DECOMPRESSED_LEN: u64 = 1 << 20;
+// Count of region files kept open by a `RegionStore`, beyond those in use.
+const MAX_OPEN_REGIONS: usize = 16;
+
+#[derive(Debug)]
+pub enum RegionError {
+    Io(std::io::Error),
+    /// The file exists, but isn't of a supported format or version.
+    InvalidHeader,
+    /// A chunk's record was decompressed, but doesn't hold a valid chunk.
+    Decode(DecodeError),
+    /// A chunk couldn't be encoded to be written.
+    Encode(EncodeError),
+}
+
+impl std::fmt::Display for RegionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "{}", error),
+            Self::InvalidHeader => write!(f, "unsupported file format"),
+            Self::Decode(error) => write!(f, "invalid chunk: {}", error),
+            Self::Encode(error) => write!(f, "unencodable chunk: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for RegionError {}
+
+impl From<std::io::Error> for RegionError {
+    fn from(error: std::io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+impl From<DecodeError> for RegionError {
+    fn from(error: DecodeError) -> Self {
+        Self::Decode(error)
+    }
+}
+
+impl From<EncodeError> for RegionError {
+    fn from(error: EncodeError) -> Self {
+        Self::Encode(error)
+    }
+}
+
+/// Splits a chunk position into the position of its region and the index of the
+/// chunk within it.
+pub fn split_chunk_position(position: ChunkPos) -> (IVec3, usize) {
+    let region = position.as_ivec3() >> REGION_SIZE_SHIFT;
+    let local = position.as_ivec3() & REGION_SIZE_MASK;
+
+    (
+        region,
+        (local.x + (local.z * REGION_SIZE) + (local.y * REGION_SIZE * REGION_SIZE)) as usize,
+    )
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+struct TableEntry {
+    first_sector: u32,
+    len: u32,
+}
+
+impl TableEntry {
+    const fn is_present(&self) -> bool {
+        self.len > 0
+    }
+
+    const fn sectors(&self) -> u32 {
+        ((self.len as u64 + SECTOR_SIZE - 1) / SECTOR_SIZE) as u32
+    }
+}
+
+/// A file holding the chunks of a single region, each of which may be read or
+/// written without touching the others.
+///
+/// Records are always written into free space, and only then pointed at by their
+/// table entries, so an interrupted write leaves either the previous record or
+/// the new one, and never a mix of both. Entries pointing past
+/// the end of a truncated file, or overlapping other entries, are discarded when
+/// the file is opened.
+pub struct RegionFile {
+    file: File,
+    table: Vec<TableEntry>,
+    // Whether each sector of the file is allocated to the header or a chunk.
+    used_sectors: Vec<bool>,
+}
+
+impl RegionFile {
+    /// Opens the region file at `path`, creating it if it doesn't exist.
+    pub fn open(path: &Path) -> Result<Self, RegionError> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+        let file_len = file.metadata()?.len();
+
+        let mut header = Vec::new();
+        (&mut file)
+            .take((HEADER_SECTORS as u64) * SECTOR_SIZE)
+            .read_to_end(&mut header)?;
+
+        // A file too short to hold a header was never completely written, so is
+        //  started afresh.
+        if header.len() < (TABLE_OFFSET as usize) {
+            header.clear();
+            header.extend_from_slice(&REGION_MAGIC);
+            header.extend_from_slice(&[REGION_FORMAT_VERSION, 0, 0, 0]);
+
+            file.seek(SeekFrom::Start(0))?;
+            file.write_all(&header)?;
+        } else if header[0..4] != REGION_MAGIC || header[4] != REGION_FORMAT_VERSION {
+            return Err(RegionError::InvalidHeader);
+        }
+
+        let file_sectors =
+            (((file_len + SECTOR_SIZE - 1) / SECTOR_SIZE) as usize).max(HEADER_SECTORS as usize);
+        let mut used_sectors = vec![false; file_sectors];
+        used_sectors[..(HEADER_SECTORS as usize)].fill(true);
+
+        let mut table = vec![TableEntry::default(); REGION_CHUNKS];
+        let mut discarded = Vec::new();
+        for (index, entry) in table.iter_mut().enumerate() {
+            let offset = (TABLE_OFFSET + ((index as u64) * TABLE_ENTRY_SIZE)) as usize;
+            let bytes = match header.get(offset..(offset + (TABLE_ENTRY_SIZE as usize))) {
+                Some(bytes) => bytes,
+                None => break,
+            };
+
+            let candidate = TableEntry {
+                first_sector: u32::from_le_bytes(bytes[0..4].try_into().unwrap()),
+                len: u32::from_le_bytes(bytes[4..8].try_into().unwrap()),
+            };
+            if !candidate.is_present() {
+                continue;
+            }
+
+            let sectors = (candidate.first_sector as usize)
+                ..((candidate.first_sector as usize) + (candidate.sectors() as usize));
+            let end = ((candidate.first_sector as u64) * SECTOR_SIZE) + (candidate.len as u64);
+
+            if end > file_len || used_sectors[sectors.clone()].iter().any(|used| *used) {
+                discarded.push(index);
+            } else {
+                used_sectors[sectors].fill(true);
+                *entry = candidate;
+            }
+        }
+
+        // Pad out a truncated header, so that chunks are never allocated within it.
+        if file_len < ((HEADER_SECTORS as u64) * SECTOR_SIZE) {
+            file.set_len((HEADER_SECTORS as u64) * SECTOR_SIZE)?;
+        }
+
+        let mut region = Self {
+            file,
+            table,
+            used_sectors,
+        };
+
+        if !discarded.is_empty() {
+            warn!(
+                "Discarding {} damaged chunks from region file {:?}.",
+                discarded.len(),
+                path
+            );
+
+            for index in discarded {
+                region.write_entry(index, TableEntry::default())?;
+            }
+        }
+
+        Ok(region)
+    }
+
+    /// Whether the chunk at `index` within the region has been written.
+    pub fn contains(&self, index: usize) -> bool {
+        self.table[index].is_present()
+    }
+
+    /// Reads the chunk at `index` within the region, if it has been written.
+    pub fn read_chunk(&mut self, index: usize) -> Result<Option<ChunkStorage>, RegionError> {
+        let entry = self.table[index];
+        if !entry.is_present() {
+            return Ok(None);
+        }
+
+        let mut compressed = vec![0; entry.len as usize];
+        self.file
+            .seek(SeekFrom::Start((entry.first_sector as u64) * SECTOR_SIZE))?;
+        self.file.read_exact(&mut compressed)?;
+
+        let mut encoded = Vec::new();
+        flate2::read::ZlibDecoder::new(compressed.as_slice())
+            .take(MAX_DECOMPRESSED_LEN)
+            .read_to_end(&mut encoded)?;
+
+        Ok(Some(decode_chunk(&encoded)?))
+    }
+
+    /// Writes the chunk at `index` within the region into newly allocated space,
+    /// releasing the space of its previous record once the new one is in place.
+    pub fn write_chunk(
+        &mut self,
+        index: usize,
+        blocks: &dyn BlockStorage,
+    ) -> Result<(), RegionError> {
+        let mut encoder =
+            flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(&encode_chunk(blocks)?)?;
+        let compressed = encoder.finish()?;
+
+        let previous = self.table[index];
+        let mut entry = TableEntry {
+            first_sector: 0,
+            len: compressed.len() as u32,
+        };
+        entry.first_sector = self.allocate(entry.sectors());
+
+        self.file
+            .seek(SeekFrom::Start((entry.first_sector as u64) * SECTOR_SIZE))?;
+        self.file.write_all(&compressed)?;
+
+        // Keep the file a whole number of sectors long.
+        let end = (entry.first_sector + entry.sectors()) as usize;
+        if end >= self.used_sectors.len() {
+            self.file.set_len((end as u64) * SECTOR_SIZE)?;
+        }
+
+        self.write_entry(index, entry)?;
+
+        // Space is only released once nothing points at it, so that the previous
+        //  record is never overwritten while it's still the one in the table.
+        if previous.is_present() {
+            self.release(previous);
+        }
+
+        Ok(())
+    }
+
+    /// Removes the chunk at `index` within the region, releasing its space.
+    pub fn remove_chunk(&mut self, index: usize) -> Result<(), RegionError> {
+        let previous = self.table[index];
+
+        if previous.is_present() {
+            self.write_entry(index, TableEntry::default())?;
+            self.release(previous);
+        }
+
+        Ok(())
+    }
+
+    fn write_entry(&mut self, index: usize, entry: TableEntry) -> Result<(), RegionError> {
+        let mut bytes = [0; TABLE_ENTRY_SIZE as usize];
+        bytes[0..4].copy_from_slice(&entry.first_sector.to_le_bytes());
+        bytes[4..8].copy_from_slice(&entry.len.to_le_bytes());
+
+        self.file.seek(SeekFrom::Start(
+            TABLE_OFFSET + ((index as u64) * TABLE_ENTRY_SIZE),
+        ))?;
+        self.file.write_all(&bytes)?;
+        self.table[index] = entry;
+
+        Ok(())
+    }
+
+    /// Finds the first run of `sectors` free sectors, extending the file if there
+    /// is none, and marks it used.
+    fn allocate(&mut self, sectors: u32) -> u32 {
+        let sectors = sectors as usize;
+
+        let mut run_start = 0;
+        let mut run_len = 0;
+        for (sector, used) in self.used_sectors.iter().enumerate() {
+            if *used {
+                run_len = 0;
+            } else {
+                if run_len == 0 {
+                    run_start = sector;
+                }
+
+                run_len += 1;
+                if run_len == sectors {
+                    break;
+                }
+            }
+        }
+
+        // Runs at the end of the file may be extended past it.
+        if run_len < sectors && (run_start + run_len) != self.used_sectors.len() {
+            run_start = self.used_sectors.len();
+        }
+        if (run_start + sectors) > self.used_sectors.len() {
+            self.used_sectors.resize(run_start + sectors, false);
+        }
+
+        self.used_sectors[run_start..(run_start + sectors)].fill(true);
+        run_start as u32
+    }
+
+    fn release(&mut self, entry: TableEntry) {
+        let start = entry.first_sector as usize;
+        self.used_sectors[start..(start + (entry.sectors() as usize))].fill(false);
+    }
+}
+
+/// Copies `blocks` into new storage, translating each block, along with its
+/// index, with `map`.
+fn map_blocks(blocks: &dyn BlockStorage, map: impl Fn(usize, Block) -> Block) -> ChunkStorage {
+    let mut mapped = vec![Block::AIR; CHUNK_SIZE_CUBED as usize];
+    blocks.copy_to_slice(&mut mapped);
+    for (index, block) in mapped.iter_mut().enumerate() {
+        *block = map(index, *block);
+    }
+
+    let mut storage = ChunkStorage::default();
+    storage.copy_from_slice(&mapped);
+
+    storage
+}
+
+/// Chunks saved to disk, as a directory of region files.
+///
+/// Saved chunks are held in memory until `flush`ed, and loads see them
+/// immediately, so saving is cheap enough for the main thread while writing to
+/// disk happens on background workers.
+///
+/// With an id map, blocks are written with their saved ids, and translated back
+/// to registry ids when loaded. Blocks loaded as placeholders for blocks which
+/// are no longer registered keep their saved ids for as long as they remain
+/// placeholders, so that re-registering the block restores them.
+///
+/// Writes queued for chunks which were never loaded are kept alongside the
+/// regions, in `PENDING_FILE_NAME`, until their chunk next generates.
+pub struct RegionStore {
+    directory: PathBuf,
+    id_map: Option<Arc<BlockIdMap>>,
+    regions: Mutex<HashMap<IVec3, Arc<Mutex<RegionFile>>>>,
+    // Chunks saved but not yet written, with the version at which they were saved.
+    unsaved: Mutex<HashMap<ChunkPos, (u64, StorageSnapshot)>>,
+    next_version: AtomicU64,
+    // Saved ids of the placeholder blocks in each loaded chunk, by block index.
+    placeholders: Mutex<HashMap<ChunkPos, HashMap<usize, u16>>>,
+    // Saved pending writes, with registry ids, read from disk on first use.
+    pending: Mutex<Option<HashMap<ChunkPos, Vec<PendingWrite>>>>,
+    pending_changed: AtomicBool,
+}
+
+impl RegionStore {
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self {
+            directory: directory.into(),
+            id_map: None,
+            regions: Mutex::new(HashMap::new()),
+            unsaved: Mutex::new(HashMap::new()),
+            next_version: AtomicU64::new(0),
+            placeholders: Mutex::new(HashMap::new()),
+            pending: Mutex::new(None),
+            pending_changed: AtomicBool::new(false),
+        }
+    }
+
+    /// Translates blocks between registry ids and the ids saved in region files.
+    pub fn with_id_map(mut self, id_map: Arc<BlockIdMap>) -> Self {
+        self.id_map = Some(id_map);
+        self
+    }
+
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    fn region_path(&self, region: IVec3) -> PathBuf {
+        self.directory
+            .join(format!("r.{}.{}.{}.region", region.x, region.y, region.z))
+    }
+
+    /// Returns the open region file at `region`, opening it if needed.
+    fn region(&self, region: IVec3) -> Result<Arc<Mutex<RegionFile>>, RegionError> {
+        let mut regions = self.regions.lock().unwrap();
+
+        if let Some(file) = regions.get(&region) {
+            return Ok(Arc::clone(file));
+        }
+
+        // Files are only closed while unused, so that no region is ever open twice.
+        if regions.len() >= MAX_OPEN_REGIONS {
+            regions.retain(|_, file| Arc::strong_count(file) > 1);
+        }
+
+        std::fs::create_dir_all(&self.directory)?;
+        let file = Arc::new(Mutex::new(RegionFile::open(&self.region_path(region))?));
+        regions.insert(region, Arc::clone(&file));
+
+        Ok(file)
+    }
+
+    /// Loads the chunk at `position`, if it has been saved.
+    pub fn load(&self, position: ChunkPos) -> Result<Option<ChunkStorage>, RegionError> {
+        if let Some((_, snapshot)) = self.unsaved.lock().unwrap().get(&position) {
+            return Ok(Some(map_blocks(snapshot, |_, block| block)));
+        }
+
+        let (region, index) = split_chunk_position(position);
+
+        // Regions which were never written to aren't created just to be read.
+        if !self.regions.lock().unwrap().contains_key(&region) && !self.region_path(region).exists()
+        {
+            return Ok(None);
+        }
+
+        let file = self.region(region)?;
+        let blocks = file.lock().unwrap().read_chunk(index)?;
+
+        Ok(match (blocks, &self.id_map) {
+            (Some(blocks), Some(id_map)) => {
+                let placeholders = (0..(CHUNK_SIZE_CUBED as usize))
+                    .map(|index| (index, blocks.get(index)))
+                    .filter(|(_, block)| id_map.is_placeholder(*block))
+                    .map(|(index, block)| (index, block.id()))
+                    .collect::<HashMap<_, _>>();
+                self.set_placeholders(position, placeholders);
+
+                Some(map_blocks(&blocks, |_, block| id_map.to_registry(block)))
+            }
+            (blocks, _) => blocks,
+        })
+    }
+
+    fn set_placeholders(&self, position: ChunkPos, placeholders: HashMap<usize, u16>) {
+        let mut all_placeholders = self.placeholders.lock().unwrap();
+
+        if placeholders.is_empty() {
+            all_placeholders.remove(&position);
+        } else {
+            all_placeholders.insert(position, placeholders);
+        }
+    }
+
+    /// Saves the blocks of the chunk at `position`, to be written by the next
+    /// `flush`.
+    pub fn save(&self, position: ChunkPos, blocks: StorageSnapshot) {
+        let version = self.next_version.fetch_add(1, Ordering::Relaxed);
+        self.unsaved
+            .lock()
+            .unwrap()
+            .insert(position, (version, blocks));
+    }
+
+    /// Runs `f` on the saved pending writes, reading them from disk if needed.
+    fn with_pending<T>(
+        &self,
+        f: impl FnOnce(&mut HashMap<ChunkPos, Vec<PendingWrite>>) -> T,
+    ) -> Result<T, RegionError> {
+        let mut pending = self.pending.lock().unwrap();
+
+        if pending.is_none() {
+            let writes = match std::fs::read(self.directory.join(PENDING_FILE_NAME)) {
+                Ok(bytes) => decode_pending_writes(&bytes)?,
+                Err(error) if error.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+                Err(error) => return Err(error.into()),
+            };
+
+            *pending = Some(match &self.id_map {
+                Some(id_map) => writes
+                    .into_iter()
+                    .map(|(position, writes)| {
+                        let writes = writes
+                            .into_iter()
+                            .map(|write| write.map_blocks(|block| id_map.to_registry(block)))
+                            .collect();
+                        (position, writes)
+                    })
+                    .collect(),
+                None => writes,
+            });
+        }
+
+        Ok(f(pending.as_mut().unwrap()))
+    }
+
+    /// Saves writes queued for the chunk at `position`, to be written by the next
+    /// `flush`. They're returned by `take_pending` once the chunk generates.
+    pub fn save_pending(
+        &self,
+        position: ChunkPos,
+        writes: impl IntoIterator<Item = PendingWrite>,
+    ) -> Result<(), RegionError> {
+        self.with_pending(|pending| pending.entry(position).or_default().extend(writes))?;
+        self.pending_changed.store(true, Ordering::Relaxed);
+
+        Ok(())
+    }
+
+    /// Removes and returns the saved writes queued for the chunk at `position`.
+    pub fn take_pending(&self, position: ChunkPos) -> Result<Vec<PendingWrite>, RegionError> {
+        let writes = self.with_pending(|pending| pending.remove(&position))?;
+        if writes.is_some() {
+            self.pending_changed.store(true, Ordering::Relaxed);
+        }
+
+        Ok(writes.unwrap_or_default())
+    }
+
+    /// Count of chunks saved but not yet written to disk.
+    pub fn unsaved_len(&self) -> usize {
+        self.unsaved.lock().unwrap().len()
+    }
+
+    /// Writes every saved chunk to disk, stopping at the first failure.
+    pub fn flush(&self) -> Result<(), RegionError> {
+        let unsaved = self
+            .unsaved
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(position, (version, snapshot))| (*position, *version, snapshot.clone()))
+            .collect::<Vec<_>>();
+
+        let is_latest = |position: &ChunkPos, version: u64| {
+            self.unsaved
+                .lock()
+                .unwrap()
+                .get(position)
+                .map(|(saved, _)| *saved)
+                == Some(version)
+        };
+
+        for (position, version, snapshot) in unsaved {
+            let (region, index) = split_chunk_position(position);
+            let file = self.region(region)?;
+            let mut file = file.lock().unwrap();
+
+            // Flushes may run concurrently, so chunks which have since been saved
+            //  again, or written by another flush, are skipped rather than written
+            //  over newer blocks.
+            if !is_latest(&position, version) {
+                continue;
+            }
+
+            match &self.id_map {
+                Some(id_map) => {
+                    // Placeholders are written with the saved ids they were loaded
+                    //  with, and forgotten once replaced.
+                    let placeholders = self
+                        .placeholders
+                        .lock()
+                        .unwrap()
+                        .get(&position)
+                        .cloned()
+                        .unwrap_or_default();
+                    let is_placeholder = |index: usize, block: Block| {
+                        (block.id() == id_map.unknown()) && placeholders.contains_key(&index)
+                    };
+
+                    file.write_chunk(
+                        index,
+                        &map_blocks(&snapshot, |index, block| {
+                            if is_placeholder(index, block) {
+                                Block::new(placeholders[&index], block.color(), block.light_lvl())
+                            } else {
+                                id_map.to_saved(block)
+                            }
+                        }),
+                    )?;
+
+                    self.set_placeholders(
+                        position,
+                        placeholders
+                            .iter()
+                            .filter(|(index, _)| is_placeholder(**index, snapshot.get(**index)))
+                            .map(|(index, saved_id)| (*index, *saved_id))
+                            .collect(),
+                    );
+                }
+                None => file.write_chunk(index, &snapshot)?,
+            }
+
+            let mut unsaved = self.unsaved.lock().unwrap();
+            if unsaved.get(&position).map(|(saved, _)| *saved) == Some(version) {
+                unsaved.remove(&position);
+            }
+        }
+
+        // Pending writes are written after the chunks they were taken by, so that
+        //  a failed flush never loses a write.
+        if self.pending_changed.swap(false, Ordering::Relaxed) {
+            let pending = self.pending.lock().unwrap();
+            let pending = pending.as_ref().unwrap();
+            let path = self.directory.join(PENDING_FILE_NAME);
+
+            let result = if pending.is_empty() {
+                match std::fs::remove_file(&path) {
+                    Err(error) if error.kind() != std::io::ErrorKind::NotFound => Err(error),
+                    _ => Ok(()),
+                }
+            } else {
+                let saved = match &self.id_map {
+                    Some(id_map) => pending
+                        .iter()
+                        .map(|(position, writes)| {
+                            let writes = writes
+                                .iter()
+                                .map(|write| write.map_blocks(|block| id_map.to_saved(block)))
+                                .collect();
+                            (*position, writes)
+                        })
+                        .collect(),
+                    None => pending.clone(),
+                };
+
+                let temporary_path = path.with_extension("tmp");
+                std::fs::create_dir_all(&self.directory)
+                    .and_then(|_| std::fs::write(&temporary_path, encode_pending_writes(&saved)))
+                    .and_then(|_| std::fs::rename(&temporary_path, &path))
+            };
+
+            if let Err(error) = result {
+                self.pending_changed.store(true, Ordering::Relaxed);
+                return Err(error.into());
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::{
+        block::{Attributes, BlockRegistry},
+        chunk::{LocalIndex, Replace, WorldMetadata},
+    };
+
+    // Path of a fresh region file in the system's temporary directory.
+    fn temp_path(name: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("region-{}-{}.region", name, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        path
+    }
+
+    // Pseudo-random blocks, which compress poorly and so span many sectors.
+    fn noisy_chunk(seed: u64) -> ChunkStorage {
+        let mut state = seed | 1;
+        let mut blocks = vec![Block::AIR; CHUNK_SIZE_CUBED as usize];
+        for block in blocks.iter_mut() {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            *block = Block::new((state % 64) as u16, 0, 0);
+        }
+
+        let mut storage = ChunkStorage::default();
+        storage.copy_from_slice(&blocks);
+
+        storage
+    }
+
+    fn assert_same_blocks(region: &mut RegionFile, index: usize, expected: &ChunkStorage) {
+        let found = region.read_chunk(index).unwrap().unwrap();
+        assert!(
+            (0..(CHUNK_SIZE_CUBED as usize)).all(|block| found.get(block) == expected.get(block)),
+            "chunk {} differs from the one written",
+            index
+        );
+    }
+
+    fn file_len(path: &Path) -> u64 {
+        std::fs::metadata(path).unwrap().len()
+    }
+
+    fn truncate(path: &Path, len: u64) {
+        OpenOptions::new()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_len(len)
+            .unwrap();
+    }
+
+    #[test]
+    fn chunk_positions_split_into_regions_and_indexes() {
+        let coordinates = [
+            -REGION_SIZE - 1,
+            -REGION_SIZE,
+            -1,
+            0,
+            1,
+            REGION_SIZE - 1,
+            REGION_SIZE,
+        ];
+        let mut seen = HashMap::new();
+
+        for x in coordinates {
+            for y in coordinates {
+                for z in coordinates {
+                    let position = ChunkPos::new(x, y, z);
+                    let (region, index) = split_chunk_position(position);
+                    assert!(index < REGION_CHUNKS);
+
+                    // Regions are laid out as chunks are, so indexes decode like block
+                    //  indexes within a chunk of matching size.
+                    let local = IVec3::new(
+                        index as i32 & REGION_SIZE_MASK,
+                        (index as i32 >> (REGION_SIZE_SHIFT * 2)) & REGION_SIZE_MASK,
+                        (index as i32 >> REGION_SIZE_SHIFT) & REGION_SIZE_MASK,
+                    );
+                    assert_eq!((region << REGION_SIZE_SHIFT) + local, position.as_ivec3());
+                    assert_eq!(seen.insert((region, index), position), None);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn chunks_survive_reopening() {
+        let path = temp_path("reopen");
+        let chunks = [noisy_chunk(1), ChunkStorage::default(), noisy_chunk(2)];
+
+        let mut region = RegionFile::open(&path).unwrap();
+        assert!(region.read_chunk(0).unwrap().is_none());
+        for (index, chunk) in chunks.iter().enumerate() {
+            region.write_chunk(index, chunk).unwrap();
+        }
+        region.remove_chunk(1).unwrap();
+        drop(region);
+
+        let mut region = RegionFile::open(&path).unwrap();
+        assert_same_blocks(&mut region, 0, &chunks[0]);
+        assert!(!region.contains(1));
+        assert_same_blocks(&mut region, 2, &chunks[2]);
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn truncated_records_are_discarded_on_reopening() {
+        let path = temp_path("truncated-record");
+        let chunks = [noisy_chunk(1), noisy_chunk(2), noisy_chunk(3)];
+
+        let mut region = RegionFile::open(&path).unwrap();
+        for (index, chunk) in chunks.iter().enumerate() {
+            region.write_chunk(index, chunk).unwrap();
+        }
+        drop(region);
+        let len = file_len(&path);
+
+        // Cut off the end of the last record written, which lies at the end of the file.
+        truncate(&path, len - SECTOR_SIZE - 1);
+
+        let mut region = RegionFile::open(&path).unwrap();
+        assert_same_blocks(&mut region, 0, &chunks[0]);
+        assert_same_blocks(&mut region, 1, &chunks[1]);
+        assert!(!region.contains(2));
+
+        // The discarded record's space is reused.
+        region.write_chunk(2, &chunks[2]).unwrap();
+        assert_same_blocks(&mut region, 2, &chunks[2]);
+        assert_eq!(file_len(&path), len);
+        drop(region);
+
+        let mut region = RegionFile::open(&path).unwrap();
+        for (index, chunk) in chunks.iter().enumerate() {
+            assert_same_blocks(&mut region, index, chunk);
+        }
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn truncated_headers_are_recovered_on_reopening() {
+        let path = temp_path("truncated-header");
+
+        let mut region = RegionFile::open(&path).unwrap();
+        region.write_chunk(0, &noisy_chunk(1)).unwrap();
+        drop(region);
+
+        // Part of the offset table remains, but its entries point past the end.
+        truncate(&path, 1000);
+        let mut region = RegionFile::open(&path).unwrap();
+        assert!((0..REGION_CHUNKS).all(|index| !region.contains(index)));
+        assert_eq!(file_len(&path), (HEADER_SECTORS as u64) * SECTOR_SIZE);
+
+        region.write_chunk(0, &noisy_chunk(2)).unwrap();
+        drop(region);
+        let mut region = RegionFile::open(&path).unwrap();
+        assert_same_blocks(&mut region, 0, &noisy_chunk(2));
+        drop(region);
+
+        // Too little remains to hold the magic and version, so the file starts afresh.
+        truncate(&path, 3);
+        let region = RegionFile::open(&path).unwrap();
+        assert!(!region.contains(0));
+        drop(region);
+        assert!(RegionFile::open(&path).is_ok());
+
+        std::fs::write(&path, b"NOT A REGION FILE").unwrap();
+        assert!(matches!(
+            RegionFile::open(&path),
+            Err(RegionError::InvalidHeader)
+        ));
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn rewrites_never_overwrite_the_previous_record() {
+        let path = temp_path("rewrite");
+
+        let mut region = RegionFile::open(&path).unwrap();
+        region.write_chunk(0, &noisy_chunk(1)).unwrap();
+        let previous = region.table[0];
+        let previous_bytes = std::fs::read(&path).unwrap();
+        let previous_record = (previous.first_sector as usize * SECTOR_SIZE as usize)
+            ..((previous.first_sector as usize * SECTOR_SIZE as usize) + previous.len as usize);
+
+        // Were the new entry never written, the previous record would remain intact.
+        region.write_chunk(0, &ChunkStorage::default()).unwrap();
+        assert_ne!(region.table[0].first_sector, previous.first_sector);
+        assert_eq!(
+            std::fs::read(&path).unwrap()[previous_record.clone()],
+            previous_bytes[previous_record]
+        );
+        let len = file_len(&path);
+
+        // Once replaced, the previous record's space is reused.
+        region.write_chunk(1, &noisy_chunk(1)).unwrap();
+        assert_eq!(region.table[1].first_sector, previous.first_sector);
+        assert_eq!(file_len(&path), len);
+        drop(region);
+
+        let mut region = RegionFile::open(&path).unwrap();
+        assert_same_blocks(&mut region, 0, &ChunkStorage::default());
+        assert_same_blocks(&mut region, 1, &noisy_chunk(1));
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn pending_writes_survive_reopening() {
+        let directory = std::env::temp_dir().join(format!("region-pending-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&directory);
+
+        let write = |index: usize, replace: Replace| PendingWrite {
+            index: LocalIndex::new(index).unwrap(),
+            block: Block::new(3, 7, 0),
+            replace,
+        };
+        let first = ChunkPos::new(-1, 2, 40);
+        let second = ChunkPos::new(0, 0, 0);
+        let writes = vec![
+            write(0, Replace::Any),
+            write(1, Replace::Air),
+            write(2, Replace::Solid),
+            write(
+                CHUNK_SIZE_CUBED as usize - 1,
+                Replace::Only(Block::new(1, 2, 0)),
+            ),
+        ];
+
+        let store = RegionStore::new(&directory);
+        store.save_pending(first, writes.clone()).unwrap();
+        store.save_pending(second, writes[..1].to_vec()).unwrap();
+        store.flush().unwrap();
+
+        let store = RegionStore::new(&directory);
+        assert_eq!(store.take_pending(first).unwrap(), writes);
+        assert!(store.take_pending(first).unwrap().is_empty());
+        store.flush().unwrap();
+
+        // Taken writes are gone once flushed, and the file with them once empty.
+        let store = RegionStore::new(&directory);
+        assert!(store.take_pending(first).unwrap().is_empty());
+        assert_eq!(store.take_pending(second).unwrap(), writes[..1]);
+        store.flush().unwrap();
+        assert!(!directory.join(PENDING_FILE_NAME).exists());
+
+        let _ = std::fs::remove_dir_all(&directory);
+    }
+
+    #[test]
+    fn placeholders_keep_their_saved_ids() {
+        let directory =
+            std::env::temp_dir().join(format!("region-placeholders-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&directory);
+        let position = ChunkPos::new(3, -1, 0);
+
+        // A world saved while "mod:gone" was registered, loaded after it was removed.
+        let with_gone = BlockRegistry::default();
+        let gone = with_gone.register_block("mod", "gone", Attributes::COLLIDEABLE);
+        let mut metadata = WorldMetadata::new(0);
+        metadata.register_missing(&with_gone);
+        let saved = metadata.id_map(&with_gone);
+        let registry = BlockRegistry::default();
+        let stone = registry.core_block("stone");
+        let unknown = registry.core_block("unknown");
+
+        let mut blocks = ChunkStorage::default();
+        blocks.set(0, saved.to_saved(Block::new(gone, 0, 0)));
+        blocks.set(1, saved.to_saved(Block::new(gone, 0, 0)));
+        blocks.set(2, saved.to_saved(unknown));
+        let store = RegionStore::new(&directory);
+        store.save(position, blocks.snapshot());
+        store.flush().unwrap();
+
+        // Re-saving twice shows the saved ids outlive the first write.
+        let store = RegionStore::new(&directory).with_id_map(Arc::new(metadata.id_map(&registry)));
+        let mut loaded = store.load(position).unwrap().unwrap();
+        assert!((0..3).all(|index| loaded.get(index) == unknown));
+        loaded.set(1, stone);
+        for _ in 0..2 {
+            store.save(position, loaded.snapshot());
+            store.flush().unwrap();
+        }
+
+        let store = RegionStore::new(&directory).with_id_map(Arc::new(metadata.id_map(&with_gone)));
+        let loaded = store.load(position).unwrap().unwrap();
+        assert_eq!(loaded.get(0), Block::new(gone, 0, 0));
+        assert_eq!(loaded.get(1), stone);
+        assert_eq!(loaded.get(2), unknown);
+
+        let _ = std::fs::remove_dir_all(&directory);
+    }
+}
@@ -0,0 +1,685 @@
+use super::{CHUNK_SIZE, CHUNK_SIZE_CUBED, CHUNK_SIZE_SQUARED};
+use crate::{
+    collections::{Palette, PaletteSnapshot},
+    world::block::Block,
+};
+use std::{ops::Range, sync::Arc};
+
+/// Read access to the blocks of a single chunk, indexed by local block index.
+pub trait BlockStorage {
+    fn len(&self) -> usize;
+
+    fn get(&self, index: usize) -> Block;
+
+    /// Count of distinct blocks currently held by the storage.
+    fn distinct_len(&self) -> usize;
+
+    fn copy_to_slice(&self, slice: &mut [Block]) {
+        assert!(
+            slice.len() >= self.len(),
+            "Slice must be large enough to accomodate storage contents."
+        );
+
+        for (index, block) in slice[..self.len()].iter_mut().enumerate() {
+            *block = self.get(index);
+        }
+    }
+}
+
+/// Write access to the blocks of a single chunk.
+pub trait BlockStorageMut: BlockStorage {
+    fn set(&mut self, index: usize, block: Block);
+
+    fn fill(&mut self, block: Block) {
+        self.fill_range(0..self.len(), block);
+    }
+
+    fn fill_range(&mut self, range: Range<usize>, block: Block) {
+        for index in range {
+            self.set(index, block);
+        }
+    }
+
+    /// Fills every block from `min` (inclusive) to `max` (exclusive), in local block
+    /// coordinates, coalescing rows which are contiguous in storage into single ranges.
+    fn fill_box(&mut self, min: glam::IVec3, max: glam::IVec3, block: Block) {
+        assert!(
+            min.cmpge(glam::IVec3::ZERO).all() && max.cmple(glam::IVec3::splat(CHUNK_SIZE)).all(),
+            "Box must be contained within the chunk."
+        );
+
+        if min.cmpge(max).any() {
+            return;
+        }
+
+        let mut pending: Option<Range<usize>> = None;
+        for y in min.y..max.y {
+            for z in min.z..max.z {
+                let row_index = ((y * CHUNK_SIZE_SQUARED) + (z * CHUNK_SIZE)) as usize;
+                let row = (row_index + (min.x as usize))..(row_index + (max.x as usize));
+
+                pending = match pending {
+                    Some(range) if range.end == row.start => Some(range.start..row.end),
+                    Some(range) => {
+                        self.fill_range(range, block);
+                        Some(row)
+                    }
+                    None => Some(row),
+                };
+            }
+        }
+
+        if let Some(range) = pending {
+            self.fill_range(range, block);
+        }
+    }
+
+    fn copy_from_slice(&mut self, slice: &[Block]) {
+        for (index, block) in slice.iter().enumerate() {
+            self.set(index, *block);
+        }
+    }
+}
+
+// Counts the distinct block ids yielded by `blocks`, for storages which don't track them.
+fn count_distinct(blocks: impl Iterator<Item = Block>) -> usize {
+    let mut distinct = Vec::<u16>::new();
+
+    for block in blocks {
+        if let Err(insert_index) = distinct.binary_search(&block.id()) {
+            distinct.insert(insert_index, block.id());
+        }
+    }
+
+    distinct.len()
+}
+
+// Counts the runs of consecutive equal blocks in `storage`.
+fn count_runs(storage: &dyn BlockStorage) -> usize {
+    let mut runs = 0;
+    let mut last_block = None;
+
+    for index in 0..storage.len() {
+        let block = storage.get(index);
+
+        if last_block != Some(block) {
+            runs += 1;
+            last_block = Some(block);
+        }
+    }
+
+    runs
+}
+
+impl BlockStorage for Palette<Block> {
+    fn len(&self) -> usize {
+        Palette::len(self)
+    }
+
+    fn get(&self, index: usize) -> Block {
+        *Palette::get(self, index)
+    }
+
+    fn distinct_len(&self) -> usize {
+        self.live_lookup_len()
+    }
+
+    fn copy_to_slice(&self, slice: &mut [Block]) {
+        Palette::copy_to_slice(self, slice)
+    }
+}
+
+impl BlockStorage for PaletteSnapshot<Block> {
+    fn len(&self) -> usize {
+        Palette::len(self)
+    }
+
+    fn get(&self, index: usize) -> Block {
+        *Palette::get(self, index)
+    }
+
+    fn distinct_len(&self) -> usize {
+        self.live_lookup_len()
+    }
+
+    fn copy_to_slice(&self, slice: &mut [Block]) {
+        Palette::copy_to_slice(self, slice)
+    }
+}
+
+impl BlockStorageMut for Palette<Block> {
+    fn set(&mut self, index: usize, block: Block) {
+        Palette::set(self, index, block)
+    }
+
+    fn fill(&mut self, block: Block) {
+        Palette::fill(self, block)
+    }
+
+    fn fill_range(&mut self, range: Range<usize>, block: Block) {
+        Palette::fill_range(self, range, block)
+    }
+
+    fn copy_from_slice(&mut self, slice: &[Block]) {
+        Palette::copy_from_slice(self, slice)
+    }
+}
+
+/// Storage for a chunk containing only a single block type, such as all-air or all-stone.
+///
+/// Uniform storage is read-only, as it cannot represent differing blocks; wrap it in a
+/// `ChunkStorage` to have it promoted on the first differing `set`.
+#[derive(Debug, Clone, Copy)]
+pub struct UniformStorage {
+    block: Block,
+}
+
+impl UniformStorage {
+    pub const fn new(block: Block) -> Self {
+        Self { block }
+    }
+
+    pub const fn block(&self) -> Block {
+        self.block
+    }
+}
+
+impl BlockStorage for UniformStorage {
+    fn len(&self) -> usize {
+        CHUNK_SIZE_CUBED as usize
+    }
+
+    fn get(&self, _: usize) -> Block {
+        self.block
+    }
+
+    fn distinct_len(&self) -> usize {
+        1
+    }
+
+    fn copy_to_slice(&self, slice: &mut [Block]) {
+        slice[..self.len()].fill(self.block);
+    }
+}
+
+/// Uncompressed storage, trading memory for the fastest possible reads and writes.
+#[derive(Clone)]
+pub struct DenseStorage {
+    blocks: Arc<Vec<Block>>,
+}
+
+impl DenseStorage {
+    pub fn new(block: Block) -> Self {
+        Self {
+            blocks: Arc::new(vec![block; CHUNK_SIZE_CUBED as usize]),
+        }
+    }
+
+    pub fn from_storage(storage: &dyn BlockStorage) -> Self {
+        let mut dense = Self::new(Block::AIR);
+        let blocks: &mut Vec<Block> = Arc::make_mut(&mut dense.blocks);
+        storage.copy_to_slice(blocks);
+
+        dense
+    }
+
+    pub fn as_slice(&self) -> &[Block] {
+        &self.blocks
+    }
+}
+
+impl BlockStorage for DenseStorage {
+    fn len(&self) -> usize {
+        self.blocks.len()
+    }
+
+    fn get(&self, index: usize) -> Block {
+        self.blocks[index]
+    }
+
+    fn distinct_len(&self) -> usize {
+        count_distinct(self.blocks.iter().copied())
+    }
+
+    fn copy_to_slice(&self, slice: &mut [Block]) {
+        slice[..self.len()].copy_from_slice(&self.blocks);
+    }
+}
+
+impl BlockStorageMut for DenseStorage {
+    fn set(&mut self, index: usize, block: Block) {
+        Arc::make_mut(&mut self.blocks)[index] = block;
+    }
+
+    fn fill_range(&mut self, range: Range<usize>, block: Block) {
+        Arc::make_mut(&mut self.blocks)[range].fill(block);
+    }
+
+    fn copy_from_slice(&mut self, slice: &[Block]) {
+        Arc::make_mut(&mut self.blocks).copy_from_slice(slice);
+    }
+}
+
+/// Run-length encoded storage, well suited to chunks made up of large
+/// contiguous layers, such as freshly generated terrain.
+#[derive(Clone)]
+pub struct RunLengthStorage {
+    // Each run is stored as its (exclusive) end index and block, ordered by end index.
+    runs: Arc<Vec<(u32, Block)>>,
+}
+
+impl RunLengthStorage {
+    pub fn new(block: Block) -> Self {
+        Self {
+            runs: Arc::new(vec![(CHUNK_SIZE_CUBED as u32, block)]),
+        }
+    }
+
+    pub fn from_storage(storage: &dyn BlockStorage) -> Self {
+        let mut runs = Vec::<(u32, Block)>::new();
+
+        for index in 0..storage.len() {
+            let block = storage.get(index);
+
+            match runs.last_mut() {
+                Some((end, run_block)) if *run_block == block => *end = (index + 1) as u32,
+                _ => runs.push(((index + 1) as u32, block)),
+            }
+        }
+
+        Self {
+            runs: Arc::new(runs),
+        }
+    }
+
+    pub fn runs_len(&self) -> usize {
+        self.runs.len()
+    }
+
+    fn find_run(&self, index: usize) -> usize {
+        self.runs
+            .partition_point(|(end, _)| (*end as usize) <= index)
+    }
+}
+
+impl BlockStorage for RunLengthStorage {
+    fn len(&self) -> usize {
+        self.runs.last().map_or(0, |(end, _)| *end as usize)
+    }
+
+    fn get(&self, index: usize) -> Block {
+        self.runs[self.find_run(index)].1
+    }
+
+    fn distinct_len(&self) -> usize {
+        count_distinct(self.runs.iter().map(|(_, block)| *block))
+    }
+
+    fn copy_to_slice(&self, slice: &mut [Block]) {
+        let mut start = 0;
+
+        for (end, block) in self.runs.iter() {
+            slice[start..(*end as usize)].fill(*block);
+            start = *end as usize;
+        }
+    }
+}
+
+impl BlockStorageMut for RunLengthStorage {
+    fn set(&mut self, index: usize, block: Block) {
+        self.fill_range(index..(index + 1), block);
+    }
+
+    fn fill_range(&mut self, range: Range<usize>, block: Block) {
+        if range.is_empty() {
+            return;
+        }
+
+        let first_run_index = self.find_run(range.start);
+        let last_run_index = self.find_run(range.end - 1);
+        let (last_end, last_block) = self.runs[last_run_index];
+        let first_block = self.runs[first_run_index].1;
+        let first_start = match first_run_index {
+            0 => 0,
+            first_run_index => self.runs[first_run_index - 1].0 as usize,
+        };
+
+        // Replace the runs overlapping the range with a single run, keeping
+        //  whatever parts of the outermost runs lie outside of it.
+        let mut split = Vec::with_capacity(3);
+        if range.start > first_start {
+            split.push((range.start as u32, first_block));
+        }
+        split.push((range.end as u32, block));
+        if range.end < (last_end as usize) {
+            split.push((last_end, last_block));
+        }
+        let runs = Arc::make_mut(&mut self.runs);
+        runs.splice(first_run_index..=last_run_index, split);
+
+        // Merge the new run into its neighbours, if they hold the same block.
+        let new_run_index = first_run_index + if range.start > first_start { 1 } else { 0 };
+        if (new_run_index + 1) < runs.len() && runs[new_run_index + 1].1 == block {
+            runs.remove(new_run_index);
+        }
+        if new_run_index > 0 && runs[new_run_index - 1].1 == block {
+            runs[new_run_index - 1].0 = runs[new_run_index].0;
+            runs.remove(new_run_index);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StorageKind {
+    Uniform,
+    Palette,
+    Dense,
+    RunLength,
+}
+
+#[derive(Clone)]
+enum StorageBackend {
+    Uniform(UniformStorage),
+    Palette(Palette<Block>),
+    Dense(DenseStorage),
+    RunLength(RunLengthStorage),
+}
+
+impl StorageBackend {
+    fn as_storage(&self) -> &dyn BlockStorage {
+        match self {
+            StorageBackend::Uniform(storage) => storage,
+            StorageBackend::Palette(storage) => storage,
+            StorageBackend::Dense(storage) => storage,
+            StorageBackend::RunLength(storage) => storage,
+        }
+    }
+}
+
+/// Block storage for a chunk which automatically moves between backends
+/// based on its content and how often it is edited.
+pub struct ChunkStorage {
+    backend: StorageBackend,
+    edits: usize,
+}
+
+impl ChunkStorage {
+    /// Run-length storage is demoted to a palette once it exceeds this many runs.
+    pub const MAX_RUNS: usize = 1024;
+    /// Palette storage is promoted to dense storage after this many edits
+    /// without an intervening `optimize` or `settle`.
+    pub const DENSE_EDIT_THRESHOLD: usize = 4096;
+    /// Dense storage is demoted by `settle` once it receives fewer than this many
+    /// edits between settles.
+    pub const DENSE_SETTLE_EDITS: usize = Self::DENSE_EDIT_THRESHOLD / 4;
+
+    pub const fn new(block: Block) -> Self {
+        Self {
+            backend: StorageBackend::Uniform(UniformStorage::new(block)),
+            edits: 0,
+        }
+    }
+
+    pub fn kind(&self) -> StorageKind {
+        match &self.backend {
+            StorageBackend::Uniform(_) => StorageKind::Uniform,
+            StorageBackend::Palette(_) => StorageKind::Palette,
+            StorageBackend::Dense(_) => StorageKind::Dense,
+            StorageBackend::RunLength(_) => StorageKind::RunLength,
+        }
+    }
+
+    fn storage(&self) -> &dyn BlockStorage {
+        self.backend.as_storage()
+    }
+
+    /// Returns an immutable view of the chunk's current blocks, sharing its backing
+    /// memory until the storage is next written to.
+    pub fn snapshot(&self) -> StorageSnapshot {
+        StorageSnapshot {
+            backend: self.backend.clone(),
+        }
+    }
+
+    fn palette_from_storage(storage: &dyn BlockStorage) -> Palette<Block> {
+        let mut blocks = vec![Block::AIR; storage.len()];
+        storage.copy_to_slice(&mut blocks);
+
+        let mut palette = Palette::new(storage.len(), Block::AIR);
+        palette.copy_from_slice(&blocks);
+
+        palette
+    }
+
+    /// Re-encodes the chunk into whichever backend best fits its current content,
+    /// demoting heavily edited chunks back to compact storage.
+    pub fn optimize(&mut self) {
+        self.edits = 0;
+
+        let storage = self.storage();
+        let kind = if storage.distinct_len() == 1 {
+            StorageKind::Uniform
+        } else if count_runs(storage) <= Self::MAX_RUNS {
+            StorageKind::RunLength
+        } else {
+            StorageKind::Palette
+        };
+
+        if kind == self.kind() {
+            if let StorageBackend::Palette(palette) = &mut self.backend {
+                palette.compact();
+            }
+
+            return;
+        }
+
+        let storage = self.storage();
+        self.backend = match kind {
+            StorageKind::Uniform => StorageBackend::Uniform(UniformStorage::new(storage.get(0))),
+            StorageKind::RunLength => {
+                StorageBackend::RunLength(RunLengthStorage::from_storage(storage))
+            }
+            _ => StorageBackend::Palette(Self::palette_from_storage(storage)),
+        };
+    }
+
+    /// Optimizes the chunk if it has been edited since it was last optimized or
+    /// settled, keeping dense storage while it's still being edited heavily.
+    ///
+    /// Called whenever the chunk is remeshed.
+    pub fn settle(&mut self) {
+        match self.edits {
+            0 => {}
+            edits if (self.kind() == StorageKind::Dense) && (edits >= Self::DENSE_SETTLE_EDITS) => {
+                self.edits = 0
+            }
+            _ => self.optimize(),
+        }
+    }
+}
+
+impl BlockStorage for ChunkStorage {
+    fn len(&self) -> usize {
+        self.storage().len()
+    }
+
+    fn get(&self, index: usize) -> Block {
+        self.storage().get(index)
+    }
+
+    fn distinct_len(&self) -> usize {
+        self.storage().distinct_len()
+    }
+
+    fn copy_to_slice(&self, slice: &mut [Block]) {
+        self.storage().copy_to_slice(slice)
+    }
+}
+
+impl BlockStorageMut for ChunkStorage {
+    fn set(&mut self, index: usize, block: Block) {
+        self.fill_range(index..(index + 1), block);
+    }
+
+    fn fill(&mut self, block: Block) {
+        self.edits = 0;
+        self.backend = StorageBackend::Uniform(UniformStorage::new(block));
+    }
+
+    fn fill_range(&mut self, range: Range<usize>, block: Block) {
+        self.edits += 1;
+
+        match &mut self.backend {
+            StorageBackend::Uniform(uniform) => {
+                if uniform.block() != block {
+                    let mut run_length = RunLengthStorage::new(uniform.block());
+                    run_length.fill_range(range, block);
+                    self.backend = StorageBackend::RunLength(run_length);
+                }
+            }
+
+            StorageBackend::RunLength(run_length) => {
+                run_length.fill_range(range, block);
+
+                if run_length.runs_len() > Self::MAX_RUNS {
+                    self.backend = StorageBackend::Palette(Self::palette_from_storage(run_length));
+                }
+            }
+
+            StorageBackend::Palette(palette) => {
+                if range.len() == 1 {
+                    palette.set(range.start, block);
+                } else {
+                    palette.fill_range(range, block);
+                }
+
+                if self.edits > Self::DENSE_EDIT_THRESHOLD {
+                    self.backend = StorageBackend::Dense(DenseStorage::from_storage(palette));
+                }
+            }
+
+            StorageBackend::Dense(dense) => dense.fill_range(range, block),
+        }
+    }
+
+    fn copy_from_slice(&mut self, slice: &[Block]) {
+        let mut palette = Palette::new(slice.len(), Block::AIR);
+        palette.copy_from_slice(slice);
+
+        self.backend = StorageBackend::Palette(palette);
+        self.optimize();
+    }
+}
+
+impl Default for ChunkStorage {
+    fn default() -> Self {
+        Self::new(Block::AIR)
+    }
+}
+
+/// Immutable view of a `ChunkStorage` at the time it was taken, suitable for
+/// handing to worker jobs such as meshing and lighting.
+#[derive(Clone)]
+pub struct StorageSnapshot {
+    backend: StorageBackend,
+}
+
+impl BlockStorage for StorageSnapshot {
+    fn len(&self) -> usize {
+        self.backend.as_storage().len()
+    }
+
+    fn get(&self, index: usize) -> Block {
+        self.backend.as_storage().get(index)
+    }
+
+    fn distinct_len(&self) -> usize {
+        self.backend.as_storage().distinct_len()
+    }
+
+    fn copy_to_slice(&self, slice: &mut [Block]) {
+        self.backend.as_storage().copy_to_slice(slice)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STONE: Block = Block::new(2, 0, 0);
+    const DIRT: Block = Block::new(3, 0, 0);
+
+    fn blocks(storage: &dyn BlockStorage) -> Vec<Block> {
+        let mut blocks = vec![Block::AIR; storage.len()];
+        storage.copy_to_slice(&mut blocks);
+        blocks
+    }
+
+    #[test]
+    fn run_length_fills_merge_with_equal_neighbors() {
+        let mut storage = RunLengthStorage::new(Block::AIR);
+        let mut expected = vec![Block::AIR; CHUNK_SIZE_CUBED as usize];
+
+        storage.fill_range(100..200, STONE);
+        storage.fill_range(300..400, STONE);
+        expected[100..200].fill(STONE);
+        expected[300..400].fill(STONE);
+        assert_eq!(storage.runs_len(), 5);
+
+        // Bridging the gap merges all three stone runs into one.
+        storage.fill_range(200..300, STONE);
+        expected[200..300].fill(STONE);
+        assert_eq!(storage.runs_len(), 3);
+        assert_eq!(blocks(&storage), expected);
+
+        // Splitting a run keeps its outer parts.
+        storage.fill_range(150..160, DIRT);
+        expected[150..160].fill(DIRT);
+        assert_eq!(storage.runs_len(), 5);
+        assert_eq!(blocks(&storage), expected);
+
+        // Covering whole runs, and filling a run with its own block.
+        storage.fill_range(0..400, Block::AIR);
+        storage.fill_range(10..20, Block::AIR);
+        expected[0..400].fill(Block::AIR);
+        assert_eq!(storage.runs_len(), 1);
+        assert_eq!(blocks(&storage), expected);
+    }
+
+    #[test]
+    fn dense_storage_is_demoted_once_edits_slow() {
+        let len = CHUNK_SIZE_CUBED as usize;
+        let mut storage = ChunkStorage::default();
+        let mut expected = vec![Block::AIR; len];
+
+        // Scattered edits outgrow run-length storage, then promote the palette.
+        for index in (0..len)
+            .step_by(7)
+            .take(ChunkStorage::DENSE_EDIT_THRESHOLD + 1)
+        {
+            storage.set(index, STONE);
+            expected[index] = STONE;
+        }
+        assert_eq!(storage.kind(), StorageKind::Dense);
+
+        // Still being edited heavily.
+        storage.settle();
+        for index in (1..len).step_by(7).take(ChunkStorage::DENSE_SETTLE_EDITS) {
+            storage.set(index, DIRT);
+            expected[index] = DIRT;
+        }
+        storage.settle();
+        assert_eq!(storage.kind(), StorageKind::Dense);
+
+        storage.set(2, DIRT);
+        expected[2] = DIRT;
+        storage.settle();
+        assert_eq!(storage.kind(), StorageKind::Palette);
+        assert_eq!(blocks(&storage), expected);
+
+        // Chunks emptied by an edit collapse back to uniform storage.
+        storage.fill_range(0..len, Block::AIR);
+        storage.settle();
+        assert_eq!(storage.kind(), StorageKind::Uniform);
+    }
+}
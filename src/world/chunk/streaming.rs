@@ -0,0 +1,197 @@
+use super::{Chunk, ChunkMap, ChunkPos, ChunkState, Heightmaps, PendingWrites, RegionStore};
+use crate::{render::camera::Camera, world::Transform};
+use glam::IVec3;
+use std::sync::Arc;
+
+/// Loads chunks around every camera, and unloads those left behind.
+///
+/// Chunks are created nearest first, within `view_radius` chunks of a camera,
+/// and removed once they lie beyond `unload_radius` chunks of every camera. The
+/// gap between the two keeps chunks along the edge of view from being reloaded
+/// every time a camera moves back and forth across a chunk border.
+///
+/// With a region store, chunks which have been modified are saved as they're
+/// unloaded. Unmodified chunks are generated or loaded again just as they were.
+/// Writes queued for chunks beyond the unload radius are saved too, or dropped
+/// without a region store, rather than held in memory indefinitely. Heightmaps
+/// are dropped along with the last chunk of their column.
+pub struct ChunkStreamingSystem {
+    view_radius: i32,
+    unload_radius: i32,
+    max_loads_per_frame: usize,
+    region_store: Option<Arc<RegionStore>>,
+    // Offsets within the view radius, nearest first.
+    offsets: Vec<IVec3>,
+}
+
+impl ChunkStreamingSystem {
+    pub fn new(view_radius: i32, unload_radius: i32) -> Self {
+        assert!(view_radius >= 0, "View radius must not be negative.");
+        assert!(
+            unload_radius >= view_radius,
+            "Unload radius must be at least the view radius."
+        );
+
+        let mut offsets = Vec::new();
+        for y in -view_radius..=view_radius {
+            for z in -view_radius..=view_radius {
+                for x in -view_radius..=view_radius {
+                    let offset = IVec3::new(x, y, z);
+
+                    if offset.length_squared() <= (view_radius * view_radius) {
+                        offsets.push(offset);
+                    }
+                }
+            }
+        }
+        offsets.sort_by_key(|offset| offset.length_squared());
+
+        Self {
+            view_radius,
+            unload_radius,
+            max_loads_per_frame: usize::MAX,
+            region_store: None,
+            offsets,
+        }
+    }
+
+    /// Saves modified chunks into `region_store` as they're unloaded.
+    pub fn with_region_store(mut self, region_store: Arc<RegionStore>) -> Self {
+        self.region_store = Some(region_store);
+        self
+    }
+
+    /// Limits how many chunks may be waiting for generation to start, which bounds
+    /// both the chunks created each frame and the backlog of the generation system,
+    /// so that the nearest chunks are always generated first.
+    pub fn with_max_loads_per_frame(mut self, max_loads_per_frame: usize) -> Self {
+        self.max_loads_per_frame = max_loads_per_frame;
+        self
+    }
+
+    pub const fn view_radius(&self) -> i32 {
+        self.view_radius
+    }
+
+    pub const fn unload_radius(&self) -> i32 {
+        self.unload_radius
+    }
+}
+
+impl<'a> specs::System<'a> for ChunkStreamingSystem {
+    type SystemData = (
+        specs::Entities<'a>,
+        specs::Write<'a, ChunkMap>,
+        specs::Read<'a, PendingWrites>,
+        specs::Write<'a, Heightmaps>,
+        specs::WriteStorage<'a, Chunk>,
+        specs::ReadStorage<'a, Transform>,
+        specs::ReadStorage<'a, Camera>,
+    );
+
+    fn run(
+        &mut self,
+        (entities, mut chunk_map, pending_writes, mut heightmaps, mut chunks, transforms, cameras): Self::SystemData,
+    ) {
+        use specs::Join;
+
+        let centers = (&transforms, &cameras)
+            .join()
+            .map(|(transform, _)| ChunkPos::containing(transform.pos))
+            .collect::<Vec<_>>();
+
+        // Without a camera, every chunk would lie beyond the unload radius; keep
+        //  them loaded until one returns.
+        if centers.is_empty() {
+            return;
+        }
+
+        // Unload chunks beyond the unload radius of every camera.
+        let unload_radius_squared = self.unload_radius * self.unload_radius;
+        let is_unloaded = |position: ChunkPos| {
+            centers
+                .iter()
+                .all(|center| (position - *center).length_squared() > unload_radius_squared)
+        };
+        let unloaded = chunk_map
+            .iter()
+            .filter(|(position, _)| is_unloaded(*position))
+            .collect::<Vec<_>>();
+
+        let mut saved = false;
+        for (position, entity) in unloaded {
+            chunk_map.remove(position);
+            heightmaps.remove_chunk(position);
+
+            let modified = chunks
+                .get(entity)
+                .filter(|chunk| chunk.is_modified())
+                .and_then(Chunk::snapshot);
+
+            if let (Some(region_store), Some(blocks)) = (&self.region_store, modified) {
+                region_store.save(position, blocks);
+                saved = true;
+            }
+
+            if let Err(error) = entities.delete(entity) {
+                warn!("Failed to unload chunk at {}: {}", position, error);
+            }
+        }
+
+        for (position, writes) in pending_writes.take_where(is_unloaded) {
+            if let Some(region_store) = &self.region_store {
+                match region_store.save_pending(position, writes) {
+                    Ok(()) => saved = true,
+                    Err(error) => {
+                        warn!("Failed to save pending writes for {}: {}", position, error)
+                    }
+                }
+            }
+        }
+
+        // Write saved chunks to disk in the background.
+        if saved {
+            let region_store = Arc::clone(self.region_store.as_ref().unwrap());
+            let work = Box::new(move || {
+                if let Err(error) = region_store.flush() {
+                    error!("Failed to save chunks: {}", error);
+                }
+            });
+
+            if crate::concurrency::queue(work).is_err() {
+                warn!("Failed to queue save job; the worker pool is unavailable.");
+            }
+        }
+
+        // Load missing chunks within view, nearest first, until the backlog is full.
+        let waiting = chunks
+            .join()
+            .filter(|chunk| chunk.state() == ChunkState::Unloaded)
+            .count();
+        let mut budget = self.max_loads_per_frame.saturating_sub(waiting);
+        if budget == 0 {
+            return;
+        }
+
+        for offset in &self.offsets {
+            for center in &centers {
+                let position = *center + *offset;
+
+                if chunk_map.contains(position) {
+                    continue;
+                }
+
+                let entity = entities.create();
+                chunks
+                    .insert(entity, Chunk::new(position))
+                    .expect("Failed to insert chunk component.");
+                chunk_map.insert(position, entity);
+
+                budget -= 1;
+                if budget == 0 {
+                    return;
+                }
+            }
+        }
+    }
+}
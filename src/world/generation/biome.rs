@@ -0,0 +1,208 @@
+use super::{derive_seed, FractalNoise};
+use crate::world::block::{Block, BlockRegistry};
+
+const TEMPERATURE_STREAM: u64 = 0x5445_4D50;
+const HUMIDITY_STREAM: u64 = 0x4855_4D49_4449_5459;
+
+/// A landscape type, selected for each column by its climate.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Biome {
+    pub name: String,
+    /// Temperature the biome is centered on, in `[-1, 1]`.
+    pub temperature: f64,
+    /// Humidity the biome is centered on, in `[-1, 1]`.
+    pub humidity: f64,
+    /// Topmost solid block of each column.
+    pub surface: Block,
+    /// Blocks between the surface and stone.
+    pub subsurface: Block,
+    /// Offset added to the terrain's base height.
+    pub height_offset: f64,
+    /// Multiplier of the terrain's height noise.
+    pub height_scale: f64,
+    /// Relative likelihood of features (trees, boulders, ...) being placed.
+    pub feature_density: f64,
+}
+
+/// Biome parameters at a single column, blended between neighboring biomes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BiomeSample {
+    /// Index of the biome with the greatest influence over the column.
+    pub dominant: usize,
+    pub height_offset: f64,
+    pub height_scale: f64,
+    pub feature_density: f64,
+}
+
+/// Classifies columns into biomes from seeded temperature and humidity fields.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BiomeMap {
+    biomes: Vec<Biome>,
+    pub temperature: FractalNoise,
+    pub humidity: FractalNoise,
+    /// Distance in climate space over which neighboring biomes are blended.
+    pub blend_width: f64,
+}
+
+impl BiomeMap {
+    pub fn new() -> Self {
+        // Fractal noise rarely strays far from zero, so it's amplified beyond the
+        //  range of climates to make extreme climates reasonably common.
+        let climate = FractalNoise {
+            octaves: 3,
+            frequency: 1.0 / 1024.0,
+            amplitude: 3.0,
+            lacunarity: 2.0,
+            persistence: 0.5,
+        };
+
+        Self {
+            biomes: Vec::new(),
+            temperature: climate,
+            humidity: climate,
+            blend_width: 0.2,
+        }
+    }
+
+    /// A varied set of biomes built from the `core` blocks in `registry`.
+    pub fn from_registry(registry: &BlockRegistry) -> Self {
+        let biome = |name: &str, temperature, humidity, surface: &str, subsurface: &str| Biome {
+            name: name.to_string(),
+            temperature,
+            humidity,
+            surface: registry.core_block(surface),
+            subsurface: registry.core_block(subsurface),
+            height_offset: 0.0,
+            height_scale: 1.0,
+            feature_density: 0.0,
+        };
+
+        Self::new()
+            .with_biome(Biome {
+                feature_density: 0.1,
+                ..biome("plains", 0.0, 0.0, "grass", "dirt")
+            })
+            .with_biome(Biome {
+                height_offset: 4.0,
+                feature_density: 1.0,
+                ..biome("forest", 0.0, 0.5, "grass", "dirt")
+            })
+            .with_biome(Biome {
+                height_scale: 0.4,
+                feature_density: 0.02,
+                ..biome("desert", 0.6, -0.5, "sand", "sand")
+            })
+            .with_biome(Biome {
+                height_offset: 24.0,
+                height_scale: 2.5,
+                feature_density: 0.05,
+                ..biome("mountains", -0.3, -0.2, "stone", "stone")
+            })
+            .with_biome(Biome {
+                height_scale: 0.6,
+                feature_density: 0.05,
+                ..biome("tundra", -0.6, 0.3, "snow", "dirt")
+            })
+    }
+
+    pub fn with_biome(mut self, biome: Biome) -> Self {
+        self.add_biome(biome);
+        self
+    }
+
+    /// Adds `biome`, returning its index.
+    pub fn add_biome(&mut self, biome: Biome) -> usize {
+        self.biomes.push(biome);
+        self.biomes.len() - 1
+    }
+
+    pub fn biome(&self, index: usize) -> &Biome {
+        &self.biomes[index]
+    }
+
+    pub fn biomes(&self) -> &[Biome] {
+        &self.biomes
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.biomes.is_empty()
+    }
+
+    /// Temperature and humidity at block-space column `(x, z)`, each in `[-1, 1]`.
+    pub fn climate(&self, seed: u64, x: i32, z: i32) -> (f64, f64) {
+        (
+            self.temperature
+                .sample2(derive_seed(seed, TEMPERATURE_STREAM), x as f64, z as f64)
+                .clamp(-1.0, 1.0),
+            self.humidity
+                .sample2(derive_seed(seed, HUMIDITY_STREAM), x as f64, z as f64)
+                .clamp(-1.0, 1.0),
+        )
+    }
+
+    /// Samples the biomes at block-space column `(x, z)`.
+    ///
+    /// Every biome whose climate lies within `blend_width` of the closest biome's
+    /// contributes to the result, weighted by how much closer it is. Since the
+    /// climate fields are continuous, so is the blended result across borders.
+    pub fn sample(&self, seed: u64, x: i32, z: i32) -> BiomeSample {
+        assert!(!self.biomes.is_empty(), "Biome map contains no biomes!");
+
+        let (temperature, humidity) = self.climate(seed, x, z);
+        let distances = self
+            .biomes
+            .iter()
+            .map(|biome| {
+                let (dt, dh) = (biome.temperature - temperature, biome.humidity - humidity);
+                ((dt * dt) + (dh * dh)).sqrt()
+            })
+            .collect::<Vec<_>>();
+
+        let (dominant, closest) = distances.iter().copied().enumerate().fold(
+            (0, f64::INFINITY),
+            |closest, (index, distance)| {
+                if distance < closest.1 {
+                    (index, distance)
+                } else {
+                    closest
+                }
+            },
+        );
+
+        let mut sample = BiomeSample {
+            dominant,
+            height_offset: 0.0,
+            height_scale: 0.0,
+            feature_density: 0.0,
+        };
+        let mut total_weight = 0.0;
+
+        for (biome, distance) in self.biomes.iter().zip(distances) {
+            let weight = if self.blend_width > 0.0 {
+                let falloff = (1.0 - ((distance - closest) / self.blend_width)).max(0.0);
+                falloff * falloff
+            } else if distance == closest {
+                1.0
+            } else {
+                0.0
+            };
+
+            sample.height_offset += biome.height_offset * weight;
+            sample.height_scale += biome.height_scale * weight;
+            sample.feature_density += biome.feature_density * weight;
+            total_weight += weight;
+        }
+
+        sample.height_offset /= total_weight;
+        sample.height_scale /= total_weight;
+        sample.feature_density /= total_weight;
+
+        sample
+    }
+}
+
+impl Default for BiomeMap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
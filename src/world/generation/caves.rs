@@ -0,0 +1,267 @@
+use super::{derive_seed, FractalNoise, Random};
+use crate::world::{
+    block::Block,
+    chunk::{
+        BlockStorageMut, ChunkGenerationContext, ChunkGenerationStep, CHUNK_SIZE, CHUNK_SIZE_CUBED,
+        CHUNK_SIZE_SQUARED,
+    },
+};
+use glam::{DVec3, IVec3};
+
+const CAVERN_STREAM: u64 = 0x4341_5645_524E;
+const WORM_STREAM: u64 = 0x574F_524D;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CaveSettings {
+    /// Noise carving open caverns wherever it exceeds `cavern_threshold`.
+    pub cavern_noise: FractalNoise,
+    /// Caverns are disabled when this is at least the noise's amplitude.
+    pub cavern_threshold: f64,
+    /// Vertical stretch of the cavern noise; values above one flatten caverns.
+    pub cavern_squash: f64,
+    /// Chance of each chunk being the starting point of a tunnel.
+    pub worm_chance: f64,
+    /// Length of each tunnel, in blocks.
+    pub worm_length: (i32, i32),
+    /// Radius of each tunnel, in blocks, which varies along its length.
+    pub worm_radius: (f64, f64),
+    /// How sharply tunnels turn each block, from `0` (straight) to `1`.
+    pub worm_turn: f64,
+    /// Block-space height above which nothing is carved.
+    pub max_height: i32,
+}
+
+impl Default for CaveSettings {
+    fn default() -> Self {
+        Self {
+            cavern_noise: FractalNoise {
+                octaves: 2,
+                frequency: 1.0 / 48.0,
+                amplitude: 1.0,
+                lacunarity: 2.0,
+                persistence: 0.5,
+            },
+            cavern_threshold: 0.3,
+            cavern_squash: 2.0,
+            worm_chance: 0.2,
+            worm_length: (48, 112),
+            worm_radius: (1.5, 3.5),
+            worm_turn: 0.2,
+            max_height: -8,
+        }
+    }
+}
+
+/// Carves caverns and tunnels out of previously generated terrain.
+///
+/// Tunnels ("worms") start at random points, seeded by the chunk containing
+/// them, and wander through neighboring chunks. Every chunk within reach of a
+/// tunnel's start replays the same walk, carving only the blocks it contains,
+/// so that tunnels line up no matter the order in which chunks are generated.
+pub struct CaveStep {
+    settings: CaveSettings,
+}
+
+impl CaveStep {
+    pub fn new(settings: CaveSettings) -> Self {
+        Self { settings }
+    }
+
+    pub const fn settings(&self) -> &CaveSettings {
+        &self.settings
+    }
+
+    fn carve_caverns(&self, context: &ChunkGenerationContext, blocks: &mut [Block]) {
+        let noise = &self.settings.cavern_noise;
+        if self.settings.cavern_threshold >= noise.amplitude {
+            return;
+        }
+
+        let seed = derive_seed(context.seed, CAVERN_STREAM);
+        let origin = context.origin();
+
+        for y in 0..CHUNK_SIZE.min(self.settings.max_height - origin.y + 1) {
+            for z in 0..CHUNK_SIZE {
+                for x in 0..CHUNK_SIZE {
+                    let index = (x + (z * CHUNK_SIZE) + (y * CHUNK_SIZE_SQUARED)) as usize;
+                    if blocks[index] == Block::AIR {
+                        continue;
+                    }
+
+                    let world = origin + IVec3::new(x, y, z);
+                    let density = noise.sample3(
+                        seed,
+                        world.x as f64,
+                        (world.y as f64) * self.settings.cavern_squash,
+                        world.z as f64,
+                    );
+
+                    if density > self.settings.cavern_threshold {
+                        blocks[index] = Block::AIR;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Walks the tunnel starting in chunk `start_chunk`, if any, carving the parts
+    /// of it within the chunk at `origin`.
+    fn carve_worm(&self, seed: u64, start_chunk: IVec3, origin: IVec3, blocks: &mut [Block]) {
+        let settings = &self.settings;
+        let mut random = Random::at(seed, WORM_STREAM, start_chunk);
+
+        if !random.chance(settings.worm_chance) {
+            return;
+        }
+
+        let start = (start_chunk * CHUNK_SIZE)
+            + IVec3::new(
+                random.range_i32(0, CHUNK_SIZE),
+                random.range_i32(0, CHUNK_SIZE),
+                random.range_i32(0, CHUNK_SIZE),
+            );
+        if start.y > settings.max_height {
+            return;
+        }
+
+        let length = random.range_i32(settings.worm_length.0, settings.worm_length.1);
+        let mut position = start.as_dvec3();
+        let mut direction = flatten(random.unit_vector());
+        let mut radius = random.range_f64(settings.worm_radius.0, settings.worm_radius.1);
+
+        let chunk_min = origin.as_dvec3();
+        let chunk_max = (origin + IVec3::splat(CHUNK_SIZE)).as_dvec3();
+
+        for step in 0..length {
+            // Taper the ends of the tunnel.
+            let taper = (step.min(length - step) as f64 / 4.0).min(1.0);
+            let step_radius = (radius * taper).max(settings.worm_radius.0.min(1.0));
+
+            if (position + step_radius).cmpge(chunk_min).all()
+                && (position - step_radius).cmplt(chunk_max).all()
+            {
+                carve_sphere(position, step_radius, origin, settings.max_height, blocks);
+            }
+
+            direction = flatten(direction + (random.unit_vector() * settings.worm_turn));
+            radius = (radius + random.range_f64(-0.25, 0.25))
+                .clamp(settings.worm_radius.0, settings.worm_radius.1);
+            position += direction;
+        }
+    }
+
+    /// Distance, in chunks, that a tunnel may reach from the chunk it starts in.
+    fn worm_reach(&self) -> i32 {
+        let reach = self.settings.worm_length.1.max(0) as f64 + self.settings.worm_radius.1;
+        (reach / CHUNK_SIZE as f64).ceil() as i32
+    }
+}
+
+/// Normalizes `direction`, biased towards the horizontal.
+fn flatten(direction: DVec3) -> DVec3 {
+    let flattened = DVec3::new(direction.x, direction.y * 0.5, direction.z);
+
+    if flattened.length_squared() > 1.0e-6 {
+        flattened.normalize()
+    } else {
+        DVec3::X
+    }
+}
+
+fn carve_sphere(center: DVec3, radius: f64, origin: IVec3, max_height: i32, blocks: &mut [Block]) {
+    let min = ((center - radius).floor().as_ivec3() - origin).max(IVec3::ZERO);
+    let max = ((center + radius).ceil().as_ivec3() - origin).min(IVec3::splat(CHUNK_SIZE - 1));
+    let max_y = max.y.min(max_height - origin.y);
+    let radius_squared = radius * radius;
+
+    for y in min.y..=max_y {
+        for z in min.z..=max.z {
+            for x in min.x..=max.x {
+                let block_center = (origin + IVec3::new(x, y, z)).as_dvec3() + 0.5;
+
+                if block_center.distance_squared(center) <= radius_squared {
+                    blocks[(x + (z * CHUNK_SIZE) + (y * CHUNK_SIZE_SQUARED)) as usize] = Block::AIR;
+                }
+            }
+        }
+    }
+}
+
+impl ChunkGenerationStep for CaveStep {
+    fn gen_pass(&self, context: &ChunkGenerationContext, blocks: &mut dyn BlockStorageMut) {
+        let origin = context.origin().as_ivec3();
+
+        // Nothing can be carved from chunks entirely above the caves.
+        if origin.y > self.settings.max_height {
+            return;
+        }
+
+        let mut carved = vec![Block::AIR; CHUNK_SIZE_CUBED as usize];
+        blocks.copy_to_slice(&mut carved);
+
+        self.carve_caverns(context, &mut carved);
+
+        let reach = self.worm_reach();
+        for z in -reach..=reach {
+            for y in -reach..=reach {
+                for x in -reach..=reach {
+                    let start_chunk = context.position.as_ivec3() + IVec3::new(x, y, z);
+                    self.carve_worm(context.seed, start_chunk, origin, &mut carved);
+                }
+            }
+        }
+
+        blocks.copy_from_slice(&carved);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::chunk::{BlockStorage, ChunkPos, ChunkStorage};
+
+    const STONE: Block = Block::new(2, 0, 0);
+
+    fn carve(step: &CaveStep, seed: u64, position: ChunkPos) -> Vec<Block> {
+        let mut storage = ChunkStorage::new(STONE);
+        step.gen_pass(&ChunkGenerationContext { position, seed }, &mut storage);
+
+        let mut blocks = vec![Block::AIR; storage.len()];
+        storage.copy_to_slice(&mut blocks);
+        blocks
+    }
+
+    #[test]
+    fn caves_carve_identically_for_the_same_seed_and_position() {
+        let (first, second) = (
+            CaveStep::new(CaveSettings::default()),
+            CaveStep::new(CaveSettings::default()),
+        );
+        let positions = (-2..2)
+            .flat_map(|x| (-2..2).map(move |z| ChunkPos::new(x, -3, z)))
+            .collect::<Vec<_>>();
+
+        let carved = positions
+            .iter()
+            .map(|position| carve(&first, 1, *position))
+            .collect::<Vec<_>>();
+        assert!(carved.iter().flatten().any(|block| *block == Block::AIR));
+
+        // Carve in reverse, as chunks may generate in any order.
+        for (position, blocks) in positions.iter().zip(carved.iter()).rev() {
+            assert!(*blocks == carve(&second, 1, *position));
+        }
+        assert!(positions
+            .iter()
+            .zip(carved.iter())
+            .any(|(position, blocks)| *blocks != carve(&first, 2, *position)));
+    }
+
+    #[test]
+    fn nothing_is_carved_above_the_maximum_height() {
+        let step = CaveStep::new(CaveSettings::default());
+        let blocks = carve(&step, 1, ChunkPos::new(0, 1, 0));
+
+        assert!(blocks.iter().all(|block| *block == STONE));
+    }
+}
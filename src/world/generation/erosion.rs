@@ -0,0 +1,312 @@
+use super::Random;
+use glam::{DVec2, IVec2, IVec3};
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+};
+
+const EROSION_STREAM: u64 = 0x0045_524F_5349_4F4E;
+
+/// Count of eroded regions kept in memory, per `HydraulicErosion`.
+pub const MAX_CACHED_REGIONS: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ErosionSettings {
+    /// Width of each simulated region, in columns. Must be even.
+    pub region_size: i32,
+    /// Count of droplets simulated over each region.
+    pub droplets: u32,
+    /// Count of steps each droplet takes before evaporating entirely.
+    pub lifetime: u32,
+    /// How much of its direction a droplet keeps each step, from `0` (always
+    /// flowing downhill) to `1` (never turning).
+    pub inertia: f64,
+    /// Sediment carried per unit of slope, speed and water.
+    pub capacity: f64,
+    /// Lowest slope used for the sediment capacity, so that droplets on flat
+    /// ground still carry some sediment.
+    pub min_slope: f64,
+    /// Fraction of spare capacity taken from the ground each step.
+    pub erosion: f64,
+    /// Fraction of excess sediment deposited each step.
+    pub deposition: f64,
+    /// Fraction of water lost each step.
+    pub evaporation: f64,
+    pub gravity: f64,
+    /// Radius over which each droplet erodes, in columns.
+    pub radius: i32,
+    /// Deepest a single step may erode, in blocks, which keeps droplets from
+    /// digging narrow pits.
+    pub max_erosion: f64,
+}
+
+impl Default for ErosionSettings {
+    fn default() -> Self {
+        Self {
+            region_size: 128,
+            droplets: 8192,
+            lifetime: 48,
+            inertia: 0.1,
+            capacity: 4.0,
+            min_slope: 0.01,
+            erosion: 0.3,
+            deposition: 0.3,
+            evaporation: 0.02,
+            gravity: 4.0,
+            radius: 3,
+            max_erosion: 0.5,
+        }
+    }
+}
+
+type RegionSlot = Arc<Mutex<Option<Arc<Vec<f32>>>>>;
+
+#[derive(Default)]
+struct RegionCache {
+    regions: HashMap<(u64, IVec2), RegionSlot>,
+    // Keys in the order they were inserted, oldest first.
+    order: VecDeque<(u64, IVec2)>,
+}
+
+/// Simulates droplet-based hydraulic erosion over a heightmap, one region at a
+/// time.
+///
+/// Regions overlap their neighbors by half, and the change in height of each
+/// column is blended between the regions containing it, with weights falling to
+/// zero at each region's edges. Droplets are seeded by the region they fall in,
+/// so results are identical no matter the order in which chunks are generated,
+/// and seamless across both chunk and region borders.
+///
+/// Regions are cached, keyed only by seed and position, so each instance must only
+/// ever be used with a single heightmap.
+pub struct HydraulicErosion {
+    settings: ErosionSettings,
+    brush: Vec<(IVec2, f64)>,
+    cache: Mutex<RegionCache>,
+}
+
+impl HydraulicErosion {
+    pub fn new(settings: ErosionSettings) -> Self {
+        assert!(
+            settings.region_size >= 2 && (settings.region_size % 2) == 0,
+            "Erosion regions must be of a positive, even size."
+        );
+
+        // Erosion is spread over nearby columns, weighted by their distance.
+        let radius = settings.radius.max(0);
+        let mut brush = Vec::new();
+        for z in -radius..=radius {
+            for x in -radius..=radius {
+                let distance = (((x * x) + (z * z)) as f64).sqrt();
+
+                if distance <= (radius as f64) {
+                    brush.push((IVec2::new(x, z), (radius as f64) + 1.0 - distance));
+                }
+            }
+        }
+
+        let total = brush.iter().map(|(_, weight)| weight).sum::<f64>();
+        for (_, weight) in &mut brush {
+            *weight /= total;
+        }
+
+        Self {
+            settings,
+            brush,
+            cache: Mutex::new(RegionCache::default()),
+        }
+    }
+
+    pub const fn settings(&self) -> &ErosionSettings {
+        &self.settings
+    }
+
+    /// Change in height of block-space column `(x, z)` due to erosion, where
+    /// `height` gives the uneroded height of any column.
+    pub fn offset(&self, seed: u64, x: i32, z: i32, height: &dyn Fn(i32, i32) -> f64) -> f64 {
+        let half = self.settings.region_size / 2;
+        let column = IVec2::new(x, z);
+        let cell = IVec2::new(x.div_euclid(half), z.div_euclid(half));
+
+        let mut offset = 0.0;
+        for region_z in (cell.y - 1)..=cell.y {
+            for region_x in (cell.x - 1)..=cell.x {
+                let region = IVec2::new(region_x, region_z);
+                let local = column - (region * half);
+                let weight = self.weight(local.x) * self.weight(local.y);
+
+                if weight > 0.0 {
+                    let deltas = self.region(seed, region, height);
+                    let index = local.x + (local.y * self.settings.region_size);
+
+                    offset += weight * (deltas[index as usize] as f64);
+                }
+            }
+        }
+
+        offset
+    }
+
+    /// Blend weight of a column `local` columns into a region, along one axis.
+    fn weight(&self, local: i32) -> f64 {
+        let size = self.settings.region_size as f64;
+        1.0 - (((2.0 * (local as f64)) / size) - 1.0).abs()
+    }
+
+    /// Returns the eroded change in height of every column of the region at
+    /// `region`, simulating it if it isn't cached.
+    fn region(&self, seed: u64, region: IVec2, height: &dyn Fn(i32, i32) -> f64) -> Arc<Vec<f32>> {
+        let key = (seed, region);
+        let slot = {
+            let mut cache = self.cache.lock().unwrap();
+
+            if !cache.regions.contains_key(&key) {
+                if cache.order.len() >= MAX_CACHED_REGIONS {
+                    let oldest = cache.order.pop_front().unwrap();
+                    cache.regions.remove(&oldest);
+                }
+
+                cache.order.push_back(key);
+            }
+
+            Arc::clone(cache.regions.entry(key).or_default())
+        };
+
+        // Workers needing the same region wait for the first to simulate it, rather
+        //  than each simulating it themselves.
+        let mut slot = slot.lock().unwrap();
+        Arc::clone(slot.get_or_insert_with(|| Arc::new(self.simulate(seed, region, height))))
+    }
+
+    fn simulate(&self, seed: u64, region: IVec2, height: &dyn Fn(i32, i32) -> f64) -> Vec<f32> {
+        let settings = &self.settings;
+        let size = settings.region_size;
+        let origin = region * (size / 2);
+
+        let mut heights = Vec::with_capacity((size * size) as usize);
+        for z in 0..size {
+            for x in 0..size {
+                heights.push(height(origin.x + x, origin.y + z));
+            }
+        }
+        let initial = heights.clone();
+
+        let mut random = Random::at(seed, EROSION_STREAM, IVec3::new(region.x, 0, region.y));
+        for _ in 0..settings.droplets {
+            let start = DVec2::new(
+                random.range_f64(0.0, (size - 1) as f64),
+                random.range_f64(0.0, (size - 1) as f64),
+            );
+
+            self.simulate_droplet(&mut heights, start);
+        }
+
+        heights
+            .iter()
+            .zip(&initial)
+            .map(|(eroded, initial)| (eroded - initial) as f32)
+            .collect()
+    }
+
+    fn simulate_droplet(&self, heights: &mut [f64], start: DVec2) {
+        let settings = &self.settings;
+        let size = settings.region_size;
+
+        let mut position = start;
+        let mut direction = DVec2::ZERO;
+        let mut speed = 1.0;
+        let mut water = 1.0;
+        let mut sediment = 0.0;
+
+        for _ in 0..settings.lifetime {
+            let node = position.floor().as_ivec2();
+            let (height, gradient) = sample(heights, size, position);
+
+            direction = (direction * settings.inertia) - (gradient * (1.0 - settings.inertia));
+            if direction.length_squared() < 1.0e-12 {
+                break;
+            }
+            direction = direction.normalize();
+            position += direction;
+
+            // Droplets leaving the region are lost, along with their sediment.
+            if position.cmplt(DVec2::ZERO).any()
+                || position.cmpge(DVec2::splat((size - 1) as f64)).any()
+            {
+                break;
+            }
+
+            let height_change = sample(heights, size, position).0 - height;
+            let capacity =
+                (-height_change).max(settings.min_slope) * speed * water * settings.capacity;
+
+            if (sediment > capacity) || (height_change > 0.0) {
+                // Fill the pit the droplet climbed out of, or drop what it can't carry.
+                let deposit = if height_change > 0.0 {
+                    height_change.min(sediment)
+                } else {
+                    (sediment - capacity) * settings.deposition
+                };
+                sediment -= deposit;
+
+                let offset = position_offset(node, position - direction);
+                for (corner, weight) in bilinear_weights(offset) {
+                    let corner = node + corner;
+                    heights[(corner.x + (corner.y * size)) as usize] += deposit * weight;
+                }
+            } else {
+                let amount = ((capacity - sediment) * settings.erosion)
+                    .min(-height_change)
+                    .min(settings.max_erosion);
+
+                for (brush_offset, weight) in &self.brush {
+                    let cell = node + *brush_offset;
+
+                    if cell.cmpge(IVec2::ZERO).all() && cell.cmplt(IVec2::splat(size)).all() {
+                        heights[(cell.x + (cell.y * size)) as usize] -= amount * weight;
+                        sediment += amount * weight;
+                    }
+                }
+            }
+
+            speed = ((speed * speed) - (height_change * settings.gravity))
+                .max(0.0)
+                .sqrt();
+            water *= 1.0 - settings.evaporation;
+        }
+    }
+}
+
+/// Offset of `position` within the cell at `node`.
+fn position_offset(node: IVec2, position: DVec2) -> DVec2 {
+    position - node.as_dvec2()
+}
+
+/// Weights of the corners of a cell, bilinearly interpolating to `offset`.
+fn bilinear_weights(offset: DVec2) -> [(IVec2, f64); 4] {
+    [
+        (IVec2::new(0, 0), (1.0 - offset.x) * (1.0 - offset.y)),
+        (IVec2::new(1, 0), offset.x * (1.0 - offset.y)),
+        (IVec2::new(0, 1), (1.0 - offset.x) * offset.y),
+        (IVec2::new(1, 1), offset.x * offset.y),
+    ]
+}
+
+/// Height and gradient of the heightmap at `position`, bilinearly interpolated.
+fn sample(heights: &[f64], size: i32, position: DVec2) -> (f64, DVec2) {
+    let node = position.floor().as_ivec2();
+    let offset = position_offset(node, position);
+    let height = |x: i32, z: i32| heights[((node.x + x) + ((node.y + z) * size)) as usize];
+
+    let (h00, h10, h01, h11) = (height(0, 0), height(1, 0), height(0, 1), height(1, 1));
+    let gradient = DVec2::new(
+        ((h10 - h00) * (1.0 - offset.y)) + ((h11 - h01) * offset.y),
+        ((h01 - h00) * (1.0 - offset.x)) + ((h11 - h10) * offset.x),
+    );
+    let height = bilinear_weights(offset)
+        .iter()
+        .map(|(corner, weight)| weight * height(corner.x, corner.y))
+        .sum();
+
+    (height, gradient)
+}
@@ -0,0 +1,327 @@
+use super::{BiomeMap, LSystemTreeFeature, Random, TreeSpecies};
+use crate::world::{
+    block::{Block, BlockRegistry},
+    chunk::{
+        BlockPos, BlockStorageMut, ChunkGenerationContext, ChunkGenerationStep, ChunkPos,
+        PendingWrite, PendingWrites, Replace, CHUNK_SIZE, CHUNK_SIZE_CUBED, CHUNK_SIZE_SQUARED,
+    },
+};
+use glam::IVec3;
+use std::{collections::HashMap, sync::Arc};
+
+const FEATURE_STREAM: u64 = 0x4645_4154_5552_4500;
+
+/// Block access for features, in block-space coordinates.
+///
+/// Only blocks of the chunk being generated may be read. Writes into other chunks
+/// are collected, and queued into `PendingWrites` once the chunk is complete.
+pub struct FeatureWriter<'a> {
+    position: ChunkPos,
+    blocks: &'a mut [Block],
+    spilled: HashMap<ChunkPos, Vec<PendingWrite>>,
+}
+
+impl<'a> FeatureWriter<'a> {
+    fn new(position: ChunkPos, blocks: &'a mut [Block]) -> Self {
+        Self {
+            position,
+            blocks,
+            spilled: HashMap::new(),
+        }
+    }
+
+    /// Returns the block at `position`, if it lies within the chunk being generated.
+    pub fn get_block(&self, position: IVec3) -> Option<Block> {
+        let (chunk, local) = BlockPos::from(position).split();
+
+        if chunk == self.position {
+            Some(self.blocks[local.index().get()])
+        } else {
+            None
+        }
+    }
+
+    /// Writes `block` at `position` if `replace` allows it, in whichever chunk
+    /// contains it.
+    pub fn set_block(&mut self, position: IVec3, block: Block, replace: Replace) {
+        let (chunk, local) = BlockPos::from(position).split();
+        let index = local.index();
+
+        if chunk == self.position {
+            let existing = &mut self.blocks[index.get()];
+            if replace.allows(*existing) {
+                *existing = block;
+            }
+        } else {
+            self.spilled.entry(chunk).or_default().push(PendingWrite {
+                index,
+                block,
+                replace,
+            });
+        }
+    }
+}
+
+/// Something placed into the world at a single point, which may extend into
+/// neighboring chunks.
+pub trait Feature: Send + Sync {
+    /// Places the feature at block-space `origin`. Returns false if `origin` was
+    /// unsuitable and nothing was placed.
+    fn place(&self, origin: IVec3, random: &mut Random, writer: &mut FeatureWriter) -> bool;
+}
+
+/// Where features are anchored within each chunk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Placement {
+    /// Directly above the topmost solid block of a column, scaled by the biome's
+    /// feature density.
+    Surface,
+    /// At any solid block between the given block-space heights.
+    Underground { min_height: i32, max_height: i32 },
+}
+
+struct PlacedFeature {
+    feature: Box<dyn Feature>,
+    placement: Placement,
+    attempts: u32,
+}
+
+/// Places registered features into each chunk, spilling over into its neighbors.
+///
+/// Each chunk only places features anchored within itself, using randomness seeded
+/// by its position, so features are placed identically regardless of the order in
+/// which chunks are generated.
+pub struct FeatureStep {
+    pending_writes: PendingWrites,
+    biomes: Option<Arc<BiomeMap>>,
+    features: Vec<PlacedFeature>,
+}
+
+impl FeatureStep {
+    pub fn new(pending_writes: PendingWrites) -> Self {
+        Self {
+            pending_writes,
+            biomes: None,
+            features: Vec::new(),
+        }
+    }
+
+    /// Trees, boulders and ores built from the `core` blocks in `registry`.
+    pub fn from_registry(pending_writes: PendingWrites, registry: &BlockRegistry) -> Self {
+        Self::new(pending_writes)
+            .with_feature(
+                LSystemTreeFeature {
+                    species: TreeSpecies::defaults(registry),
+                },
+                Placement::Surface,
+                8,
+            )
+            .with_feature(
+                BoulderFeature {
+                    block: registry.core_block("cobblestone"),
+                    radius: (1.0, 2.5),
+                },
+                Placement::Surface,
+                1,
+            )
+            .with_feature(
+                OreFeature {
+                    ore: registry.core_block("coal_ore"),
+                    host: registry.core_block("stone"),
+                    size: (6, 16),
+                },
+                Placement::Underground {
+                    min_height: -256,
+                    max_height: 64,
+                },
+                8,
+            )
+            .with_feature(
+                OreFeature {
+                    ore: registry.core_block("iron_ore"),
+                    host: registry.core_block("stone"),
+                    size: (4, 10),
+                },
+                Placement::Underground {
+                    min_height: -256,
+                    max_height: -16,
+                },
+                4,
+            )
+    }
+
+    /// Scales surface features by the biome feature density of their column.
+    pub fn with_biomes(mut self, biomes: Arc<BiomeMap>) -> Self {
+        self.biomes = Some(biomes);
+        self
+    }
+
+    /// Registers `feature`, attempting to place it `attempts` times per chunk.
+    pub fn with_feature(
+        mut self,
+        feature: impl Feature + 'static,
+        placement: Placement,
+        attempts: u32,
+    ) -> Self {
+        self.features.push(PlacedFeature {
+            feature: Box::new(feature),
+            placement,
+            attempts,
+        });
+        self
+    }
+
+    /// Finds the anchor of a feature in the column at local `(x, z)`, in block space.
+    fn anchor(
+        &self,
+        context: &ChunkGenerationContext,
+        placement: Placement,
+        random: &mut Random,
+        x: i32,
+        z: i32,
+        blocks: &[Block],
+    ) -> Option<IVec3> {
+        let origin = context.origin().as_ivec3();
+        let block = |y: i32| blocks[(x + (z * CHUNK_SIZE) + (y * CHUNK_SIZE_SQUARED)) as usize];
+
+        match placement {
+            Placement::Surface => {
+                let density = self.biomes.as_ref().map_or(1.0, |biomes| {
+                    biomes
+                        .sample(context.seed, origin.x + x, origin.z + z)
+                        .feature_density
+                });
+
+                if !random.chance(density) {
+                    return None;
+                }
+
+                // Surfaces in the topmost layer can't be told apart from solid ground
+                //  without the chunk above, so are left to be missed.
+                (0..(CHUNK_SIZE - 1))
+                    .rev()
+                    .find(|y| block(*y) != Block::AIR && block(*y + 1) == Block::AIR)
+                    .map(|y| origin + IVec3::new(x, y + 1, z))
+            }
+            Placement::Underground {
+                min_height,
+                max_height,
+            } => {
+                let min_y = (min_height - origin.y).max(0);
+                let max_y = (max_height - origin.y).min(CHUNK_SIZE - 1);
+                let y = random.range_i32(min_y, max_y + 1);
+
+                if (min_y <= max_y) && (block(y) != Block::AIR) {
+                    Some(origin + IVec3::new(x, y, z))
+                } else {
+                    None
+                }
+            }
+        }
+    }
+}
+
+impl ChunkGenerationStep for FeatureStep {
+    fn gen_pass(&self, context: &ChunkGenerationContext, blocks: &mut dyn BlockStorageMut) {
+        let mut placed = vec![Block::AIR; CHUNK_SIZE_CUBED as usize];
+        blocks.copy_to_slice(&mut placed);
+
+        let mut writer = FeatureWriter::new(context.position, &mut placed);
+
+        for (feature_index, placed_feature) in self.features.iter().enumerate() {
+            let mut random = Random::at(
+                context.seed,
+                FEATURE_STREAM + (feature_index as u64),
+                context.position.as_ivec3(),
+            );
+
+            for _ in 0..placed_feature.attempts {
+                let x = random.range_i32(0, CHUNK_SIZE);
+                let z = random.range_i32(0, CHUNK_SIZE);
+
+                if let Some(origin) = self.anchor(
+                    context,
+                    placed_feature.placement,
+                    &mut random,
+                    x,
+                    z,
+                    writer.blocks,
+                ) {
+                    placed_feature
+                        .feature
+                        .place(origin, &mut random, &mut writer);
+                }
+            }
+        }
+
+        let spilled = std::mem::take(&mut writer.spilled);
+        blocks.copy_from_slice(&placed);
+
+        if !spilled.is_empty() {
+            self.pending_writes.extend_all(spilled);
+        }
+    }
+}
+
+/// Roughly spherical lump of a block, resting on the ground.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoulderFeature {
+    pub block: Block,
+    pub radius: (f64, f64),
+}
+
+impl Feature for BoulderFeature {
+    fn place(&self, origin: IVec3, random: &mut Random, writer: &mut FeatureWriter) -> bool {
+        let radius = random.range_f64(self.radius.0, self.radius.1);
+        let extent = radius.ceil() as i32;
+        let radius_squared = radius * radius;
+
+        for y in -extent..=extent {
+            for z in -extent..=extent {
+                for x in -extent..=extent {
+                    let offset = IVec3::new(x, y, z);
+
+                    if (offset.length_squared() as f64) <= radius_squared {
+                        writer.set_block(origin + offset, self.block, Replace::Air);
+                    }
+                }
+            }
+        }
+
+        true
+    }
+}
+
+/// Vein of ore, wandering through its host block.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OreFeature {
+    pub ore: Block,
+    pub host: Block,
+    /// Count of blocks in the vein.
+    pub size: (i32, i32),
+}
+
+impl Feature for OreFeature {
+    fn place(&self, origin: IVec3, random: &mut Random, writer: &mut FeatureWriter) -> bool {
+        const STEPS: [IVec3; 6] = [
+            IVec3::X,
+            IVec3::Y,
+            IVec3::Z,
+            IVec3::NEG_X,
+            IVec3::NEG_Y,
+            IVec3::NEG_Z,
+        ];
+
+        if writer.get_block(origin) != Some(self.host) {
+            return false;
+        }
+
+        let mut position = origin;
+        for _ in 0..random.range_i32(self.size.0, self.size.1 + 1) {
+            writer.set_block(position, self.ore, Replace::Only(self.host));
+            position += STEPS[random.range_i32(0, 6) as usize];
+        }
+
+        true
+    }
+}
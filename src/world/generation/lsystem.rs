@@ -0,0 +1,620 @@
+use super::{Feature, FeatureWriter, Random};
+use crate::world::{
+    block::{Block, BlockRegistry},
+    chunk::Replace,
+};
+use glam::{DVec3, IVec3};
+use std::collections::HashMap;
+
+/// Upper bound on the length of an expanded L-system, so that an overly
+/// productive rule set can't exhaust memory.
+pub const MAX_SYMBOLS: usize = 16 * 1024;
+
+/// Upper bound on the `step_length`, `width` and `leaf_radius` of a species, in
+/// blocks, so that a single symbol can't place an unreasonable number of blocks.
+pub const MAX_SPECIES_EXTENT: f64 = 16.0;
+
+/// Oak, birch and pine, in the format read by `TreeSpecies::parse`.
+pub const DEFAULT_TREE_SPECIES: &str = r"
+[oak]
+axiom FFA
+iterations 3
+rule A 2 [&FL!A]/////[&FL!A]///////[&FL!A]
+rule A 1 [&FL!A]///////[&FL!A]
+step_length 2
+width 2
+wood core:log
+leaves core:leaves
+soil core:grass core:dirt
+
+[birch]
+axiom FFFFB
+iterations 3
+rule B 1 F[&L]////[&L]///F[&FL]B
+angle 20
+leaf_radius 1.5
+wood core:log
+leaves core:leaves
+soil core:grass core:dirt
+
+[pine]
+axiom FFFP
+iterations 4
+rule P 1 F[&fL]///[&fL]///[&fL]///[&fL]FP
+rule P 1 F[&fL]////[&fL]////[&fL]FP
+angle 75
+angle_variance 4
+leaf_radius 1
+wood core:log
+leaves core:leaves
+soil core:grass core:dirt
+";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SpeciesError {
+    /// The line at the given number is neither a `[name]` header nor a valid
+    /// property of the species above it.
+    InvalidLine(usize, String),
+    /// The named species lacks a required property.
+    MissingProperty(String, &'static str),
+    /// No block is registered under the given name.
+    UnknownBlock(String),
+}
+
+impl std::fmt::Display for SpeciesError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidLine(number, line) => write!(f, "invalid line {}: \"{}\"", number, line),
+            Self::MissingProperty(species, property) => {
+                write!(
+                    f,
+                    "species \"{}\" lacks the \"{}\" property",
+                    species, property
+                )
+            }
+            Self::UnknownBlock(name) => write!(f, "block \"{}\" is not registered", name),
+        }
+    }
+}
+
+impl std::error::Error for SpeciesError {}
+
+/// Sine and cosine of `radians`, evaluated with basic arithmetic only so that
+/// results are identical on every platform.
+fn sin_cos(radians: f64) -> (f64, f64) {
+    use std::f64::consts::TAU;
+
+    // Reduce to [-pi, pi], where the series converges quickly.
+    let x = radians - ((radians / TAU).round() * TAU);
+    let x_squared = x * x;
+
+    let (mut sin, mut cos) = (0.0, 0.0);
+    let (mut sin_term, mut cos_term) = (x, 1.0);
+    for n in 1..=12 {
+        sin += sin_term;
+        cos += cos_term;
+
+        let n = n as f64;
+        sin_term *= -x_squared / ((2.0 * n) * ((2.0 * n) + 1.0));
+        cos_term *= -x_squared / (((2.0 * n) - 1.0) * (2.0 * n));
+    }
+
+    (sin, cos)
+}
+
+/// Rotates `vector` by `radians` about the unit `axis`.
+fn rotate(vector: DVec3, axis: DVec3, radians: f64) -> DVec3 {
+    let (sin, cos) = sin_cos(radians);
+
+    (vector * cos) + (axis.cross(vector) * sin) + (axis * (axis.dot(vector) * (1.0 - cos)))
+}
+
+/// A string rewriting system, from which trees are grown.
+///
+/// Each iteration replaces every symbol with a rule by one of its productions,
+/// chosen randomly by weight.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct LSystem {
+    pub axiom: String,
+    pub rules: HashMap<char, Vec<(String, f64)>>,
+    pub iterations: u32,
+}
+
+impl LSystem {
+    pub fn new(axiom: &str, iterations: u32) -> Self {
+        Self {
+            axiom: axiom.to_string(),
+            rules: HashMap::new(),
+            iterations,
+        }
+    }
+
+    /// Adds a production replacing `symbol` by `production`, chosen with relative
+    /// likelihood `weight` among the productions of `symbol`.
+    pub fn with_rule(mut self, symbol: char, production: &str, weight: f64) -> Self {
+        self.rules
+            .entry(symbol)
+            .or_default()
+            .push((production.to_string(), weight));
+        self
+    }
+
+    fn produce(&self, symbol: char, random: &mut Random) -> Option<&str> {
+        let productions = self.rules.get(&symbol)?;
+        let total = productions
+            .iter()
+            .map(|(_, weight)| weight.max(0.0))
+            .sum::<f64>();
+
+        let mut target = random.next_f64() * total;
+        for (production, weight) in productions {
+            target -= weight.max(0.0);
+
+            if target < 0.0 {
+                return Some(production);
+            }
+        }
+
+        productions
+            .last()
+            .map(|(production, _)| production.as_str())
+    }
+
+    /// Expands the axiom, stopping early if it would exceed `MAX_SYMBOLS`.
+    pub fn expand(&self, random: &mut Random) -> Vec<char> {
+        let mut symbols = self.axiom.chars().collect::<Vec<_>>();
+
+        for _ in 0..self.iterations {
+            let mut expanded = Vec::with_capacity(symbols.len() * 2);
+
+            for symbol in &symbols {
+                match self.produce(*symbol, random) {
+                    Some(production) => expanded.extend(production.chars()),
+                    None => expanded.push(*symbol),
+                }
+            }
+
+            if expanded.len() > MAX_SYMBOLS {
+                break;
+            }
+
+            symbols = expanded;
+        }
+
+        symbols
+    }
+}
+
+/// A kind of tree, grown from an L-system interpreted by a 3D turtle.
+///
+/// | Symbol | Meaning |
+/// |--------|---------|
+/// | `F` | Move forward, placing wood |
+/// | `f` | Move forward without placing anything |
+/// | `+` `-` | Turn left / right |
+/// | `&` `^` | Pitch down / up |
+/// | `\` `/` | Roll left / right |
+/// | `\|` | Turn around |
+/// | `[` `]` | Save / restore the turtle's state |
+/// | `!` | Narrow the branch |
+/// | `L` | Place a cluster of leaves |
+///
+/// Any other symbol is ignored by the turtle, and only serves the L-system.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TreeSpecies {
+    pub name: String,
+    pub lsystem: LSystem,
+    /// Angle of each turn, pitch and roll, in degrees.
+    pub angle: f64,
+    /// Random variation of each turn, pitch and roll, in degrees.
+    pub angle_variance: f64,
+    /// Distance moved by each `F` and `f`, in blocks.
+    pub step_length: f64,
+    /// Width of the trunk at its base, in blocks.
+    pub width: f64,
+    /// Multiplier applied to the width by each `!`.
+    pub width_decay: f64,
+    /// Radius of each cluster of leaves, in blocks.
+    pub leaf_radius: f64,
+    pub wood: Block,
+    pub leaves: Block,
+    /// Blocks the tree may grow from.
+    pub soil: Vec<Block>,
+}
+
+#[derive(Clone, Copy)]
+struct Turtle {
+    position: DVec3,
+    heading: DVec3,
+    left: DVec3,
+    up: DVec3,
+    width: f64,
+}
+
+impl TreeSpecies {
+    /// Species with default properties, lacking an axiom and blocks.
+    fn unfinished(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            lsystem: LSystem::default(),
+            angle: 30.0,
+            angle_variance: 8.0,
+            step_length: 1.0,
+            width: 1.0,
+            width_decay: 0.7,
+            leaf_radius: 2.0,
+            wood: Block::AIR,
+            leaves: Block::AIR,
+            soil: Vec::new(),
+        }
+    }
+
+    /// Parses species definitions, each beginning with a `[name]` header followed
+    /// by one `key value` property per line. Blank lines and lines starting with
+    /// `#` are ignored.
+    ///
+    /// | Key | Value |
+    /// |-----|-------|
+    /// | `axiom` | Initial symbols of the L-system (required) |
+    /// | `iterations` | Times the L-system is expanded, 0 by default |
+    /// | `rule` | `symbol weight production`, e.g. `A 2 F[&L]A`, repeated per production |
+    /// | `angle`, `angle_variance` | The field of the same name |
+    /// | `step_length`, `width` | The field of the same name, above 0 and at most `MAX_SPECIES_EXTENT` |
+    /// | `width_decay` | The field of the same name, above 0 and at most 1 |
+    /// | `leaf_radius` | The field of the same name, at most `MAX_SPECIES_EXTENT` |
+    /// | `wood`, `leaves` | A block name, e.g. `core:log` (required) |
+    /// | `soil` | Space separated block names (required) |
+    pub fn parse(definitions: &str, registry: &BlockRegistry) -> Result<Vec<Self>, SpeciesError> {
+        let lookup = |name: &str| {
+            registry
+                .get_block(name)
+                .ok_or_else(|| SpeciesError::UnknownBlock(name.to_string()))
+        };
+
+        let mut species: Vec<Self> = Vec::new();
+        for (index, line) in definitions.lines().enumerate() {
+            let line = line.trim();
+            let invalid = || SpeciesError::InvalidLine(index + 1, line.to_string());
+            let number = |text: &str| {
+                text.parse::<f64>()
+                    .ok()
+                    .filter(|number| number.is_finite())
+                    .ok_or_else(invalid)
+            };
+            let bounded = |text: &str, range: std::ops::RangeInclusive<f64>| {
+                number(text).and_then(|number| {
+                    Some(number)
+                        .filter(|number| range.contains(number))
+                        .ok_or_else(invalid)
+                })
+            };
+
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            if let Some(name) = line
+                .strip_prefix('[')
+                .and_then(|line| line.strip_suffix(']'))
+            {
+                let name = Some(name.trim())
+                    .filter(|name| !name.is_empty())
+                    .ok_or_else(invalid)?;
+
+                species.push(Self::unfinished(name));
+                continue;
+            }
+
+            let current = species.last_mut().ok_or_else(invalid)?;
+            let (key, value) = line
+                .split_once(char::is_whitespace)
+                .map(|(key, value)| (key, value.trim()))
+                .ok_or_else(invalid)?;
+
+            match key {
+                "axiom" => current.lsystem.axiom = value.to_string(),
+                "iterations" => {
+                    current.lsystem.iterations = value.parse().map_err(|_| invalid())?;
+                }
+                "rule" => match value.split_whitespace().collect::<Vec<_>>()[..] {
+                    [symbol, weight, production] if symbol.chars().count() == 1 => {
+                        current
+                            .lsystem
+                            .rules
+                            .entry(symbol.chars().next().unwrap())
+                            .or_default()
+                            .push((production.to_string(), number(weight)?));
+                    }
+                    _ => return Err(invalid()),
+                },
+                "angle" => current.angle = number(value)?,
+                "angle_variance" => current.angle_variance = number(value)?,
+                "step_length" => {
+                    current.step_length = bounded(value, f64::MIN_POSITIVE..=MAX_SPECIES_EXTENT)?;
+                }
+                "width" => current.width = bounded(value, f64::MIN_POSITIVE..=MAX_SPECIES_EXTENT)?,
+                "width_decay" => current.width_decay = bounded(value, f64::MIN_POSITIVE..=1.0)?,
+                "leaf_radius" => current.leaf_radius = bounded(value, 0.0..=MAX_SPECIES_EXTENT)?,
+                "wood" => current.wood = lookup(value)?,
+                "leaves" => current.leaves = lookup(value)?,
+                "soil" => {
+                    current.soil = value
+                        .split_whitespace()
+                        .map(lookup)
+                        .collect::<Result<_, _>>()?;
+                }
+                _ => return Err(invalid()),
+            }
+        }
+
+        for current in &species {
+            let missing = [
+                ("axiom", current.lsystem.axiom.is_empty()),
+                ("wood", current.wood == Block::AIR),
+                ("leaves", current.leaves == Block::AIR),
+                ("soil", current.soil.is_empty()),
+            ];
+
+            if let Some((property, _)) = missing.iter().find(|(_, missing)| *missing) {
+                return Err(SpeciesError::MissingProperty(
+                    current.name.clone(),
+                    property,
+                ));
+            }
+        }
+
+        Ok(species)
+    }
+
+    /// Species of `DEFAULT_TREE_SPECIES`, built from the blocks in `registry`.
+    pub fn defaults(registry: &BlockRegistry) -> Vec<Self> {
+        Self::parse(DEFAULT_TREE_SPECIES, registry)
+            .unwrap_or_else(|error| panic!("Invalid default tree species: {}", error))
+    }
+
+    /// Grows the tree at block-space `origin`, returning the positions of its wood
+    /// and of its leaves.
+    pub fn grow(&self, origin: IVec3, random: &mut Random) -> (Vec<IVec3>, Vec<IVec3>) {
+        let (mut wood, mut leaves) = (Vec::new(), Vec::new());
+        let symbols = self.lsystem.expand(random);
+        let angle = self.angle.to_radians();
+        let variance = self.angle_variance.to_radians();
+
+        let mut turtle = Turtle {
+            position: origin.as_dvec3() + DVec3::new(0.5, 0.0, 0.5),
+            heading: DVec3::Y,
+            left: DVec3::NEG_X,
+            up: DVec3::Z,
+            width: self.width,
+        };
+
+        // Face a random direction, so that trees of a species don't all align.
+        let roll = random.range_f64(0.0, std::f64::consts::TAU);
+        turtle.left = rotate(turtle.left, turtle.heading, roll);
+        turtle.up = rotate(turtle.up, turtle.heading, roll);
+
+        let mut stack = Vec::new();
+        for symbol in symbols {
+            let mut turn = |sign: f64| sign * (angle + random.range_f64(-variance, variance));
+
+            match symbol {
+                'F' | 'f' => {
+                    let steps = self.step_length.max(1.0).round() as i32;
+                    let step = turtle.heading * (self.step_length / steps as f64);
+
+                    for _ in 0..steps {
+                        if symbol == 'F' {
+                            self.grow_wood(turtle.position, turtle.width, &mut wood);
+                        }
+
+                        turtle.position += step;
+                    }
+                }
+                '+' | '-' => {
+                    let radians = turn(if symbol == '+' { 1.0 } else { -1.0 });
+                    turtle.heading = rotate(turtle.heading, turtle.up, radians);
+                    turtle.left = rotate(turtle.left, turtle.up, radians);
+                }
+                '&' | '^' => {
+                    let radians = turn(if symbol == '&' { 1.0 } else { -1.0 });
+                    turtle.heading = rotate(turtle.heading, turtle.left, radians);
+                    turtle.up = rotate(turtle.up, turtle.left, radians);
+                }
+                '\\' | '/' => {
+                    let radians = turn(if symbol == '\\' { 1.0 } else { -1.0 });
+                    turtle.left = rotate(turtle.left, turtle.heading, radians);
+                    turtle.up = rotate(turtle.up, turtle.heading, radians);
+                }
+                '|' => {
+                    turtle.heading = -turtle.heading;
+                    turtle.left = -turtle.left;
+                }
+                '[' => stack.push(turtle),
+                ']' => {
+                    if let Some(saved) = stack.pop() {
+                        turtle = saved;
+                    }
+                }
+                '!' => turtle.width *= self.width_decay,
+                'L' => self.grow_leaves(turtle.position, random, &mut leaves),
+                _ => {}
+            }
+        }
+
+        (wood, leaves)
+    }
+
+    fn grow_wood(&self, position: DVec3, width: f64, wood: &mut Vec<IVec3>) {
+        let center = position.floor().as_ivec3();
+        let radius = (width / 2.0).max(0.5);
+        let extent = radius.floor() as i32;
+
+        for y in -extent..=extent {
+            for z in -extent..=extent {
+                for x in -extent..=extent {
+                    let offset = IVec3::new(x, y, z);
+
+                    if (offset.length_squared() as f64) <= (radius * radius) {
+                        wood.push(center + offset);
+                    }
+                }
+            }
+        }
+    }
+
+    fn grow_leaves(&self, position: DVec3, random: &mut Random, leaves: &mut Vec<IVec3>) {
+        let center = position.floor().as_ivec3();
+        let extent = self.leaf_radius.ceil() as i32;
+        let radius_squared = self.leaf_radius * self.leaf_radius;
+
+        for y in -extent..=extent {
+            for z in -extent..=extent {
+                for x in -extent..=extent {
+                    let offset = IVec3::new(x, y, z);
+                    let distance_squared = offset.length_squared() as f64;
+
+                    // Thin out the outermost leaves, to soften the cluster's outline.
+                    if distance_squared > radius_squared
+                        || (distance_squared > (radius_squared * 0.6) && random.chance(0.3))
+                    {
+                        continue;
+                    }
+
+                    leaves.push(center + offset);
+                }
+            }
+        }
+    }
+}
+
+/// Grows a tree of a randomly chosen species.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LSystemTreeFeature {
+    pub species: Vec<TreeSpecies>,
+}
+
+impl Feature for LSystemTreeFeature {
+    fn place(&self, origin: IVec3, random: &mut Random, writer: &mut FeatureWriter) -> bool {
+        if self.species.is_empty() {
+            return false;
+        }
+
+        let species = &self.species[random.range_i32(0, self.species.len() as i32) as usize];
+        match writer.get_block(origin - IVec3::Y) {
+            Some(ground) if species.soil.contains(&ground) => {}
+            _ => return false,
+        }
+
+        // Wood is written first, so that branches pass through their own leaves.
+        let (wood, leaves) = species.grow(origin, random);
+        for position in wood {
+            writer.set_block(position, species.wood, Replace::Air);
+        }
+        for position in leaves {
+            writer.set_block(position, species.leaves, Replace::Air);
+        }
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SPECIES: &str = "
+# A small shrub.
+[shrub]
+axiom FL
+wood core:log
+leaves core:leaves
+soil core:grass
+";
+
+    fn parse(definitions: &str) -> Result<Vec<TreeSpecies>, SpeciesError> {
+        TreeSpecies::parse(definitions, &BlockRegistry::default())
+    }
+
+    // `SPECIES` with `line` appended, and its line number.
+    fn with_line(line: &str) -> (String, usize) {
+        let definitions = format!("{}{}\n", SPECIES, line);
+        let number = definitions.lines().count();
+
+        (definitions, number)
+    }
+
+    #[test]
+    fn default_species_parse() {
+        let registry = BlockRegistry::default();
+        let species = TreeSpecies::defaults(&registry);
+
+        assert_eq!(
+            species
+                .iter()
+                .map(|species| species.name.as_str())
+                .collect::<Vec<_>>(),
+            ["oak", "birch", "pine"]
+        );
+        assert_eq!(species[0].wood, registry.core_block("log"));
+        assert_eq!(species[0].lsystem.rules[&'A'].len(), 2);
+    }
+
+    #[test]
+    fn invalid_lines_are_reported_by_number() {
+        for line in [
+            "angle",
+            "angle thirty",
+            "angle inf",
+            "iterations -1",
+            "rule A 1",
+            "rule AB 1 F",
+            "rule A one F",
+            "colour green",
+            "[]",
+            "step_length 0",
+            "step_length 17",
+            "width -1",
+            "width 100",
+            "width_decay 0",
+            "width_decay 1.5",
+            "leaf_radius -0.5",
+            "leaf_radius 16.5",
+        ] {
+            let (definitions, number) = with_line(line);
+            assert_eq!(
+                parse(&definitions),
+                Err(SpeciesError::InvalidLine(number, line.to_string()))
+            );
+        }
+
+        assert_eq!(
+            parse("axiom F"),
+            Err(SpeciesError::InvalidLine(1, "axiom F".to_string()))
+        );
+
+        for line in [
+            "step_length 16",
+            "width 0.5",
+            "width_decay 1",
+            "leaf_radius 0",
+        ] {
+            assert!(parse(&with_line(line).0).is_ok(), "\"{}\"", line);
+        }
+    }
+
+    #[test]
+    fn missing_properties_and_unknown_blocks_are_reported() {
+        assert_eq!(
+            parse("[bare]\naxiom F\nwood core:log\nsoil core:dirt"),
+            Err(SpeciesError::MissingProperty("bare".to_string(), "leaves"))
+        );
+        assert_eq!(
+            parse(&SPECIES.replace("axiom FL", "")),
+            Err(SpeciesError::MissingProperty("shrub".to_string(), "axiom"))
+        );
+        assert_eq!(
+            parse(&with_line("soil core:grass mod:moss").0),
+            Err(SpeciesError::UnknownBlock("mod:moss".to_string()))
+        );
+    }
+}
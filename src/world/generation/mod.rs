@@ -0,0 +1,21 @@
+mod biome;
+mod caves;
+mod erosion;
+mod features;
+mod lsystem;
+mod noise;
+mod presets;
+mod random;
+mod terrain;
+mod wfc;
+
+pub use biome::*;
+pub use caves::*;
+pub use erosion::*;
+pub use features::*;
+pub use lsystem::*;
+pub use noise::*;
+pub use presets::*;
+pub use random::*;
+pub use terrain::*;
+pub use wfc::*;
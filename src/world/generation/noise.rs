@@ -0,0 +1,182 @@
+//! Seeded, deterministic noise.
+//!
+//! Everything here is built from integer hashing and basic floating point
+//! arithmetic, so that a given seed produces identical output on every platform.
+
+/// Finalizer of SplitMix64, scrambling `value` into well distributed bits.
+pub const fn mix64(mut value: u64) -> u64 {
+    value = (value ^ (value >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    value = (value ^ (value >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    value ^ (value >> 31)
+}
+
+/// Derives an independent seed for `stream` from `seed`, so that separate noise
+/// fields of the same world are uncorrelated.
+pub const fn derive_seed(seed: u64, stream: u64) -> u64 {
+    mix64(seed ^ mix64(stream.wrapping_add(0x9E37_79B9_7F4A_7C15)))
+}
+
+/// Hashes integer lattice coordinates under `seed`.
+pub const fn hash3(seed: u64, x: i32, y: i32, z: i32) -> u64 {
+    let mut hash = mix64(seed);
+    hash = mix64(hash ^ (x as i64 as u64));
+    hash = mix64(hash ^ (y as i64 as u64).rotate_left(21));
+    mix64(hash ^ (z as i64 as u64).rotate_left(42))
+}
+
+/// Hashes integer lattice coordinates on the horizontal plane under `seed`.
+pub const fn hash2(seed: u64, x: i32, z: i32) -> u64 {
+    hash3(seed, x, 0, z)
+}
+
+const GRADIENTS_2D: [(f64, f64); 8] = [
+    (1.0, 1.0),
+    (-1.0, 1.0),
+    (1.0, -1.0),
+    (-1.0, -1.0),
+    (1.0, 0.0),
+    (-1.0, 0.0),
+    (0.0, 1.0),
+    (0.0, -1.0),
+];
+
+const GRADIENTS_3D: [(f64, f64, f64); 12] = [
+    (1.0, 1.0, 0.0),
+    (-1.0, 1.0, 0.0),
+    (1.0, -1.0, 0.0),
+    (-1.0, -1.0, 0.0),
+    (1.0, 0.0, 1.0),
+    (-1.0, 0.0, 1.0),
+    (1.0, 0.0, -1.0),
+    (-1.0, 0.0, -1.0),
+    (0.0, 1.0, 1.0),
+    (0.0, -1.0, 1.0),
+    (0.0, 1.0, -1.0),
+    (0.0, -1.0, -1.0),
+];
+
+fn fade(t: f64) -> f64 {
+    t * t * t * (t * ((t * 6.0) - 15.0) + 10.0)
+}
+
+fn lerp(a: f64, b: f64, t: f64) -> f64 {
+    a + ((b - a) * t)
+}
+
+fn grad2(seed: u64, x: i32, z: i32, dx: f64, dz: f64) -> f64 {
+    let (gx, gz) = GRADIENTS_2D[(hash2(seed, x, z) >> 61) as usize];
+    (gx * dx) + (gz * dz)
+}
+
+fn grad3(seed: u64, x: i32, y: i32, z: i32, dx: f64, dy: f64, dz: f64) -> f64 {
+    let (gx, gy, gz) = GRADIENTS_3D[((hash3(seed, x, y, z) >> 32) % 12) as usize];
+    (gx * dx) + (gy * dy) + (gz * dz)
+}
+
+/// Samples 2D gradient noise at `(x, z)`, in roughly `[-1, 1]`.
+pub fn gradient2(seed: u64, x: f64, z: f64) -> f64 {
+    let (x0, z0) = (x.floor(), z.floor());
+    let (dx, dz) = (x - x0, z - z0);
+    let (ix, iz) = (x0 as i32, z0 as i32);
+
+    let (u, v) = (fade(dx), fade(dz));
+
+    let n00 = grad2(seed, ix, iz, dx, dz);
+    let n10 = grad2(seed, ix + 1, iz, dx - 1.0, dz);
+    let n01 = grad2(seed, ix, iz + 1, dx, dz - 1.0);
+    let n11 = grad2(seed, ix + 1, iz + 1, dx - 1.0, dz - 1.0);
+
+    lerp(lerp(n00, n10, u), lerp(n01, n11, u), v)
+}
+
+/// Samples 3D gradient noise at `(x, y, z)`, in roughly `[-1, 1]`.
+pub fn gradient3(seed: u64, x: f64, y: f64, z: f64) -> f64 {
+    let (x0, y0, z0) = (x.floor(), y.floor(), z.floor());
+    let (dx, dy, dz) = (x - x0, y - y0, z - z0);
+    let (ix, iy, iz) = (x0 as i32, y0 as i32, z0 as i32);
+
+    let (u, v, w) = (fade(dx), fade(dy), fade(dz));
+
+    let n000 = grad3(seed, ix, iy, iz, dx, dy, dz);
+    let n100 = grad3(seed, ix + 1, iy, iz, dx - 1.0, dy, dz);
+    let n010 = grad3(seed, ix, iy + 1, iz, dx, dy - 1.0, dz);
+    let n110 = grad3(seed, ix + 1, iy + 1, iz, dx - 1.0, dy - 1.0, dz);
+    let n001 = grad3(seed, ix, iy, iz + 1, dx, dy, dz - 1.0);
+    let n101 = grad3(seed, ix + 1, iy, iz + 1, dx - 1.0, dy, dz - 1.0);
+    let n011 = grad3(seed, ix, iy + 1, iz + 1, dx, dy - 1.0, dz - 1.0);
+    let n111 = grad3(seed, ix + 1, iy + 1, iz + 1, dx - 1.0, dy - 1.0, dz - 1.0);
+
+    lerp(
+        lerp(lerp(n000, n100, u), lerp(n010, n110, u), v),
+        lerp(lerp(n001, n101, u), lerp(n011, n111, u), v),
+        w,
+    )
+}
+
+/// Fractal sum of gradient noise octaves, each at a higher frequency and lower
+/// amplitude than the last.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FractalNoise {
+    pub octaves: u32,
+    /// Frequency of the first octave, in cycles per block.
+    pub frequency: f64,
+    /// Largest magnitude the sum can reach.
+    pub amplitude: f64,
+    /// Frequency multiplier between successive octaves.
+    pub lacunarity: f64,
+    /// Amplitude multiplier between successive octaves.
+    pub persistence: f64,
+}
+
+impl FractalNoise {
+    fn octaves(&self, seed: u64) -> impl Iterator<Item = (u64, f64, f64)> + '_ {
+        (0..self.octaves).scan((1.0, 1.0), move |(frequency, amplitude), octave| {
+            let current = (
+                derive_seed(seed, octave as u64),
+                self.frequency * *frequency,
+                *amplitude,
+            );
+            *frequency *= self.lacunarity;
+            *amplitude *= self.persistence;
+
+            Some(current)
+        })
+    }
+
+    fn normalization(&self) -> f64 {
+        let total = self
+            .octaves(0)
+            .map(|(_, _, amplitude)| amplitude)
+            .sum::<f64>();
+
+        if total > 0.0 {
+            self.amplitude / total
+        } else {
+            0.0
+        }
+    }
+
+    /// Samples the sum at `(x, z)`, in `[-amplitude, amplitude]`.
+    pub fn sample2(&self, seed: u64, x: f64, z: f64) -> f64 {
+        let sum = self
+            .octaves(seed)
+            .map(|(seed, frequency, amplitude)| {
+                gradient2(seed, x * frequency, z * frequency) * amplitude
+            })
+            .sum::<f64>();
+
+        (sum * self.normalization()).clamp(-self.amplitude, self.amplitude)
+    }
+
+    /// Samples the sum at `(x, y, z)`, in `[-amplitude, amplitude]`.
+    pub fn sample3(&self, seed: u64, x: f64, y: f64, z: f64) -> f64 {
+        let sum = self
+            .octaves(seed)
+            .map(|(seed, frequency, amplitude)| {
+                gradient3(seed, x * frequency, y * frequency, z * frequency) * amplitude
+            })
+            .sum::<f64>();
+
+        (sum * self.normalization()).clamp(-self.amplitude, self.amplitude)
+    }
+}
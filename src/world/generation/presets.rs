@@ -0,0 +1,344 @@
+use super::{
+    BiomeMap, CaveSettings, CaveStep, ErosionSettings, FeatureStep, StructureStep, TerrainBlocks,
+    TerrainSettings, TerrainStep,
+};
+use crate::world::{
+    block::{Block, BlockRegistry},
+    chunk::{
+        BlockStorageMut, ChunkGenerationContext, ChunkGenerationStep, ChunkGenerationSystem,
+        PendingWrites, CHUNK_SIZE, CHUNK_SIZE_SQUARED,
+    },
+};
+use std::sync::Arc;
+
+/// Layers of the `flat` preset, when none are given.
+pub const DEFAULT_FLAT_LAYERS: &str = "1*core:bedrock,3*core:dirt,1*core:grass";
+
+/// Thickest layer accepted in a layer string, in blocks.
+pub const MAX_LAYER_THICKNESS: u32 = 4096;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PresetError {
+    /// No preset has the given name.
+    UnknownPreset(String),
+    /// A layer isn't of the form `[thickness*]block`.
+    InvalidLayer(String),
+    /// No block is registered under the given name.
+    UnknownBlock(String),
+}
+
+impl std::fmt::Display for PresetError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnknownPreset(name) => write!(f, "unknown preset \"{}\"", name),
+            Self::InvalidLayer(layer) => write!(
+                f,
+                "invalid layer \"{}\", expected \"[thickness*]block\" with a thickness from 1 to {}",
+                layer, MAX_LAYER_THICKNESS
+            ),
+            Self::UnknownBlock(name) => write!(f, "block \"{}\" is not registered", name),
+        }
+    }
+}
+
+impl std::error::Error for PresetError {}
+
+fn lookup_block(registry: &BlockRegistry, name: &str) -> Result<Block, PresetError> {
+    registry
+        .get_block(name)
+        .ok_or_else(|| PresetError::UnknownBlock(name.to_string()))
+}
+
+/// A horizontal layer of a flat world.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FlatLayer {
+    pub block: Block,
+    /// Thickness of the layer, in blocks.
+    pub thickness: u32,
+}
+
+impl FlatLayer {
+    /// Parses a comma separated list of layers, from the bottom up, each written as
+    /// `[thickness*]block` (e.g. `1*core:bedrock,3*core:dirt,core:grass`). At least
+    /// one layer must be given.
+    pub fn parse_layers(layers: &str, registry: &BlockRegistry) -> Result<Vec<Self>, PresetError> {
+        let parsed = layers
+            .split(',')
+            .map(str::trim)
+            .filter(|layer| !layer.is_empty())
+            .map(|layer| {
+                let (thickness, name) = match layer.split_once('*') {
+                    Some((thickness, name)) => (
+                        thickness
+                            .trim()
+                            .parse::<u32>()
+                            .ok()
+                            .filter(|thickness| (1..=MAX_LAYER_THICKNESS).contains(thickness))
+                            .ok_or_else(|| PresetError::InvalidLayer(layer.to_string()))?,
+                        name.trim(),
+                    ),
+                    None => (1, layer),
+                };
+
+                Ok(Self {
+                    block: lookup_block(registry, name)?,
+                    thickness,
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if parsed.is_empty() {
+            return Err(PresetError::InvalidLayer(layers.to_string()));
+        }
+
+        Ok(parsed)
+    }
+}
+
+/// Stacks horizontal layers of blocks, from the bottom up, leaving everything
+/// above and below them as air.
+pub struct FlatStep {
+    layers: Vec<FlatLayer>,
+    base_height: i32,
+}
+
+impl FlatStep {
+    /// Stacks `layers` so that the topmost layer lies just below a height of zero.
+    pub fn new(layers: Vec<FlatLayer>) -> Self {
+        let thickness = layers.iter().fold(0i32, |total, layer| {
+            total.saturating_add(layer.thickness as i32)
+        });
+
+        Self {
+            layers,
+            base_height: -thickness,
+        }
+    }
+
+    /// Places the bottom of the lowest layer at block-space `base_height`.
+    pub fn with_base_height(mut self, base_height: i32) -> Self {
+        self.base_height = base_height;
+        self
+    }
+
+    pub fn layers(&self) -> &[FlatLayer] {
+        &self.layers
+    }
+
+    pub const fn base_height(&self) -> i32 {
+        self.base_height
+    }
+}
+
+impl ChunkGenerationStep for FlatStep {
+    fn gen_pass(&self, context: &ChunkGenerationContext, blocks: &mut dyn BlockStorageMut) {
+        let origin_y = context.origin().y as i64;
+        let mut bottom = self.base_height as i64;
+
+        for layer in &self.layers {
+            let top = bottom + (layer.thickness as i64);
+            let min_y = (bottom - origin_y).clamp(0, CHUNK_SIZE as i64) as usize;
+            let max_y = (top - origin_y).clamp(0, CHUNK_SIZE as i64) as usize;
+
+            // Layers span whole planes of the chunk, which are contiguous in storage.
+            if min_y < max_y {
+                blocks.fill_range(
+                    (min_y * CHUNK_SIZE_SQUARED as usize)..(max_y * CHUNK_SIZE_SQUARED as usize),
+                    layer.block,
+                );
+            }
+
+            bottom = top;
+        }
+    }
+}
+
+/// Fills everything below block-space `height` with a single block.
+pub struct SolidStep {
+    block: Block,
+    height: i32,
+}
+
+impl SolidStep {
+    pub fn new(block: Block, height: i32) -> Self {
+        Self { block, height }
+    }
+}
+
+impl ChunkGenerationStep for SolidStep {
+    fn gen_pass(&self, context: &ChunkGenerationContext, blocks: &mut dyn BlockStorageMut) {
+        let max_y = (self.height - context.origin().y).clamp(0, CHUNK_SIZE) as usize;
+
+        if max_y > 0 {
+            blocks.fill_range(0..(max_y * CHUNK_SIZE_SQUARED as usize), self.block);
+        }
+    }
+}
+
+/// A named arrangement of generation steps, selectable at startup.
+///
+/// Presets are written as `name[:options]`:
+///
+/// | Preset | World |
+/// |--------|-------|
+/// | `noise` | Eroded biome terrain with caves, dungeons and features (the default) |
+/// | `flat[:layers]` | Flat layers, e.g. `flat:1*core:bedrock,3*core:dirt,1*core:grass` |
+/// | `single[:block]` | A single block below a height of zero, `core:stone` by default |
+/// | `void` | Nothing but air |
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WorldPreset {
+    Noise,
+    Flat(Vec<FlatLayer>),
+    Single(Block),
+    Void,
+}
+
+impl WorldPreset {
+    pub fn parse(preset: &str, registry: &BlockRegistry) -> Result<Self, PresetError> {
+        let (name, options) = match preset.trim().split_once(':') {
+            Some((name, options)) => (name, Some(options)),
+            None => (preset.trim(), None),
+        };
+
+        match (name, options) {
+            ("noise", None) => Ok(Self::Noise),
+            ("void", None) => Ok(Self::Void),
+            ("flat", layers) => Ok(Self::Flat(FlatLayer::parse_layers(
+                layers.unwrap_or(DEFAULT_FLAT_LAYERS),
+                registry,
+            )?)),
+            ("single", block) => Ok(Self::Single(lookup_block(
+                registry,
+                block.unwrap_or("core:stone"),
+            )?)),
+            _ => Err(PresetError::UnknownPreset(preset.to_string())),
+        }
+    }
+
+    /// Assembles the generation pipeline of the preset.
+    ///
+    /// Steps which spill over their chunk's edges queue their writes into
+    /// `pending_writes`, which must be the same queue the system reads.
+    pub fn build(
+        &self,
+        seed: u64,
+        pending_writes: PendingWrites,
+        registry: &BlockRegistry,
+    ) -> ChunkGenerationSystem {
+        let system = ChunkGenerationSystem::new(seed);
+
+        match self {
+            Self::Noise => {
+                let biomes = Arc::new(BiomeMap::from_registry(registry));
+
+                system
+                    .with_step(
+                        TerrainStep::new(
+                            TerrainSettings::default(),
+                            TerrainBlocks::from_registry(registry),
+                        )
+                        .with_biomes(Arc::clone(&biomes))
+                        .with_erosion(ErosionSettings::default()),
+                    )
+                    .with_step(CaveStep::new(CaveSettings::default()))
+                    .with_step(StructureStep::dungeons(registry))
+                    .with_step(
+                        FeatureStep::from_registry(pending_writes, registry).with_biomes(biomes),
+                    )
+            }
+            Self::Flat(layers) => system.with_step(FlatStep::new(layers.clone())),
+            Self::Single(block) => system.with_step(SolidStep::new(*block, 0)),
+            Self::Void => system,
+        }
+    }
+}
+
+impl Default for WorldPreset {
+    fn default() -> Self {
+        Self::Noise
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layer(registry: &BlockRegistry, thickness: u32, name: &str) -> FlatLayer {
+        FlatLayer {
+            block: registry.core_block(name),
+            thickness,
+        }
+    }
+
+    #[test]
+    fn layers_parse_from_the_bottom_up() {
+        let registry = BlockRegistry::default();
+
+        assert_eq!(
+            FlatLayer::parse_layers(" 1*core:bedrock, 3 * core:dirt,,core:grass ", &registry),
+            Ok(vec![
+                layer(&registry, 1, "bedrock"),
+                layer(&registry, 3, "dirt"),
+                layer(&registry, 1, "grass"),
+            ])
+        );
+        assert_eq!(
+            FlatLayer::parse_layers(DEFAULT_FLAT_LAYERS, &registry).map(|layers| layers.len()),
+            Ok(3)
+        );
+        assert_eq!(
+            FlatLayer::parse_layers("4096*core:stone", &registry),
+            Ok(vec![layer(&registry, MAX_LAYER_THICKNESS, "stone")])
+        );
+    }
+
+    #[test]
+    fn invalid_layers_are_rejected() {
+        let registry = BlockRegistry::default();
+
+        for (layers, invalid) in [
+            ("0*core:stone", "0*core:stone"),
+            ("4097*core:stone", "4097*core:stone"),
+            ("core:dirt,-1*core:stone", "-1*core:stone"),
+            ("two*core:stone", "two*core:stone"),
+            ("", ""),
+            (" , ,", " , ,"),
+        ] {
+            assert_eq!(
+                FlatLayer::parse_layers(layers, &registry),
+                Err(PresetError::InvalidLayer(invalid.to_string()))
+            );
+        }
+
+        assert_eq!(
+            FlatLayer::parse_layers("core:stone,2*mod:marble", &registry),
+            Err(PresetError::UnknownBlock("mod:marble".to_string()))
+        );
+    }
+
+    #[test]
+    fn presets_parse_by_name() {
+        let registry = BlockRegistry::default();
+
+        assert_eq!(
+            WorldPreset::parse("noise", &registry),
+            Ok(WorldPreset::Noise)
+        );
+        assert_eq!(
+            WorldPreset::parse(" void ", &registry),
+            Ok(WorldPreset::Void)
+        );
+        assert_eq!(
+            WorldPreset::parse("flat:core:sand", &registry),
+            Ok(WorldPreset::Flat(vec![layer(&registry, 1, "sand")]))
+        );
+        assert_eq!(
+            WorldPreset::parse("single", &registry),
+            Ok(WorldPreset::Single(registry.core_block("stone")))
+        );
+        assert_eq!(
+            WorldPreset::parse("noise:loud", &registry),
+            Err(PresetError::UnknownPreset("noise:loud".to_string()))
+        );
+    }
+}
@@ -0,0 +1,80 @@
+use super::{derive_seed, hash3, mix64};
+use glam::IVec3;
+
+const GOLDEN_GAMMA: u64 = 0x9E37_79B9_7F4A_7C15;
+
+/// Small, seeded SplitMix64 generator.
+///
+/// Generators are cheap to create, so rather than sharing one, each piece of
+/// generation derives its own from the world seed and the position it's
+/// working on; the output then doesn't depend on what was generated before it.
+#[derive(Debug, Clone)]
+pub struct Random {
+    state: u64,
+}
+
+impl Random {
+    pub const fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    /// A generator unique to `stream` of the world `seed` at `position`.
+    pub const fn at(seed: u64, stream: u64, position: IVec3) -> Self {
+        Self::new(hash3(
+            derive_seed(seed, stream),
+            position.x,
+            position.y,
+            position.z,
+        ))
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(GOLDEN_GAMMA);
+        mix64(self.state)
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+
+    /// Uniform value in `[0, 1)`.
+    pub fn next_f64(&mut self) -> f64 {
+        ((self.next_u64() >> 11) as f64) * (1.0 / ((1u64 << 53) as f64))
+    }
+
+    /// Uniform value in `[min, max)`.
+    pub fn range_f64(&mut self, min: f64, max: f64) -> f64 {
+        min + ((max - min) * self.next_f64())
+    }
+
+    /// Uniform value in `[min, max)`, or `min` if the range is empty.
+    pub fn range_i32(&mut self, min: i32, max: i32) -> i32 {
+        if max <= min {
+            return min;
+        }
+
+        min.wrapping_add((self.next_u64() % ((max as i64 - min as i64) as u64)) as i32)
+    }
+
+    /// Returns true with the given `probability`.
+    pub fn chance(&mut self, probability: f64) -> bool {
+        self.next_f64() < probability
+    }
+
+    /// Random direction, uniformly distributed over the unit sphere.
+    pub fn unit_vector(&mut self) -> glam::DVec3 {
+        // Rejection sampling avoids trigonometry, whose precision varies by platform.
+        loop {
+            let vector = glam::DVec3::new(
+                self.range_f64(-1.0, 1.0),
+                self.range_f64(-1.0, 1.0),
+                self.range_f64(-1.0, 1.0),
+            );
+            let length_squared = vector.length_squared();
+
+            if length_squared > 1.0e-6 && length_squared <= 1.0 {
+                return vector / length_squared.sqrt();
+            }
+        }
+    }
+}
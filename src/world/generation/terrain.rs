@@ -0,0 +1,277 @@
+use super::{derive_seed, BiomeMap, ErosionSettings, FractalNoise, HydraulicErosion};
+use crate::world::{
+    block::{Block, BlockRegistry},
+    chunk::{
+        BlockStorageMut, ChunkGenerationContext, ChunkGenerationStep, CHUNK_SIZE, CHUNK_SIZE_CUBED,
+        CHUNK_SIZE_SQUARED,
+    },
+};
+use std::sync::Arc;
+
+const HEIGHT_STREAM: u64 = 0x4845_4947_4854;
+const OVERHANG_STREAM: u64 = 0x4F56_4552_4841_4E47;
+
+/// Blocks making up the layers of generated terrain.
+#[derive(Debug, Clone, Copy)]
+pub struct TerrainBlocks {
+    pub stone: Block,
+    pub dirt: Block,
+    pub grass: Block,
+}
+
+impl TerrainBlocks {
+    /// Looks up the `core` terrain blocks in `registry`.
+    pub fn from_registry(registry: &BlockRegistry) -> Self {
+        Self {
+            stone: registry.core_block("stone"),
+            dirt: registry.core_block("dirt"),
+            grass: registry.core_block("grass"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TerrainSettings {
+    /// Block-space height around which the surface undulates.
+    pub base_height: f64,
+    /// Offset of the surface from `base_height`, sampled per column.
+    pub height: FractalNoise,
+    /// Offset of the surface sampled per block, producing overhangs and arches.
+    /// Disabled when its amplitude is zero.
+    pub overhang: FractalNoise,
+    /// Depth of dirt beneath the grass surface, before stone begins.
+    pub dirt_depth: i32,
+}
+
+impl Default for TerrainSettings {
+    fn default() -> Self {
+        Self {
+            base_height: 0.0,
+            height: FractalNoise {
+                octaves: 5,
+                frequency: 1.0 / 256.0,
+                amplitude: 48.0,
+                lacunarity: 2.0,
+                persistence: 0.5,
+            },
+            overhang: FractalNoise {
+                octaves: 2,
+                frequency: 1.0 / 32.0,
+                amplitude: 6.0,
+                lacunarity: 2.0,
+                persistence: 0.5,
+            },
+            dirt_depth: 3,
+        }
+    }
+}
+
+/// Surface of a single column of terrain.
+#[derive(Debug, Clone, Copy)]
+struct TerrainColumn {
+    height: f64,
+    surface: Block,
+    subsurface: Block,
+}
+
+/// Generates heightmap terrain from seeded fractal noise, layered as grass over
+/// dirt over stone.
+///
+/// With a biome map, each column's surface blocks come from its dominant biome,
+/// and its height from the blend of nearby biomes. With erosion, the heightmap is
+/// eroded before any blocks are placed.
+pub struct TerrainStep {
+    settings: TerrainSettings,
+    blocks: TerrainBlocks,
+    biomes: Option<Arc<BiomeMap>>,
+    erosion: Option<HydraulicErosion>,
+}
+
+impl TerrainStep {
+    pub fn new(settings: TerrainSettings, blocks: TerrainBlocks) -> Self {
+        Self {
+            settings,
+            blocks,
+            biomes: None,
+            erosion: None,
+        }
+    }
+
+    pub fn with_biomes(mut self, biomes: Arc<BiomeMap>) -> Self {
+        self.biomes = Some(biomes);
+        self
+    }
+
+    /// Erodes the heightmap by simulating rainfall over it.
+    pub fn with_erosion(mut self, settings: ErosionSettings) -> Self {
+        self.erosion = Some(HydraulicErosion::new(settings));
+        self
+    }
+
+    pub const fn settings(&self) -> &TerrainSettings {
+        &self.settings
+    }
+
+    pub fn biomes(&self) -> Option<&Arc<BiomeMap>> {
+        self.biomes.as_ref()
+    }
+
+    pub fn erosion(&self) -> Option<&HydraulicErosion> {
+        self.erosion.as_ref()
+    }
+
+    fn column(&self, seed: u64, x: i32, z: i32) -> TerrainColumn {
+        let mut column = self.uneroded_column(seed, x, z);
+
+        if let Some(erosion) = &self.erosion {
+            column.height +=
+                erosion.offset(seed, x, z, &|x, z| self.uneroded_column(seed, x, z).height);
+        }
+
+        column
+    }
+
+    fn uneroded_column(&self, seed: u64, x: i32, z: i32) -> TerrainColumn {
+        let noise =
+            self.settings
+                .height
+                .sample2(derive_seed(seed, HEIGHT_STREAM), x as f64, z as f64);
+
+        match &self.biomes {
+            Some(biomes) => {
+                let sample = biomes.sample(seed, x, z);
+                let biome = biomes.biome(sample.dominant);
+
+                TerrainColumn {
+                    height: self.settings.base_height
+                        + sample.height_offset
+                        + (noise * sample.height_scale),
+                    surface: biome.surface,
+                    subsurface: biome.subsurface,
+                }
+            }
+            None => TerrainColumn {
+                height: self.settings.base_height + noise,
+                surface: self.blocks.grass,
+                subsurface: self.blocks.dirt,
+            },
+        }
+    }
+
+    /// Height of the surface at block-space column `(x, z)`, after erosion but
+    /// before overhangs.
+    pub fn surface_height(&self, seed: u64, x: i32, z: i32) -> f64 {
+        self.column(seed, x, z).height
+    }
+
+    /// Whether the block at block-space position `(x, y, z)` is solid, given the
+    /// surface height of its column.
+    fn is_solid(&self, seed: u64, surface_height: f64, x: i32, y: i32, z: i32) -> bool {
+        let overhang = if self.settings.overhang.amplitude > 0.0 {
+            self.settings.overhang.sample3(
+                derive_seed(seed, OVERHANG_STREAM),
+                x as f64,
+                y as f64,
+                z as f64,
+            )
+        } else {
+            0.0
+        };
+
+        (y as f64) < (surface_height + overhang)
+    }
+}
+
+impl ChunkGenerationStep for TerrainStep {
+    fn gen_pass(&self, context: &ChunkGenerationContext, blocks: &mut dyn BlockStorageMut) {
+        let origin = context.origin();
+        let overhang = self.settings.overhang.amplitude.max(0.0);
+        let mut generated = vec![Block::AIR; CHUNK_SIZE_CUBED as usize];
+
+        for z in 0..CHUNK_SIZE {
+            for x in 0..CHUNK_SIZE {
+                let (world_x, world_z) = (origin.x + x, origin.z + z);
+                let column = self.column(context.seed, world_x, world_z);
+                let surface_height = column.height;
+
+                // Columns entirely above the surface are left as air.
+                if (origin.y as f64) >= (surface_height + overhang) {
+                    continue;
+                }
+
+                // Walk the column downwards, starting far enough above the chunk
+                // to tell how deep beneath the surface its topmost blocks lie.
+                let mut depth = 0;
+                for y in (0..(CHUNK_SIZE + self.settings.dirt_depth + 1)).rev() {
+                    let world_y = origin.y + y;
+
+                    let solid = if (world_y as f64) < (surface_height - overhang) {
+                        true
+                    } else if (world_y as f64) >= (surface_height + overhang) {
+                        false
+                    } else {
+                        self.is_solid(context.seed, surface_height, world_x, world_y, world_z)
+                    };
+
+                    depth = if solid { depth + 1 } else { 0 };
+
+                    if y < CHUNK_SIZE && depth > 0 {
+                        generated[(x + (z * CHUNK_SIZE) + (y * CHUNK_SIZE_SQUARED)) as usize] =
+                            if depth == 1 {
+                                column.surface
+                            } else if depth <= (self.settings.dirt_depth + 1) {
+                                column.subsurface
+                            } else {
+                                self.blocks.stone
+                            };
+                    }
+                }
+            }
+        }
+
+        blocks.copy_from_slice(&generated);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::chunk::{BlockStorage, ChunkPos, ChunkStorage};
+
+    fn generate(step: &TerrainStep, seed: u64, position: ChunkPos) -> Vec<Block> {
+        let mut storage = ChunkStorage::default();
+        step.gen_pass(&ChunkGenerationContext { position, seed }, &mut storage);
+
+        let mut blocks = vec![Block::AIR; storage.len()];
+        storage.copy_to_slice(&mut blocks);
+        blocks
+    }
+
+    fn step() -> TerrainStep {
+        TerrainStep::new(
+            TerrainSettings::default(),
+            TerrainBlocks::from_registry(&BlockRegistry::default()),
+        )
+    }
+
+    #[test]
+    fn chunks_generate_identically_for_the_same_seed_and_position() {
+        let (first, second) = (step(), step());
+
+        for position in [
+            ChunkPos::new(0, -1, 0),
+            ChunkPos::new(0, 0, 0),
+            ChunkPos::new(-3, 1, 7),
+        ] {
+            let blocks = generate(&first, 1, position);
+            assert!(blocks == generate(&first, 1, position));
+            assert!(blocks == generate(&second, 1, position));
+        }
+
+        let seeds_differ = (-4..4).any(|x| {
+            let position = ChunkPos::new(x, 0, 0);
+            generate(&first, 1, position) != generate(&first, 2, position)
+        });
+        assert!(seeds_differ, "terrain doesn't depend on the seed");
+    }
+}
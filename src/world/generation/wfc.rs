@@ -0,0 +1,701 @@
+use super::Random;
+use crate::world::{
+    block::{Block, BlockRegistry},
+    chunk::{
+        BlockStorageMut, ChunkGenerationContext, ChunkGenerationStep, CHUNK_SIZE, CHUNK_SIZE_CUBED,
+        CHUNK_SIZE_SQUARED, NEIGHBOR_OFFSETS,
+    },
+};
+use glam::IVec3;
+use std::collections::HashMap;
+
+const STRUCTURE_STREAM: u64 = 0x5354_5255_4354;
+
+/// Tile sets are limited to this many tiles, so that each cell's remaining
+/// possibilities fit in a single `u128`.
+pub const MAX_TILES: usize = 128;
+
+/// Identifies which faces may touch; two tiles may be adjacent if the sockets of
+/// their touching faces are equal.
+pub type Socket = u32;
+
+/// A small block template, placed as one cell of a structure.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tile {
+    pub name: String,
+    /// Blocks of the template, indexed `x + z * size.x + y * size.x * size.z`.
+    /// `None` leaves the existing block untouched.
+    pub blocks: Vec<Option<Block>>,
+    /// Sockets of each face, ordered as `NEIGHBOR_OFFSETS`.
+    pub sockets: [Socket; 6],
+    /// Relative likelihood of the tile being chosen.
+    pub weight: f64,
+}
+
+impl Tile {
+    /// Builds a tile from `layers` of text, from the bottom up. Each layer lists its
+    /// rows along z, and each row its blocks along x, as characters of `legend`.
+    pub fn from_layers(
+        name: &str,
+        size: IVec3,
+        layers: &[&[&str]],
+        legend: &HashMap<char, Option<Block>>,
+        sockets: [Socket; 6],
+        weight: f64,
+    ) -> Self {
+        assert_eq!(
+            layers.len(),
+            size.y as usize,
+            "Tile \"{}\" has the wrong count of layers.",
+            name
+        );
+
+        let mut blocks = Vec::with_capacity((size.x * size.y * size.z) as usize);
+        for layer in layers {
+            assert_eq!(
+                layer.len(),
+                size.z as usize,
+                "Tile \"{}\" has the wrong count of rows.",
+                name
+            );
+
+            for row in layer.iter() {
+                assert_eq!(
+                    row.chars().count(),
+                    size.x as usize,
+                    "Tile \"{}\" has a row of the wrong length.",
+                    name
+                );
+
+                blocks.extend(row.chars().map(|character| {
+                    *legend.get(&character).unwrap_or_else(|| {
+                        panic!(
+                            "Tile \"{}\" uses '{}', which is not in the legend.",
+                            name, character
+                        )
+                    })
+                }));
+            }
+        }
+
+        Self {
+            name: name.to_string(),
+            blocks,
+            sockets,
+            weight,
+        }
+    }
+}
+
+/// Tiles of a single size, from which structures are assembled.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TileSet {
+    size: IVec3,
+    tiles: Vec<Tile>,
+}
+
+impl TileSet {
+    /// Socket of dungeon tile faces which are walled off.
+    pub const DUNGEON_CLOSED: Socket = 0;
+    /// Socket of dungeon tile faces with a doorway.
+    pub const DUNGEON_OPEN: Socket = 1;
+
+    pub fn new(size: IVec3) -> Self {
+        assert!(
+            size.cmpgt(IVec3::ZERO).all(),
+            "Tiles must have a positive size."
+        );
+
+        Self {
+            size,
+            tiles: Vec::new(),
+        }
+    }
+
+    /// Dungeon corridors and rooms built from the `core` blocks in `registry`.
+    ///
+    /// Every combination of openings on the four horizontal faces gets a tile, so
+    /// that any layout of corridors can be completed.
+    pub fn dungeon(registry: &BlockRegistry) -> Self {
+        const SIZE: i32 = 5;
+        const CLOSED: Socket = TileSet::DUNGEON_CLOSED;
+        const OPEN: Socket = TileSet::DUNGEON_OPEN;
+
+        let wall = Some(registry.core_block("cobblestone"));
+
+        let mut tile_set = Self::new(IVec3::splat(SIZE));
+        tile_set.add_tile(Tile {
+            name: "empty".to_string(),
+            blocks: vec![None; (SIZE * SIZE * SIZE) as usize],
+            sockets: [CLOSED; 6],
+            weight: 4.0,
+        });
+
+        // Openings are ordered east, north, west, south, matching the horizontal
+        //  faces of `NEIGHBOR_OFFSETS`.
+        for openings in 1..16u32 {
+            let open = |face: u32| (openings & (1 << face)) != 0;
+            let mut blocks = vec![wall; (SIZE * SIZE * SIZE) as usize];
+
+            for y in 1..(SIZE - 1) {
+                for z in 1..(SIZE - 1) {
+                    for x in 1..(SIZE - 1) {
+                        blocks[(x + (z * SIZE) + (y * SIZE * SIZE)) as usize] = Some(Block::AIR);
+                    }
+                }
+
+                // Carve a doorway through each open face.
+                for offset in 1..(SIZE - 1) {
+                    let doorways = [
+                        (open(0), SIZE - 1, offset),
+                        (open(1), offset, SIZE - 1),
+                        (open(2), 0, offset),
+                        (open(3), offset, 0),
+                    ];
+
+                    for (is_open, x, z) in doorways {
+                        if is_open {
+                            blocks[(x + (z * SIZE) + (y * SIZE * SIZE)) as usize] =
+                                Some(Block::AIR);
+                        }
+                    }
+                }
+            }
+
+            let socket = |face: u32| if open(face) { OPEN } else { CLOSED };
+            let exits = openings.count_ones();
+
+            tile_set.add_tile(Tile {
+                name: format!("corridor_{:04b}", openings),
+                blocks,
+                sockets: [socket(0), CLOSED, socket(1), socket(2), CLOSED, socket(3)],
+                // Favour corridors over dead ends and crossings.
+                weight: if exits == 2 { 2.0 } else { 1.0 },
+            });
+        }
+
+        tile_set
+    }
+
+    pub fn with_tile(mut self, tile: Tile) -> Self {
+        self.add_tile(tile);
+        self
+    }
+
+    /// Adds `tile`, returning its index.
+    pub fn add_tile(&mut self, tile: Tile) -> usize {
+        assert!(
+            self.tiles.len() < MAX_TILES,
+            "Tile sets are limited to {} tiles.",
+            MAX_TILES
+        );
+        assert_eq!(
+            tile.blocks.len(),
+            (self.size.x * self.size.y * self.size.z) as usize,
+            "Tile \"{}\" does not match the size of its tile set.",
+            tile.name
+        );
+
+        self.tiles.push(tile);
+        self.tiles.len() - 1
+    }
+
+    pub const fn size(&self) -> IVec3 {
+        self.size
+    }
+
+    pub fn tile(&self, index: usize) -> &Tile {
+        &self.tiles[index]
+    }
+
+    pub fn len(&self) -> usize {
+        self.tiles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tiles.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WfcError {
+    /// No arrangement of tiles satisfies the adjacency rules.
+    Contradiction,
+    /// The solver gave up after backtracking the given number of times.
+    BacktrackLimit(usize),
+}
+
+impl std::fmt::Display for WfcError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Contradiction => {
+                write!(f, "no arrangement of tiles satisfies the adjacency rules")
+            }
+            Self::BacktrackLimit(backtracks) => {
+                write!(f, "gave up after backtracking {} times", backtracks)
+            }
+        }
+    }
+}
+
+impl std::error::Error for WfcError {}
+
+const fn opposite_face(face: usize) -> usize {
+    (face + 3) % 6
+}
+
+/// Position of `index` within a grid of `dimensions`, laid out x, then z, then y.
+fn grid_position(dimensions: IVec3, index: usize) -> IVec3 {
+    let index = index as i32;
+    let layer = dimensions.x * dimensions.z;
+
+    IVec3::new(
+        index % dimensions.x,
+        index / layer,
+        (index % layer) / dimensions.x,
+    )
+}
+
+fn grid_index(dimensions: IVec3, position: IVec3) -> usize {
+    (position.x + (position.z * dimensions.x) + (position.y * dimensions.x * dimensions.z)) as usize
+}
+
+/// Wave function collapse solver, arranging tiles in a grid such that every pair
+/// of adjacent tiles agrees on the socket between them.
+pub struct WaveFunctionCollapse<'a> {
+    tile_set: &'a TileSet,
+    dimensions: IVec3,
+    /// Socket assumed beyond the edges of the grid, if any.
+    boundary: Option<Socket>,
+    /// For each tile and face, the tiles which may neighbor it across that face.
+    compatible: Vec<[u128; 6]>,
+}
+
+impl<'a> WaveFunctionCollapse<'a> {
+    pub fn new(tile_set: &'a TileSet, dimensions: IVec3, boundary: Option<Socket>) -> Self {
+        assert!(
+            dimensions.cmpgt(IVec3::ZERO).all(),
+            "Grid must have a positive size."
+        );
+
+        let compatible = tile_set
+            .tiles
+            .iter()
+            .map(|tile| {
+                let mut faces = [0u128; 6];
+                for (face, mask) in faces.iter_mut().enumerate() {
+                    for (index, other) in tile_set.tiles.iter().enumerate() {
+                        if tile.sockets[face] == other.sockets[opposite_face(face)] {
+                            *mask |= 1 << index;
+                        }
+                    }
+                }
+
+                faces
+            })
+            .collect();
+
+        Self {
+            tile_set,
+            dimensions,
+            boundary,
+            compatible,
+        }
+    }
+
+    fn neighbor(&self, cell: usize, face: usize) -> Option<usize> {
+        let position = grid_position(self.dimensions, cell) + NEIGHBOR_OFFSETS[face];
+
+        if position.cmpge(IVec3::ZERO).all() && position.cmplt(self.dimensions).all() {
+            Some(grid_index(self.dimensions, position))
+        } else {
+            None
+        }
+    }
+
+    /// Narrows the possibilities of cells neighboring those in `queue` until no more
+    /// can be eliminated. Returns false if any cell is left without possibilities.
+    fn propagate(&self, cells: &mut [u128], mut queue: Vec<usize>) -> bool {
+        while let Some(cell) = queue.pop() {
+            for face in 0..6 {
+                let neighbor = match self.neighbor(cell, face) {
+                    Some(neighbor) => neighbor,
+                    None => continue,
+                };
+
+                let allowed = (0..self.tile_set.len())
+                    .filter(|tile| (cells[cell] & (1 << tile)) != 0)
+                    .fold(0, |allowed, tile| allowed | self.compatible[tile][face]);
+
+                let narrowed = cells[neighbor] & allowed;
+                if narrowed != cells[neighbor] {
+                    if narrowed == 0 {
+                        return false;
+                    }
+
+                    cells[neighbor] = narrowed;
+                    queue.push(neighbor);
+                }
+            }
+        }
+
+        true
+    }
+
+    /// Picks a tile from `possibilities`, weighted by tile weight.
+    fn choose(&self, possibilities: u128, random: &mut Random) -> usize {
+        let candidates = (0..self.tile_set.len()).filter(|tile| (possibilities & (1 << tile)) != 0);
+        let total = candidates
+            .clone()
+            .map(|tile| self.tile_set.tiles[tile].weight.max(0.0))
+            .sum::<f64>();
+
+        let mut target = random.next_f64() * total;
+        let mut chosen = None;
+        for tile in candidates {
+            chosen = Some(tile);
+            target -= self.tile_set.tiles[tile].weight.max(0.0);
+
+            if target < 0.0 {
+                break;
+            }
+        }
+
+        chosen.unwrap()
+    }
+
+    /// Solves the grid, returning the index of the tile chosen for each cell,
+    /// indexed `x + z * dimensions.x + y * dimensions.x * dimensions.z`.
+    ///
+    /// Choices leading to a contradiction are undone and excluded, up to
+    /// `max_backtracks` times before giving up.
+    pub fn solve(
+        &self,
+        random: &mut Random,
+        max_backtracks: usize,
+    ) -> Result<Vec<usize>, WfcError> {
+        let all_tiles = match self.tile_set.len() {
+            0 => return Err(WfcError::Contradiction),
+            MAX_TILES => u128::MAX,
+            len => (1u128 << len) - 1,
+        };
+        let cell_count = (self.dimensions.x * self.dimensions.y * self.dimensions.z) as usize;
+        let mut cells = vec![all_tiles; cell_count];
+
+        // Constrain the edges of the grid to the boundary socket.
+        if let Some(boundary) = self.boundary {
+            for (cell, possibilities) in cells.iter_mut().enumerate() {
+                for face in 0..6 {
+                    if self.neighbor(cell, face).is_none() {
+                        *possibilities &= (0..self.tile_set.len())
+                            .filter(|tile| self.tile_set.tiles[*tile].sockets[face] == boundary)
+                            .fold(0, |mask, tile| mask | (1 << tile));
+                    }
+                }
+            }
+        }
+
+        if cells.contains(&0) || !self.propagate(&mut cells, (0..cell_count).collect()) {
+            return Err(WfcError::Contradiction);
+        }
+
+        // Each choice saves the state before it, so that it can be undone.
+        let mut choices: Vec<(Vec<u128>, usize, usize)> = Vec::new();
+        let mut backtracks = 0;
+
+        loop {
+            // Collapse the least certain cell, choosing randomly between ties.
+            let least = cells
+                .iter()
+                .map(|possibilities| possibilities.count_ones())
+                .filter(|count| *count > 1)
+                .min();
+
+            let least = match least {
+                Some(least) => least,
+                None => {
+                    return Ok(cells
+                        .iter()
+                        .map(|cell| cell.trailing_zeros() as usize)
+                        .collect())
+                }
+            };
+
+            let ties = (0..cell_count)
+                .filter(|cell| cells[*cell].count_ones() == least)
+                .collect::<Vec<_>>();
+            let cell = ties[random.range_i32(0, ties.len() as i32) as usize];
+            let tile = self.choose(cells[cell], random);
+
+            choices.push((cells.clone(), cell, tile));
+            cells[cell] = 1 << tile;
+
+            if self.propagate(&mut cells, vec![cell]) {
+                continue;
+            }
+
+            // Undo choices until one can be excluded without a contradiction.
+            loop {
+                let (saved, cell, tile) = match choices.pop() {
+                    Some(choice) => choice,
+                    None => return Err(WfcError::Contradiction),
+                };
+
+                backtracks += 1;
+                if backtracks > max_backtracks {
+                    return Err(WfcError::BacktrackLimit(max_backtracks));
+                }
+
+                cells = saved;
+                cells[cell] &= !(1 << tile);
+
+                if cells[cell] != 0 && self.propagate(&mut cells, vec![cell]) {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// Stamps structures solved by wave function collapse into chunks.
+///
+/// Each structure fits within a single chunk, and is solved from randomness
+/// seeded by the chunk's position. Chunks whose structure can't be solved are
+/// left without one.
+pub struct StructureStep {
+    tile_set: TileSet,
+    /// Size of each structure, in tiles.
+    dimensions: IVec3,
+    /// Chance of each chunk containing a structure.
+    chance: f64,
+    /// Block-space heights between which structures are placed.
+    height_range: (i32, i32),
+    /// Socket assumed beyond the edges of each structure, if any.
+    boundary: Option<Socket>,
+    max_backtracks: usize,
+}
+
+impl StructureStep {
+    pub fn new(
+        tile_set: TileSet,
+        dimensions: IVec3,
+        chance: f64,
+        height_range: (i32, i32),
+    ) -> Self {
+        let extent = tile_set.size() * dimensions;
+        assert!(
+            extent.cmple(IVec3::splat(CHUNK_SIZE)).all(),
+            "Structures must fit within a single chunk."
+        );
+
+        Self {
+            tile_set,
+            dimensions,
+            chance,
+            height_range,
+            boundary: None,
+            max_backtracks: 64,
+        }
+    }
+
+    /// Underground dungeons of corridors, built from the `core` blocks in `registry`.
+    pub fn dungeons(registry: &BlockRegistry) -> Self {
+        // Closing off the edges keeps corridors from leading out of the dungeon.
+        Self::new(
+            TileSet::dungeon(registry),
+            IVec3::new(6, 1, 6),
+            0.05,
+            (-128, -24),
+        )
+        .with_boundary(TileSet::DUNGEON_CLOSED)
+    }
+
+    pub fn with_boundary(mut self, boundary: Socket) -> Self {
+        self.boundary = Some(boundary);
+        self
+    }
+
+    pub fn with_max_backtracks(mut self, max_backtracks: usize) -> Self {
+        self.max_backtracks = max_backtracks;
+        self
+    }
+
+    fn stamp(&self, tiles: &[usize], corner: IVec3, blocks: &mut [Block]) {
+        let size = self.tile_set.size();
+
+        for (cell, tile) in tiles.iter().enumerate() {
+            let tile = self.tile_set.tile(*tile);
+            let tile_corner = corner + (grid_position(self.dimensions, cell) * size);
+
+            for (index, block) in tile.blocks.iter().enumerate() {
+                let block = match block {
+                    Some(block) => *block,
+                    None => continue,
+                };
+
+                let local = tile_corner + grid_position(size, index);
+
+                blocks[(local.x + (local.z * CHUNK_SIZE) + (local.y * CHUNK_SIZE_SQUARED))
+                    as usize] = block;
+            }
+        }
+    }
+}
+
+impl ChunkGenerationStep for StructureStep {
+    fn gen_pass(&self, context: &ChunkGenerationContext, blocks: &mut dyn BlockStorageMut) {
+        let origin = context.origin();
+        let extent = self.tile_set.size() * self.dimensions;
+
+        let min_y = (self.height_range.0 - origin.y).max(0);
+        let max_y = (self.height_range.1 - origin.y).min(CHUNK_SIZE - extent.y);
+        if min_y > max_y {
+            return;
+        }
+
+        let mut random = Random::at(context.seed, STRUCTURE_STREAM, context.position.as_ivec3());
+        if !random.chance(self.chance) {
+            return;
+        }
+
+        let corner = IVec3::new(
+            random.range_i32(0, CHUNK_SIZE - extent.x + 1),
+            random.range_i32(min_y, max_y + 1),
+            random.range_i32(0, CHUNK_SIZE - extent.z + 1),
+        );
+
+        let solver = WaveFunctionCollapse::new(&self.tile_set, self.dimensions, self.boundary);
+        let tiles = match solver.solve(&mut random, self.max_backtracks) {
+            Ok(tiles) => tiles,
+            Err(error) => {
+                debug!(
+                    "Skipping structure in chunk {}: {}.",
+                    context.position, error
+                );
+                return;
+            }
+        };
+
+        let mut stamped = vec![Block::AIR; CHUNK_SIZE_CUBED as usize];
+        blocks.copy_to_slice(&mut stamped);
+        self.stamp(&tiles, corner, &mut stamped);
+        blocks.copy_from_slice(&stamped);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::chunk::{BlockStorage, ChunkPos, ChunkStorage};
+
+    const HORIZONTAL_FACES: [usize; 4] = [0, 2, 3, 5];
+
+    // Single block tiles whose horizontal faces are open (`1`) or closed (`0`), with
+    //  an odd count of open faces. As every open face must meet another, no grid of
+    //  an odd count of cells can be filled with them, but propagation alone only
+    //  notices once the grid is nearly full.
+    fn odd_tiles() -> TileSet {
+        let mut tile_set = TileSet::new(IVec3::ONE);
+
+        for open in (0..16u32).filter(|open| (open.count_ones() % 2) == 1) {
+            let mut sockets = [0; 6];
+            for (bit, face) in HORIZONTAL_FACES.iter().enumerate() {
+                sockets[*face] = (open >> bit) & 1;
+            }
+
+            tile_set.add_tile(Tile {
+                name: format!("odd {:04b}", open),
+                blocks: vec![Some(Block::AIR)],
+                sockets,
+                weight: 1.0,
+            });
+        }
+
+        tile_set
+    }
+
+    fn assert_adjacency(solver: &WaveFunctionCollapse, tiles: &[usize]) {
+        for (cell, tile) in tiles.iter().enumerate() {
+            for face in 0..6 {
+                let socket = solver.tile_set.tile(*tile).sockets[face];
+
+                match solver.neighbor(cell, face) {
+                    Some(neighbor) => assert_eq!(
+                        socket,
+                        solver.tile_set.tile(tiles[neighbor]).sockets[opposite_face(face)]
+                    ),
+                    None => assert_eq!(Some(socket), solver.boundary),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn contradicting_choices_are_backtracked() {
+        // A rare tile without open faces makes any grid solvable, but the solver
+        //  seldom chooses it before backtracking.
+        let tile_set = odd_tiles().with_tile(Tile {
+            name: "closed".to_string(),
+            blocks: vec![Some(Block::AIR)],
+            sockets: [0; 6],
+            weight: 0.01,
+        });
+        let solver = WaveFunctionCollapse::new(&tile_set, IVec3::new(7, 1, 7), Some(0));
+
+        let mut backtracked = 0;
+        for seed in 0..200 {
+            if solver.solve(&mut Random::new(seed), 0) == Err(WfcError::BacktrackLimit(0)) {
+                backtracked += 1;
+            }
+
+            let tiles = solver.solve(&mut Random::new(seed), 10_000).unwrap();
+            assert_adjacency(&solver, &tiles);
+        }
+        assert!(backtracked > 0, "no solve needed to backtrack");
+
+        // The same seed always yields the same structure.
+        assert_eq!(
+            solver.solve(&mut Random::new(1), 10_000),
+            solver.solve(&mut Random::new(1), 10_000)
+        );
+    }
+
+    #[test]
+    fn unsolvable_grids_are_contradictions() {
+        let tile_set = odd_tiles();
+        let solver = WaveFunctionCollapse::new(&tile_set, IVec3::new(3, 1, 3), Some(0));
+        assert_eq!(
+            solver.solve(&mut Random::new(1), usize::MAX),
+            Err(WfcError::Contradiction)
+        );
+        assert!(matches!(
+            solver.solve(&mut Random::new(1), 4),
+            Err(WfcError::BacktrackLimit(4))
+        ));
+
+        let empty = TileSet::new(IVec3::ONE);
+        let solver = WaveFunctionCollapse::new(&empty, IVec3::ONE, None);
+        assert_eq!(
+            solver.solve(&mut Random::new(1), 0),
+            Err(WfcError::Contradiction)
+        );
+    }
+
+    #[test]
+    fn unsolvable_structures_are_skipped() {
+        let step = StructureStep::new(odd_tiles(), IVec3::new(3, 1, 3), 1.0, (-64, 64))
+            .with_boundary(0)
+            .with_max_backtracks(usize::MAX);
+        let stone = Block::new(2, 0, 0);
+
+        let mut storage = ChunkStorage::new(stone);
+        let context = ChunkGenerationContext {
+            position: ChunkPos::new(0, 0, 0),
+            seed: 1,
+        };
+        step.gen_pass(&context, &mut storage);
+
+        assert_eq!(storage.distinct_len(), 1);
+        assert_eq!(storage.get(0), stone);
+    }
+}
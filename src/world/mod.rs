@@ -3,6 +3,9 @@ use specs::{Component, DenseVecStorage};
 
 pub mod block;
 pub mod chunk;
+pub mod generation;
+pub mod physics;
+pub mod picking;
 
 #[derive(Debug, Component)]
 #[storage(DenseVecStorage)]
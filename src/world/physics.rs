@@ -0,0 +1,276 @@
+use super::{
+    block::{Attributes, BLOCK_REGISTRY},
+    chunk::{BlockPos, BlockStorage, Chunk, ChunkMap, ChunkPos, ChunkStorage},
+    Transform,
+};
+use glam::Vec3;
+use specs::{Component, HashMapStorage};
+
+/// Leeway given to bounds lying on block borders, so that floating point error
+///  doesn't count blocks they merely touch as overlapping them.
+const BORDER_EPSILON: f32 = 1e-4;
+
+/// Axis-aligned bounding box.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Aabb {
+    pub fn new(min: Vec3, max: Vec3) -> Self {
+        assert!(
+            min.cmple(max).all(),
+            "Minimum of bounds must not exceed their maximum."
+        );
+
+        Self { min, max }
+    }
+
+    pub fn translated(&self, offset: Vec3) -> Self {
+        Self {
+            min: self.min + offset,
+            max: self.max + offset,
+        }
+    }
+
+    /// Range of block coordinates the bounds overlap along `axis`.
+    fn block_range(&self, axis: usize) -> std::ops::RangeInclusive<i32> {
+        ((self.min[axis] + BORDER_EPSILON).floor() as i32)
+            ..=((self.max[axis] - BORDER_EPSILON).ceil() as i32 - 1)
+    }
+}
+
+/// Linear velocity of an entity, in blocks per second.
+#[derive(Debug, Clone, Copy, Default, Component)]
+#[storage(HashMapStorage)]
+pub struct Velocity(pub Vec3);
+
+/// Bounds an entity collides with blocks by, relative to its transform's position.
+#[derive(Debug, Component)]
+#[storage(HashMapStorage)]
+pub struct Collider {
+    pub bounds: Aabb,
+    /// Tallest ledge the entity can step onto without jumping, in blocks.
+    pub step_height: f32,
+    grounded: bool,
+}
+
+impl Collider {
+    pub fn new(bounds: Aabb) -> Self {
+        Self {
+            bounds,
+            step_height: 0.0,
+            grounded: false,
+        }
+    }
+
+    pub fn with_step_height(mut self, step_height: f32) -> Self {
+        self.step_height = step_height;
+        self
+    }
+
+    /// Whether the entity was resting on a block as of the last run of
+    /// `PhysicsSystem`.
+    pub const fn grounded(&self) -> bool {
+        self.grounded
+    }
+}
+
+/// Looks up whether blocks are collideable, caching the chunk last looked in.
+struct CollisionQuery<'a, 'b> {
+    chunk_map: &'b ChunkMap,
+    chunks: &'b specs::ReadStorage<'a, Chunk>,
+    cached: Option<(ChunkPos, &'b ChunkStorage)>,
+}
+
+impl<'a, 'b> CollisionQuery<'a, 'b> {
+    /// Blocks in chunks which aren't loaded and generated are collideable, so
+    ///  that entities wait for the world to load rather than fall through it.
+    fn is_collideable(&mut self, position: BlockPos) -> bool {
+        let (chunk_position, local) = position.split();
+        let blocks = match self.cached {
+            Some((cached_position, blocks)) if cached_position == chunk_position => blocks,
+            _ => {
+                let chunks = self.chunks;
+                match self
+                    .chunk_map
+                    .get(chunk_position)
+                    .and_then(|entity| chunks.get(entity))
+                    .and_then(Chunk::blocks)
+                {
+                    Some(blocks) => {
+                        self.cached = Some((chunk_position, blocks));
+                        blocks
+                    }
+                    None => return true,
+                }
+            }
+        };
+
+        BLOCK_REGISTRY
+            .get_block_attributes(blocks.get(local.index().get()).id())
+            .contains(Attributes::COLLIDEABLE)
+    }
+
+    /// Sweeps `bounds` by `distance` along `axis`, returning how far they can move
+    ///  before hitting a collideable block.
+    fn sweep(&mut self, bounds: &Aabb, axis: usize, distance: f32) -> f32 {
+        if distance == 0.0 {
+            return 0.0;
+        }
+
+        // Layers of blocks along the axis which the leading face of the bounds
+        //  passes through, nearest first. Blocks the bounds already overlap are
+        //  skipped, so that embedded entities can move out of them.
+        let (first, last, step) = if distance > 0.0 {
+            (
+                (bounds.max[axis] - BORDER_EPSILON).ceil() as i32,
+                (bounds.max[axis] + distance - BORDER_EPSILON).ceil() as i32 - 1,
+                1,
+            )
+        } else {
+            (
+                (bounds.min[axis] + BORDER_EPSILON).floor() as i32 - 1,
+                (bounds.min[axis] + distance + BORDER_EPSILON).floor() as i32,
+                -1,
+            )
+        };
+
+        let (axis_u, axis_v) = ((axis + 1) % 3, (axis + 2) % 3);
+        for layer in (0..=((last - first) * step)).map(|traversed| first + (traversed * step)) {
+            for u in bounds.block_range(axis_u) {
+                for v in bounds.block_range(axis_v) {
+                    let mut position = [0; 3];
+                    position[axis] = layer;
+                    position[axis_u] = u;
+                    position[axis_v] = v;
+
+                    if self.is_collideable(BlockPos::new(position[0], position[1], position[2])) {
+                        return if distance > 0.0 {
+                            distance.min((layer as f32) - bounds.max[axis])
+                        } else {
+                            distance.max(((layer + 1) as f32) - bounds.min[axis])
+                        };
+                    }
+                }
+            }
+        }
+
+        distance
+    }
+
+    /// Moves `bounds` along the x and z axes, returning the moved bounds and the
+    ///  distance travelled along each.
+    fn move_horizontal(&mut self, mut bounds: Aabb, motion: Vec3) -> (Aabb, Vec3) {
+        let mut moved = Vec3::ZERO;
+        for axis in [0, 2] {
+            moved[axis] = self.sweep(&bounds, axis, motion[axis]);
+            bounds = bounds.translated(unit(axis) * moved[axis]);
+        }
+
+        (bounds, moved)
+    }
+}
+
+fn unit(axis: usize) -> Vec3 {
+    let mut unit = Vec3::ZERO;
+    unit[axis] = 1.0;
+    unit
+}
+
+/// Applies gravity to entities with a `Velocity` and `Collider`, and moves them
+/// by their velocity without passing through collideable blocks.
+///
+/// Entities are moved along one axis at a time, vertical first. Grounded entities
+/// blocked horizontally by a ledge no taller than their step height are lifted
+/// onto it.
+pub struct PhysicsSystem {
+    gravity: Vec3,
+    /// Longest time simulated in a single run, so that long frames don't let
+    ///  entities build up speed in free fall.
+    max_delta: f32,
+}
+
+impl PhysicsSystem {
+    pub fn new() -> Self {
+        Self {
+            gravity: Vec3::new(0.0, -28.0, 0.0),
+            max_delta: 0.1,
+        }
+    }
+
+    /// Acceleration applied to every entity, in blocks per second squared.
+    pub fn with_gravity(mut self, gravity: Vec3) -> Self {
+        self.gravity = gravity;
+        self
+    }
+}
+
+impl<'a> specs::System<'a> for PhysicsSystem {
+    type SystemData = (
+        specs::ReadExpect<'a, crate::time::DeltaTime>,
+        specs::Read<'a, ChunkMap>,
+        specs::ReadStorage<'a, Chunk>,
+        specs::WriteStorage<'a, Transform>,
+        specs::WriteStorage<'a, Velocity>,
+        specs::WriteStorage<'a, Collider>,
+    );
+
+    fn run(
+        &mut self,
+        (delta, chunk_map, chunks, mut transforms, mut velocities, mut colliders): Self::SystemData,
+    ) {
+        use specs::Join;
+
+        let delta = delta.0.as_secs_f32().min(self.max_delta);
+        let mut query = CollisionQuery {
+            chunk_map: &chunk_map,
+            chunks: &chunks,
+            cached: None,
+        };
+
+        for (transform, Velocity(velocity), collider) in
+            (&mut transforms, &mut velocities, &mut colliders).join()
+        {
+            *velocity += self.gravity * delta;
+            let motion = *velocity * delta;
+            let was_grounded = collider.grounded;
+
+            let bounds = collider.bounds.translated(transform.pos);
+            let moved_y = query.sweep(&bounds, 1, motion.y);
+            let vertical = bounds.translated(Vec3::Y * moved_y);
+            collider.grounded = motion.y < 0.0 && moved_y > motion.y;
+            if moved_y != motion.y {
+                velocity.y = 0.0;
+            }
+
+            let (mut bounds, mut moved) = query.move_horizontal(vertical, motion);
+
+            let blocked = moved.x != motion.x || moved.z != motion.z;
+            if blocked && (was_grounded || collider.grounded) && collider.step_height > 0.0 {
+                // Retry the horizontal move from atop the ledge, keeping whichever
+                //  attempt got further.
+                let lifted = query.sweep(&vertical, 1, collider.step_height);
+                let (stepped, stepped_moved) =
+                    query.move_horizontal(vertical.translated(Vec3::Y * lifted), motion);
+                let dropped = query.sweep(&stepped, 1, -lifted);
+
+                if stepped_moved.x.abs() + stepped_moved.z.abs() > moved.x.abs() + moved.z.abs() {
+                    bounds = stepped.translated(Vec3::Y * dropped);
+                    moved = stepped_moved;
+                    collider.grounded = true;
+                }
+            }
+
+            if moved.x != motion.x {
+                velocity.x = 0.0;
+            }
+            if moved.z != motion.z {
+                velocity.z = 0.0;
+            }
+
+            transform.pos = bounds.min - collider.bounds.min;
+        }
+    }
+}
@@ -0,0 +1,101 @@
+use super::{
+    block::Block,
+    chunk::{RaycastFilter, RaycastHit, WorldBlocks},
+    Transform,
+};
+use specs::{Component, HashMapStorage};
+
+/// Edit made to the block targeted by a `BlockPicker`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockAction {
+    /// Replaces the targeted block with air.
+    Break,
+    /// Places the picker's block against the targeted face.
+    Place,
+}
+
+/// Targets the block an entity is looking at, along the forward (-Z) axis of its
+/// transform, and applies queued actions to it.
+#[derive(Debug, Component)]
+#[storage(HashMapStorage)]
+pub struct BlockPicker {
+    /// Furthest a block may be targeted from, in blocks.
+    pub reach: f32,
+    /// Block placed by `BlockAction::Place`.
+    pub block: Block,
+    pub filter: RaycastFilter,
+    target: Option<RaycastHit>,
+    actions: Vec<BlockAction>,
+}
+
+impl BlockPicker {
+    pub fn new(reach: f32, block: Block) -> Self {
+        Self {
+            reach,
+            block,
+            filter: RaycastFilter::Solid,
+            target: None,
+            actions: Vec::new(),
+        }
+    }
+
+    /// Targets only blocks `filter` stops at, e.g. to pick through transparent blocks.
+    pub fn with_filter(mut self, filter: RaycastFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Block targeted as of the last run of `BlockPickingSystem`.
+    pub const fn target(&self) -> Option<RaycastHit> {
+        self.target
+    }
+
+    /// Queues `action`, to be applied to the targeted block by the next run of
+    /// `BlockPickingSystem`.
+    pub fn queue_action(&mut self, action: BlockAction) {
+        self.actions.push(action);
+    }
+}
+
+/// Updates the target of every `BlockPicker`, and applies their queued actions.
+pub struct BlockPickingSystem;
+
+impl<'a> specs::System<'a> for BlockPickingSystem {
+    type SystemData = (
+        specs::ReadStorage<'a, Transform>,
+        specs::WriteStorage<'a, BlockPicker>,
+        WorldBlocks<'a>,
+    );
+
+    fn run(&mut self, (transforms, mut pickers, mut blocks): Self::SystemData) {
+        use specs::Join;
+
+        for (transform, picker) in (&transforms, &mut pickers).join() {
+            let forward = transform.rot.mul_vec3(-glam::Vec3::Z);
+            let (reach, filter) = (picker.reach, picker.filter);
+            let raycast =
+                |blocks: &WorldBlocks| blocks.raycast(transform.pos, forward, reach, filter);
+
+            // Each action sees the edits of those before it.
+            for action in std::mem::take(&mut picker.actions) {
+                let target = match raycast(&blocks) {
+                    Some(target) => target,
+                    None => continue,
+                };
+
+                let edit = match action {
+                    BlockAction::Break => blocks.set_block(target.position, Block::AIR),
+                    // Rays starting within a block have no face to place against.
+                    BlockAction::Place if target.face.is_empty() => continue,
+                    BlockAction::Place => blocks.set_block(target.adjacent(), picker.block),
+                };
+
+                if let Err(error) = edit {
+                    warn!("Failed to apply {:?}: {}", action, error);
+                }
+            }
+
+            picker.target = raycast(&blocks);
+        }
+    }
+}